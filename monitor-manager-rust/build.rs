@@ -1,5 +1,24 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
     if cfg!(target_os = "windows") {
         embed_resource::compile("icon.rc", embed_resource::NONE);
     }
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date());
+}
+
+/// Today's UTC date as YYYY-MM-DD, without pulling chrono into the build.
+fn build_date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / 86_400) as i64;
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
 }
@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use native_windows_derive::NwgUi;
+use native_windows_gui as nwg;
+use nwg::NativeUi;
+use windows::Win32::Foundation::{GlobalFree, HANDLE, HWND};
+use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData};
+use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+
+use crate::{lock, AppState};
+use monitor_manager::logging;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const BUILD_DATE: &str = env!("BUILD_DATE");
+/// Standard clipboard format; the windows crate only has it behind the OLE feature.
+const CF_UNICODETEXT: u32 = 13;
+const LOG_LINES: usize = 50;
+
+#[derive(Default, NwgUi)]
+pub struct AboutDialog {
+    #[nwg_control(size: (560, 420), position: (320, 320), title: "About Monitor Manager", flags: "WINDOW|VISIBLE", icon: Some(&data.window_icon))]
+    #[nwg_events( OnWindowClose: [AboutDialog::close] )]
+    window: nwg::Window,
+
+    #[nwg_resource(source_bin: Some(include_bytes!("../icon.ico")))]
+    window_icon: nwg::Icon,
+
+    #[nwg_resource(family: "Segoe UI", size: 13)]
+    ui_font: nwg::Font,
+
+    #[nwg_resource(family: "Segoe UI", size: 18, weight: 700)]
+    title_font: nwg::Font,
+
+    #[nwg_layout(parent: window, spacing: 4, margin: [10, 10, 10, 10])]
+    layout: nwg::GridLayout,
+
+    #[nwg_control(text: "", font: Some(&data.title_font))]
+    #[nwg_layout_item(layout: layout, row: 0, col: 0, col_span: 4)]
+    version_label: nwg::Label,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 0, col_span: 4)]
+    build_label: nwg::Label,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 2, col: 0, col_span: 4)]
+    config_label: nwg::Label,
+
+    #[nwg_control(text: "", readonly: true, font: Some(&data.ui_font), flags: "VISIBLE|VSCROLL|AUTOVSCROLL")]
+    #[nwg_layout_item(layout: layout, row: 3, col: 0, col_span: 4, row_span: 5)]
+    monitors_box: nwg::TextBox,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 0, col_span: 2)]
+    copied_label: nwg::Label,
+
+    #[nwg_control(text: "Copy Diagnostics", font: Some(&data.ui_font), size: (130, 30))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 2)]
+    #[nwg_events( OnButtonClick: [AboutDialog::copy_diagnostics] )]
+    copy_button: nwg::Button,
+
+    #[nwg_control(text: "Close", font: Some(&data.ui_font), size: (110, 30))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 3)]
+    #[nwg_events( OnButtonClick: [AboutDialog::close] )]
+    close_button: nwg::Button,
+
+    diagnostics: RefCell<String>,
+}
+
+impl AboutDialog {
+    fn copy_diagnostics(&self) {
+        let hwnd = self.window.handle.hwnd().map_or(HWND::default(), |hwnd| HWND(hwnd.cast()));
+        match copy_to_clipboard(hwnd, &self.diagnostics.borrow()) {
+            Ok(()) => self.copied_label.set_text("Copied to the clipboard"),
+            Err(e) => {
+                log::warn!("Could not copy the diagnostics: {}", e.message());
+                self.copied_label.set_text("Could not copy to the clipboard");
+            }
+        }
+    }
+
+    fn close(&self) {
+        nwg::stop_thread_dispatch();
+    }
+}
+
+/// Shows the version, build date, config path and monitors until closed.
+pub fn show(state: &Arc<Mutex<AppState>>) {
+    let (config, watcher, status, monitor_manager) = {
+        let state = lock(state);
+        (state.config.clone(), state.watcher.to_string(), state.status.clone(), state.monitor_manager.clone())
+    };
+    let monitors = monitor_manager.run(|manager| manager.get_all_monitors_detailed());
+    let monitor_lines: Vec<String> = monitors
+        .iter()
+        .map(|monitor| format!("{}: {}", monitor.device_name, monitor.label()))
+        .collect();
+
+    let app = AboutDialog::build_ui(Default::default()).expect("Failed to build UI");
+    app.version_label.set_text(&format!("Monitor Manager {}", VERSION));
+    app.build_label.set_text(&format!("Built {}", BUILD_DATE));
+    app.config_label.set_text(&format!("Config: {}", config.path().display()));
+    app.monitors_box.set_text(&monitor_lines.join("\r\n"));
+
+    let config_text = serde_json::to_string_pretty(&config).unwrap_or_else(|e| format!("unreadable: {}", e));
+    let monitors_text = serde_json::to_string_pretty(&monitors).unwrap_or_else(|e| format!("unreadable: {}", e));
+    *app.diagnostics.borrow_mut() = [
+        format!("Monitor Manager {} (built {})", VERSION, BUILD_DATE),
+        format!("Config: {}", config.path().display()),
+        format!("Log: {}", logging::log_file_path().display()),
+        format!("Watcher: {} ({})", watcher, status),
+        String::new(),
+        "== Config ==".to_string(),
+        config_text,
+        String::new(),
+        "== Monitors ==".to_string(),
+        monitors_text,
+        String::new(),
+        format!("== Last {} log lines ==", LOG_LINES),
+        last_log_lines(),
+    ]
+    .join("\r\n");
+
+    nwg::dispatch_thread_events();
+}
+
+fn last_log_lines() -> String {
+    let Ok(content) = fs::read_to_string(logging::log_file_path()) else {
+        return "(no log file)".to_string();
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(LOG_LINES)..].join("\r\n")
+}
+
+/// Puts `text` on the clipboard as Unicode text.
+fn copy_to_clipboard(owner: HWND, text: &str) -> windows::core::Result<()> {
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        let memory = GlobalAlloc(GMEM_MOVEABLE, wide.len() * 2)?;
+        let target = GlobalLock(memory) as *mut u16;
+        if target.is_null() {
+            let _ = GlobalFree(memory);
+            return Err(windows::core::Error::from_win32());
+        }
+        std::ptr::copy_nonoverlapping(wide.as_ptr(), target, wide.len());
+        let _ = GlobalUnlock(memory);
+
+        if let Err(e) = OpenClipboard(owner) {
+            let _ = GlobalFree(memory);
+            return Err(e);
+        }
+        let result = EmptyClipboard().and_then(|()| SetClipboardData(CF_UNICODETEXT, HANDLE(memory.0)));
+        let _ = CloseClipboard();
+        // Once set, the clipboard owns the memory.
+        if result.is_err() {
+            let _ = GlobalFree(memory);
+        }
+        result.map(|_| ())
+    }
+}
@@ -0,0 +1,86 @@
+use std::ffi::c_void;
+
+use windows::core::{interface, IUnknown, IUnknown_Vtbl, GUID, HRESULT, HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+use windows::Win32::Media::Audio::{
+    eCommunications, eConsole, eMultimedia, eRender, ERole, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator,
+    DEVICE_STATE_ACTIVE,
+};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ,
+};
+
+/// The undocumented class Sound settings uses to change the default device.
+const POLICY_CONFIG_CLIENT: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
+
+/// Only `set_default_endpoint` is called; the other methods are declared to
+/// keep the vtable in order.
+#[interface("f8679f50-850a-41cf-9c72-430f290290c8")]
+unsafe trait IPolicyConfig: IUnknown {
+    fn get_mix_format(&self, device_id: PCWSTR, format: *mut *mut c_void) -> HRESULT;
+    fn get_device_format(&self, device_id: PCWSTR, default: i32, format: *mut *mut c_void) -> HRESULT;
+    fn reset_device_format(&self, device_id: PCWSTR) -> HRESULT;
+    fn set_device_format(&self, device_id: PCWSTR, endpoint: *mut c_void, mix: *mut c_void) -> HRESULT;
+    fn get_processing_period(&self, device_id: PCWSTR, default: i32, period: *mut i64, min: *mut i64) -> HRESULT;
+    fn set_processing_period(&self, device_id: PCWSTR, period: *mut i64) -> HRESULT;
+    fn get_share_mode(&self, device_id: PCWSTR, mode: *mut c_void) -> HRESULT;
+    fn set_share_mode(&self, device_id: PCWSTR, mode: *mut c_void) -> HRESULT;
+    fn get_property_value(&self, device_id: PCWSTR, key: *const c_void, value: *mut c_void) -> HRESULT;
+    fn set_property_value(&self, device_id: PCWSTR, key: *const c_void, value: *mut c_void) -> HRESULT;
+    fn set_default_endpoint(&self, device_id: PCWSTR, role: ERole) -> HRESULT;
+    fn set_endpoint_visibility(&self, device_id: PCWSTR, visible: i32) -> HRESULT;
+}
+
+/// An active audio output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaybackDevice {
+    /// Endpoint ID, e.g. `{0.0.0.00000000}.{...}`; what profiles store.
+    pub id: String,
+    /// E.g. "Speakers (Realtek High Definition Audio)".
+    pub name: String,
+}
+
+/// Every active playback device, in the order Windows lists them.
+pub fn playback_devices() -> windows::core::Result<Vec<PlaybackDevice>> {
+    unsafe {
+        let devices = enumerator()?.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        let mut found = Vec::new();
+        for idx in 0..devices.GetCount()? {
+            let device = devices.Item(idx)?;
+            let name = device.OpenPropertyStore(STGM_READ)?.GetValue(&PKEY_Device_FriendlyName)?.to_string();
+            found.push(PlaybackDevice { id: device_id(&device)?, name });
+        }
+        Ok(found)
+    }
+}
+
+/// The endpoint ID of the current default playback device.
+pub fn default_playback_device() -> windows::core::Result<String> {
+    unsafe { device_id(&enumerator()?.GetDefaultAudioEndpoint(eRender, eConsole)?) }
+}
+
+/// Makes the endpoint `id` the default playback device for every role, as
+/// Sound settings does.
+pub fn set_default_playback_device(id: &str) -> windows::core::Result<()> {
+    let id = HSTRING::from(id);
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let policy: IPolicyConfig = CoCreateInstance(&POLICY_CONFIG_CLIENT, None, CLSCTX_ALL)?;
+        for role in [eConsole, eMultimedia, eCommunications] {
+            policy.set_default_endpoint(PCWSTR(id.as_ptr()), role).ok()?;
+        }
+    }
+    Ok(())
+}
+
+unsafe fn enumerator() -> windows::core::Result<IMMDeviceEnumerator> {
+    let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+}
+
+unsafe fn device_id(device: &IMMDevice) -> windows::core::Result<String> {
+    let id: PWSTR = device.GetId()?;
+    let result = id.to_string().map_err(|_| windows::core::Error::from_win32());
+    CoTaskMemFree(Some(id.0 as *const c_void));
+    result
+}
@@ -0,0 +1,120 @@
+use std::ffi::c_void;
+use std::fmt;
+use std::io;
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+use windows::Win32::System::Registry::{
+    RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ,
+};
+
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+const VALUE_NAME: &str = "MonitorManager";
+
+type Result<T> = std::result::Result<T, AutostartError>;
+
+#[derive(Debug)]
+pub enum AutostartError {
+    /// The path of the running exe couldn't be determined.
+    ExePath(io::Error),
+    Registry(windows::core::Error),
+}
+
+impl fmt::Display for AutostartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExePath(e) => write!(f, "could not find the running exe: {}", e),
+            Self::Registry(e) => write!(f, "could not update the Run key: {}", e.message()),
+        }
+    }
+}
+
+impl From<windows::core::Error> for AutostartError {
+    fn from(e: windows::core::Error) -> Self {
+        Self::Registry(e)
+    }
+}
+
+/// The command line registered under the Run key: the quoted exe path, plus
+/// `--portable` when this run was started with it.
+fn launch_command() -> Result<String> {
+    let exe = std::env::current_exe().map_err(AutostartError::ExePath)?;
+    let mut command = format!("\"{}\"", exe.display());
+    if std::env::args().any(|arg| arg == "--portable") {
+        command.push_str(" --portable");
+    }
+    Ok(command)
+}
+
+/// The registered command, or `None` when start-with-Windows is off.
+fn registered_command() -> Result<Option<String>> {
+    let (key, name) = (HSTRING::from(RUN_KEY), HSTRING::from(VALUE_NAME));
+    let mut size = 0u32;
+    let status = unsafe { RegGetValueW(HKEY_CURRENT_USER, &key, &name, RRF_RT_REG_SZ, None, None, Some(&mut size)) };
+    if status == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    status.ok()?;
+
+    let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            &name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            Some(&mut size),
+        )
+    }
+    .ok()?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(Some(String::from_utf16_lossy(&buffer[..len])))
+}
+
+pub fn is_enabled() -> Result<bool> {
+    Ok(registered_command()?.is_some())
+}
+
+/// Reads the registry at startup, pointing an existing entry back at this
+/// exe if it was moved since it was registered. Returns whether it's on.
+pub fn sync() -> Result<bool> {
+    let Some(registered) = registered_command()? else {
+        return Ok(false);
+    };
+    let command = launch_command()?;
+    if registered != command {
+        log::info!("Start with Windows pointed at {}, updating to {}", registered, command);
+        write_command(&command)?;
+    }
+    Ok(true)
+}
+
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    if enabled {
+        write_command(&launch_command()?)
+    } else {
+        let status = unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, &HSTRING::from(RUN_KEY), &HSTRING::from(VALUE_NAME)) };
+        if status == ERROR_FILE_NOT_FOUND {
+            return Ok(());
+        }
+        Ok(status.ok()?)
+    }
+}
+
+fn write_command(command: &str) -> Result<()> {
+    let data: Vec<u16> = command.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(RUN_KEY),
+            &HSTRING::from(VALUE_NAME),
+            REG_SZ.0,
+            Some(data.as_ptr() as *const c_void),
+            (data.len() * 2) as u32,
+        )
+    }
+    .ok()?;
+    Ok(())
+}
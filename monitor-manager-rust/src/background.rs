@@ -0,0 +1,212 @@
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE, HWND, LPARAM, WPARAM};
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+};
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows::Win32::System::Threading::{
+    GetCurrentProcessId, IsProcessCritical, OpenProcess, OpenThread, ResumeThread, SuspendThread,
+    PROCESS_QUERY_LIMITED_INFORMATION, THREAD_SUSPEND_RESUME,
+};
+use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetWindowThreadProcessId, PostMessageW, WM_CLOSE};
+
+use crate::config::{MatchMode, SuspendAction, SuspendRule};
+use crate::matcher::TargetPattern;
+
+/// PIDs up to this are the idle and System processes.
+const SYSTEM_PID: u32 = 4;
+
+/// A background program put aside while a target runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetAside {
+    Suspended { pid: u32, name: String },
+    /// Started again from `exe` on restore.
+    Closed { exe: PathBuf, name: String },
+}
+
+/// Suspends or closes every process matching one of `rules`, as its rule
+/// says. Processes matching `targets`, this process and system processes
+/// (other sessions, critical ones) are never touched. Each action is logged;
+/// one that fails is skipped. Returns what [`bring_back`] needs to undo it.
+pub fn set_aside(rules: &[SuspendRule], targets: &[TargetPattern], mode: MatchMode) -> Vec<SetAside> {
+    let patterns: Vec<(TargetPattern, MatchMode, SuspendAction)> = rules
+        .iter()
+        .filter_map(|rule| match TargetPattern::parse(&rule.process) {
+            // A bare exe name can't match a full path.
+            Ok(pattern) if rule.process.contains(['\\', '/']) => Some((pattern, MatchMode::FullPath, rule.action)),
+            Ok(pattern) => Some((pattern, MatchMode::FileName, rule.action)),
+            Err(e) => {
+                log::warn!("Not setting aside {}: {}", rule.process, e);
+                None
+            }
+        })
+        .collect();
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet),
+    );
+    let own_pid = unsafe { GetCurrentProcessId() };
+    let own_session = session_of(own_pid);
+    let mut set_aside = Vec::new();
+    for (pid, process) in system.processes() {
+        let (exe, name) = (process.exe(), process.name());
+        let Some(&(_, _, action)) = patterns.iter().find(|(pattern, mode, _)| pattern.matches(exe, name, *mode)) else {
+            continue;
+        };
+        let (pid, name) = (pid.as_u32(), name.to_string_lossy().into_owned());
+        let refusal = if pid <= SYSTEM_PID || pid == own_pid {
+            Some("a system process")
+        } else if targets.iter().any(|target| target.matches(exe, process.name(), mode)) {
+            Some("a target")
+        } else if session_of(pid) != own_session {
+            Some("in another session")
+        } else if is_critical(pid) {
+            Some("protected or critical")
+        } else {
+            None
+        };
+        if let Some(refusal) = refusal {
+            log::info!("Leaving {} ({}) alone: it's {}", name, pid, refusal);
+            continue;
+        }
+
+        match action {
+            SuspendAction::Suspend => match for_each_thread(pid, |thread| unsafe { SuspendThread(thread) }) {
+                0 => log::warn!("Could not suspend {} ({})", name, pid),
+                threads => {
+                    log::info!("Suspended {} ({}), {} thread(s)", name, pid, threads);
+                    set_aside.push(SetAside::Suspended { pid, name });
+                }
+            },
+            SuspendAction::Close => {
+                let Some(exe) = exe else {
+                    log::warn!("Not closing {} ({}): its exe path can't be read to start it again", name, pid);
+                    continue;
+                };
+                match close_windows(pid) {
+                    0 => log::warn!("Could not close {} ({}): it has no windows", name, pid),
+                    windows => {
+                        log::info!("Asked {} ({}) to close, {} window(s)", name, pid, windows);
+                        set_aside.push(SetAside::Closed { exe: exe.to_path_buf(), name });
+                    }
+                }
+            }
+        }
+    }
+    set_aside
+}
+
+/// Resumes suspended processes and starts closed ones again, unless they
+/// are already running. Each is logged; failures are skipped.
+pub fn bring_back(set_aside: Vec<SetAside>) {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet),
+    );
+    for item in set_aside {
+        match item {
+            SetAside::Suspended { pid, name } => {
+                if system.process(Pid::from_u32(pid)).is_none() {
+                    log::info!("{} ({}) exited while suspended", name, pid);
+                    continue;
+                }
+                match for_each_thread(pid, |thread| unsafe { ResumeThread(thread) }) {
+                    0 => log::warn!("Could not resume {} ({})", name, pid),
+                    threads => log::info!("Resumed {} ({}), {} thread(s)", name, pid, threads),
+                }
+            }
+            SetAside::Closed { exe, name } => {
+                if system.processes().values().any(|process| process.exe() == Some(exe.as_path())) {
+                    log::info!("{} is running again already", name);
+                    continue;
+                }
+                match Command::new(&exe).current_dir(exe.parent().unwrap_or(Path::new("."))).spawn() {
+                    Ok(child) => log::info!("Started {} again ({})", name, child.id()),
+                    Err(e) => log::warn!("Could not start {} again: {}", exe.display(), e),
+                }
+            }
+        }
+    }
+}
+
+/// Calls `action` on every thread of `pid` and returns how many it worked
+/// on; both `SuspendThread` and `ResumeThread` return `u32::MAX` on failure.
+fn for_each_thread(pid: u32, action: impl Fn(HANDLE) -> u32) -> usize {
+    let Ok(snapshot) = (unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) }) else {
+        return 0;
+    };
+    let mut entry = THREADENTRY32 {
+        dwSize: mem::size_of::<THREADENTRY32>() as u32,
+        ..Default::default()
+    };
+    let mut done = 0;
+    let mut next = unsafe { Thread32First(snapshot, &mut entry) };
+    while next.is_ok() {
+        if entry.th32OwnerProcessID == pid {
+            if let Ok(thread) = unsafe { OpenThread(THREAD_SUSPEND_RESUME, false, entry.th32ThreadID) } {
+                if action(thread) != u32::MAX {
+                    done += 1;
+                }
+                unsafe {
+                    let _ = CloseHandle(thread);
+                }
+            }
+        }
+        next = unsafe { Thread32Next(snapshot, &mut entry) };
+    }
+    unsafe {
+        let _ = CloseHandle(snapshot);
+    }
+    done
+}
+
+/// Posts WM_CLOSE to every top-level window of `pid`, hidden ones included,
+/// since tray programs often only have those. Returns how many there were.
+fn close_windows(pid: u32) -> usize {
+    unsafe extern "system" fn visit(window: HWND, data: LPARAM) -> BOOL {
+        let (pid, closed) = &mut *(data.0 as *mut (u32, usize));
+        let mut owner = 0u32;
+        GetWindowThreadProcessId(window, Some(&mut owner));
+        if owner == *pid && PostMessageW(window, WM_CLOSE, WPARAM(0), LPARAM(0)).is_ok() {
+            *closed += 1;
+        }
+        true.into()
+    }
+
+    let mut found = (pid, 0usize);
+    unsafe {
+        let _ = EnumWindows(Some(visit), LPARAM(&mut found as *mut _ as isize));
+    }
+    found.1
+}
+
+fn session_of(pid: u32) -> Option<u32> {
+    let mut session = 0;
+    unsafe { ProcessIdToSessionId(pid, &mut session) }.ok().map(|()| session)
+}
+
+/// Processes that blue-screen the PC when they stop. One that can't even be
+/// queried, such as a protected process, is treated the same.
+fn is_critical(pid: u32) -> bool {
+    let Ok(process) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }) else {
+        return true;
+    };
+    let mut critical = BOOL::default();
+    let result = unsafe { IsProcessCritical(process, &mut critical) };
+    unsafe {
+        let _ = CloseHandle(process);
+    }
+    result.is_err() || critical.as_bool()
+}
@@ -0,0 +1,310 @@
+//! Display topology changes through the CCD API (`QueryDisplayConfig` /
+//! `SetDisplayConfig`), an alternative to detaching monitors with
+//! `ChangeDisplaySettingsExW`.
+
+use std::mem;
+use serde::{Deserialize, Serialize};
+use windows::Win32::Devices::Display::*;
+use windows::Win32::Foundation::{ERROR_NOT_FOUND, ERROR_NOT_SUPPORTED, WIN32_ERROR};
+
+/// The active paths and modes as returned by `QueryDisplayConfig`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawTopology", into = "RawTopology")]
+pub struct Topology {
+    paths: Vec<DISPLAYCONFIG_PATH_INFO>,
+    modes: Vec<DISPLAYCONFIG_MODE_INFO>,
+}
+
+/// On-disk form of a `Topology`: the path and mode arrays as hex-encoded
+/// bytes, since the structs are plain data full of unions.
+#[derive(Serialize, Deserialize)]
+struct RawTopology {
+    paths: String,
+    modes: String,
+}
+
+impl From<Topology> for RawTopology {
+    fn from(topology: Topology) -> Self {
+        Self {
+            paths: to_hex(&topology.paths),
+            modes: to_hex(&topology.modes),
+        }
+    }
+}
+
+impl TryFrom<RawTopology> for Topology {
+    type Error = String;
+
+    fn try_from(raw: RawTopology) -> Result<Self, Self::Error> {
+        Ok(Self {
+            paths: from_hex(&raw.paths).ok_or("malformed path array")?,
+            modes: from_hex(&raw.modes).ok_or("malformed mode array")?,
+        })
+    }
+}
+
+fn to_hex<T: Copy>(items: &[T]) -> String {
+    let bytes = unsafe { std::slice::from_raw_parts(items.as_ptr() as *const u8, mem::size_of_val(items)) };
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex<T: Copy>(hex: &str) -> Option<Vec<T>> {
+    let size = mem::size_of::<T>();
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2 * size) {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<_>>()?;
+    Some(
+        bytes
+            .chunks_exact(size)
+            .map(|chunk| unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const T) })
+            .collect(),
+    )
+}
+
+impl Topology {
+    /// Reads the paths that currently drive a display.
+    pub fn query_active() -> windows::core::Result<Self> {
+        loop {
+            let (mut path_count, mut mode_count) = (0u32, 0u32);
+            unsafe { GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count) }.ok()?;
+            let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+            let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+            let result = unsafe {
+                QueryDisplayConfig(
+                    QDC_ONLY_ACTIVE_PATHS,
+                    &mut path_count,
+                    paths.as_mut_ptr(),
+                    &mut mode_count,
+                    modes.as_mut_ptr(),
+                    None,
+                )
+            };
+            // The topology can change between the two calls; ask again.
+            if result == windows::Win32::Foundation::ERROR_INSUFFICIENT_BUFFER {
+                continue;
+            }
+            result.ok()?;
+            paths.truncate(path_count as usize);
+            modes.truncate(mode_count as usize);
+            return Ok(Self { paths, modes });
+        }
+    }
+
+    /// Makes this the active topology. With `persist` it's also written to the
+    /// persistence database, so it sticks across a reboot the way
+    /// `CDS_UPDATEREGISTRY` does.
+    pub fn apply(&self, persist: bool) -> windows::core::Result<()> {
+        let mut flags = SDC_APPLY | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES;
+        if persist {
+            flags |= SDC_SAVE_TO_DATABASE;
+        }
+        let code = unsafe { SetDisplayConfig(Some(&self.paths), Some(&self.modes), flags) };
+        WIN32_ERROR(code as u32).ok()
+    }
+}
+
+fn source_device_name(path: &DISPLAYCONFIG_PATH_INFO) -> Option<String> {
+    let mut request = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+            size: mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        },
+        ..Default::default()
+    };
+    if unsafe { DisplayConfigGetDeviceInfo(&mut request.header) } != 0 {
+        return None;
+    }
+    let name = &request.viewGdiDeviceName;
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    Some(String::from_utf16_lossy(&name[..len]))
+}
+
+/// Drops the paths driving `device_names` from the active topology. Every
+/// named device is turned off in a single `SetDisplayConfig` call.
+pub fn detach(device_names: &[String], persist: bool) -> windows::core::Result<()> {
+    let mut topology = Topology::query_active()?;
+    topology.paths.retain(|path| {
+        source_device_name(path).is_none_or(|name| !device_names.iter().any(|d| d.eq_ignore_ascii_case(&name)))
+    });
+    topology.apply(persist)
+}
+
+/// Undocumented `DisplayConfigGetDeviceInfo`/`DisplayConfigSetDeviceInfo`
+/// request types for the scaling slider in Display settings. Scales are given
+/// as steps relative to the one Windows recommends for the monitor.
+const GET_DPI_SCALE: DISPLAYCONFIG_DEVICE_INFO_TYPE = DISPLAYCONFIG_DEVICE_INFO_TYPE(-3);
+const SET_DPI_SCALE: DISPLAYCONFIG_DEVICE_INFO_TYPE = DISPLAYCONFIG_DEVICE_INFO_TYPE(-4);
+/// The slider's steps, in percent.
+const DPI_SCALES: [u32; 12] = [100, 125, 150, 175, 200, 225, 250, 300, 350, 400, 450, 500];
+
+#[repr(C)]
+struct DpiScaleGet {
+    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    min_scale_rel: i32,
+    cur_scale_rel: i32,
+    max_scale_rel: i32,
+}
+
+#[repr(C)]
+struct DpiScaleSet {
+    header: DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    scale_rel: i32,
+}
+
+/// What the scaling slider reports for one source: the recommended step's
+/// index into `DPI_SCALES` and the allowed range of relative steps.
+struct DpiScale {
+    recommended: i32,
+    current: i32,
+    min: i32,
+    max: i32,
+}
+
+fn query_dpi_scale(path: &DISPLAYCONFIG_PATH_INFO) -> Option<DpiScale> {
+    let mut request = DpiScaleGet {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: GET_DPI_SCALE,
+            size: mem::size_of::<DpiScaleGet>() as u32,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        },
+        min_scale_rel: 0,
+        cur_scale_rel: 0,
+        max_scale_rel: 0,
+    };
+    if unsafe { DisplayConfigGetDeviceInfo(&mut request.header) } != 0 {
+        return None;
+    }
+    let recommended = request.min_scale_rel.abs();
+    Some(DpiScale {
+        recommended,
+        current: request.cur_scale_rel.clamp(request.min_scale_rel, request.max_scale_rel),
+        min: request.min_scale_rel,
+        max: request.max_scale_rel,
+    })
+}
+
+/// Whether a display can do HDR ("advanced color") and whether it's on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvancedColor {
+    pub supported: bool,
+    pub enabled: bool,
+}
+
+fn query_advanced_color(path: &DISPLAYCONFIG_PATH_INFO) -> Option<AdvancedColor> {
+    let mut request = DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+            size: mem::size_of::<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO>() as u32,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..Default::default()
+    };
+    if unsafe { DisplayConfigGetDeviceInfo(&mut request.header) } != 0 {
+        return None;
+    }
+    // Bit 0 is advancedColorSupported, bit 1 advancedColorEnabled and bit 3
+    // advancedColorForceDisabled (by policy or the driver).
+    let value = unsafe { request.Anonymous.value };
+    Some(AdvancedColor {
+        supported: value & 0b1 != 0 && value & 0b1000 == 0,
+        enabled: value & 0b10 != 0,
+    })
+}
+
+/// What the CCD API reports for one active display beyond its mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceState {
+    pub device_name: String,
+    /// Scaling in percent; `None` if it can't be read.
+    pub scale_percent: Option<u32>,
+    pub advanced_color: Option<AdvancedColor>,
+}
+
+/// Scaling and HDR state of every active display.
+pub fn source_states() -> Vec<SourceState> {
+    let Ok(topology) = Topology::query_active() else {
+        return Vec::new();
+    };
+    topology
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let scale_percent = query_dpi_scale(path).map(|scale| {
+                let index = (scale.recommended + scale.current).clamp(0, DPI_SCALES.len() as i32 - 1);
+                DPI_SCALES[index as usize]
+            });
+            Some(SourceState {
+                device_name: source_device_name(path)?,
+                scale_percent,
+                advanced_color: query_advanced_color(path),
+            })
+        })
+        .collect()
+}
+
+/// The active path driving `device_name`.
+fn active_path(device_name: &str) -> windows::core::Result<DISPLAYCONFIG_PATH_INFO> {
+    Topology::query_active()?
+        .paths
+        .into_iter()
+        .find(|path| source_device_name(path).is_some_and(|name| name.eq_ignore_ascii_case(device_name)))
+        .ok_or_else(|| ERROR_NOT_FOUND.to_hresult().into())
+}
+
+/// Turns HDR on or off for `device_name`. Fails for displays that don't
+/// support it.
+pub fn set_advanced_color(device_name: &str, enabled: bool) -> windows::core::Result<()> {
+    let path = active_path(device_name)?;
+    let state = query_advanced_color(&path).ok_or_else(|| windows::core::Error::from(ERROR_NOT_SUPPORTED.to_hresult()))?;
+    if !state.supported {
+        return Err(ERROR_NOT_SUPPORTED.to_hresult().into());
+    }
+    if state.enabled == enabled {
+        return Ok(());
+    }
+    let mut request = DISPLAYCONFIG_SET_ADVANCED_COLOR_STATE {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_SET_ADVANCED_COLOR_STATE,
+            size: mem::size_of::<DISPLAYCONFIG_SET_ADVANCED_COLOR_STATE>() as u32,
+            adapterId: path.targetInfo.adapterId,
+            id: path.targetInfo.id,
+        },
+        ..Default::default()
+    };
+    request.Anonymous.value = u32::from(enabled);
+    WIN32_ERROR(unsafe { DisplayConfigSetDeviceInfo(&request.header) } as u32).ok()
+}
+
+/// Sets the scaling of `device_name` to the slider step closest to
+/// `percent`. Does nothing if it's already there.
+pub fn set_dpi_scale(device_name: &str, percent: u32) -> windows::core::Result<()> {
+    let path = &active_path(device_name)?;
+    let scale = query_dpi_scale(path).ok_or_else(|| windows::core::Error::from(ERROR_NOT_SUPPORTED.to_hresult()))?;
+    let index = DPI_SCALES
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &scale)| scale.abs_diff(percent))
+        .map_or(0, |(index, _)| index as i32);
+    let scale_rel = (index - scale.recommended).clamp(scale.min, scale.max);
+    if scale_rel == scale.current {
+        return Ok(());
+    }
+    let request = DpiScaleSet {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: SET_DPI_SCALE,
+            size: mem::size_of::<DpiScaleSet>() as u32,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        },
+        scale_rel,
+    };
+    WIN32_ERROR(unsafe { DisplayConfigSetDeviceInfo(&request.header) } as u32).ok()
+}
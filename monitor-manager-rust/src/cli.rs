@@ -0,0 +1,167 @@
+use std::fmt::Write as _;
+
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+use crate::{ipc, service};
+use monitor_manager::config::{self, Config};
+use monitor_manager::monitor::{self, DeviceResult, MonitorManager};
+
+const USAGE: &str = "Usage: monitor-manager [list [--json] | disable [DEVICE] | restore | status | \
+                     snapshot (save | apply) NAME | service (install | uninstall)]";
+
+/// A subcommand given on the command line. Without one the tray starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Prints every display device.
+    List { json: bool },
+    /// Detaches one monitor, or every secondary one.
+    Disable { device: Option<String> },
+    /// Reapplies the saved monitor settings.
+    Restore,
+    /// Asks the running instance what it's doing.
+    Status,
+    /// Saves the current layout under a name.
+    SaveSnapshot { name: String },
+    /// Reapplies a layout saved under a name.
+    ApplySnapshot { name: String },
+    /// Registers the per-user service; needs an elevated prompt.
+    InstallService,
+    UninstallService,
+}
+
+/// Parses the arguments after the exe name. `None` means no subcommand was
+/// given; flags like `--portable` are left to whoever reads them.
+pub fn parse(args: &[String]) -> Option<Result<Command, String>> {
+    // The path after `--config` isn't a word either.
+    let mut words = args
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i == 0 || args[i - 1] != config::CONFIG_FLAG)
+        .map(|(_, arg)| arg)
+        .filter(|arg| !arg.starts_with("--") || *arg == "--json");
+    let command = words.next()?;
+    let rest: Vec<&String> = words.collect();
+    Some(match (command.as_str(), rest.as_slice()) {
+        ("list", []) => Ok(Command::List { json: false }),
+        ("list", [flag]) if *flag == "--json" => Ok(Command::List { json: true }),
+        ("disable", []) => Ok(Command::Disable { device: None }),
+        ("disable", [device]) => Ok(Command::Disable { device: Some(device.to_string()) }),
+        ("restore", []) => Ok(Command::Restore),
+        ("status", []) => Ok(Command::Status),
+        ("snapshot", [action, name]) if *action == "save" => Ok(Command::SaveSnapshot { name: name.to_string() }),
+        ("snapshot", [action, name]) if *action == "apply" => Ok(Command::ApplySnapshot { name: name.to_string() }),
+        ("service", [action]) if *action == "install" => Ok(Command::InstallService),
+        ("service", [action]) if *action == "uninstall" => Ok(Command::UninstallService),
+        _ => Err(format!("unrecognized arguments: {}", args.join(" "))),
+    })
+}
+
+/// Runs a subcommand against the displays, or the running instance for
+/// `status`, and returns the process exit code.
+pub fn run(command: Result<Command, String>) -> i32 {
+    // The exe is a GUI app, so output only shows up once it borrows the
+    // terminal it was started from.
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+    let result = match command {
+        Ok(Command::List { json }) => list(json),
+        Ok(Command::Disable { device }) => disable(device.as_deref()),
+        Ok(Command::Restore) => restore(),
+        Ok(Command::Status) => status(),
+        Ok(Command::SaveSnapshot { name }) => save_snapshot(&name),
+        Ok(Command::ApplySnapshot { name }) => apply_snapshot(&name),
+        Ok(Command::InstallService) => service::install(),
+        Ok(Command::UninstallService) => service::uninstall(),
+        Err(e) => Err(format!("{}\n{}", e, USAGE)),
+    };
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn list(json: bool) -> Result<String, String> {
+    let manager = MonitorManager::new(MonitorManager::load_saved_settings_from_disk(&config::config_dir()));
+    let monitors = manager.get_all_monitors_detailed();
+    if json {
+        return serde_json::to_string_pretty(&monitors).map_err(|e| e.to_string());
+    }
+    let mut output = String::new();
+    for monitor in &monitors {
+        let _ = writeln!(output, "{:<14} {}", monitor.device_name, monitor.label());
+        if let Some(stable_id) = &monitor.stable_id {
+            let _ = writeln!(output, "{:<14} {}", "", stable_id);
+        }
+    }
+    Ok(output.trim_end().to_string())
+}
+
+fn configured_manager() -> (Config, MonitorManager) {
+    let (config, problem) = Config::load();
+    if let Some(problem) = problem {
+        eprintln!("{}", problem);
+    }
+    let mut manager = MonitorManager::new(MonitorManager::load_saved_settings_from_disk(&config::config_dir()));
+    manager.set_backend(config.display_backend);
+    manager.set_persist_changes(config.persist_changes);
+    manager.set_managed_monitors(config.managed_monitors.clone());
+    manager.set_excluded_monitors(config.excluded_monitors.clone());
+    (config, manager)
+}
+
+fn disable(device: Option<&str>) -> Result<String, String> {
+    let (config, mut manager) = configured_manager();
+    let device_names = match device {
+        Some(id) => {
+            let monitor = manager
+                .get_all_monitors()
+                .into_iter()
+                .find(|m| m.matches_id(id))
+                .ok_or_else(|| format!("no monitor named {}", id))?;
+            vec![monitor.device_name]
+        }
+        None => manager.secondary_device_names(config.profile().keep_enabled.as_deref()),
+    };
+    manager.save_current_settings();
+    let results = manager.disable_monitors(&device_names);
+    report("Disabled", &results)
+}
+
+fn restore() -> Result<String, String> {
+    let (_, mut manager) = configured_manager();
+    let results = manager.restore_all_monitors_with_retries();
+    report("Restored", &results)
+}
+
+fn save_snapshot(name: &str) -> Result<String, String> {
+    let (_, manager) = configured_manager();
+    let count = manager.capture_snapshot(name).map_err(|e| e.to_string())?;
+    Ok(format!("Saved layout \"{}\" with {} monitor(s)", name.trim(), count))
+}
+
+fn apply_snapshot(name: &str) -> Result<String, String> {
+    let (_, mut manager) = configured_manager();
+    let results = manager.apply_snapshot(name).map_err(|e| e.to_string())?;
+    report("Applied layout to", &results)
+}
+
+fn status() -> Result<String, String> {
+    let response =
+        ipc::send(ipc::Request::Status).map_err(|e| format!("Monitor Manager isn't running ({})", e))?;
+    Ok(format!("State: {}\nStatus: {}", response.state, response.status))
+}
+
+fn report(action: &str, results: &[DeviceResult]) -> Result<String, String> {
+    let changed = results.iter().filter(|(_, r)| r.is_ok()).count();
+    match monitor::describe_failures(results) {
+        Some(failures) => Err(format!("{} {} monitor(s); failed: {}", action, changed, failures)),
+        None => Ok(format!("{} {} monitor(s)", action, changed)),
+    }
+}
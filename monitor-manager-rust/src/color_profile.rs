@@ -0,0 +1,96 @@
+use std::mem;
+
+use windows::core::{HSTRING, PWSTR};
+use windows::Win32::Graphics::Gdi::{EnumDisplayDevicesW, DISPLAY_DEVICEW};
+use windows::Win32::UI::ColorSystem::{
+    WcsAssociateColorProfileWithDevice, WcsGetDefaultColorProfile, WcsGetDefaultColorProfileSize,
+    WcsSetDefaultColorProfile, CPST_NONE, CPT_ICC, WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+};
+
+/// ICC profiles for `CPST_NONE` are looked up under profile ID 1.
+const PROFILE_ID: u32 = 1;
+
+/// The file name of the ICC profile Windows uses for the monitor on
+/// `device_name` (e.g. `\\.\DISPLAY2`), or `None` if it has none of its own.
+pub fn default_profile(device_name: &str) -> Option<String> {
+    let key = HSTRING::from(monitor_key(device_name)?);
+    let mut size = 0u32;
+    let found = unsafe {
+        WcsGetDefaultColorProfileSize(
+            WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+            &key,
+            CPT_ICC,
+            CPST_NONE,
+            PROFILE_ID,
+            &mut size,
+        )
+    };
+    if !found.as_bool() || size == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+    let found = unsafe {
+        WcsGetDefaultColorProfile(
+            WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+            &key,
+            CPT_ICC,
+            CPST_NONE,
+            PROFILE_ID,
+            size,
+            PWSTR(buffer.as_mut_ptr()),
+        )
+    };
+    if !found.as_bool() {
+        return None;
+    }
+    Some(wide_to_string(&buffer)).filter(|name| !name.is_empty())
+}
+
+/// Associates `profile` with the monitor on `device_name` again and makes it
+/// the default. Failures are only logged.
+pub fn associate(device_name: &str, profile: &str) {
+    if default_profile(device_name).is_some_and(|current| current.eq_ignore_ascii_case(profile)) {
+        return;
+    }
+    let Some(key) = monitor_key(device_name) else {
+        log::warn!("No monitor found on {}, could not restore its color profile", device_name);
+        return;
+    };
+    let (key, profile_name) = (HSTRING::from(key), HSTRING::from(profile));
+    let restored = unsafe {
+        WcsAssociateColorProfileWithDevice(WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER, &profile_name, &key).as_bool()
+            && WcsSetDefaultColorProfile(
+                WCS_PROFILE_MANAGEMENT_SCOPE_CURRENT_USER,
+                &key,
+                CPT_ICC,
+                CPST_NONE,
+                PROFILE_ID,
+                &profile_name,
+            )
+            .as_bool()
+    };
+    if restored {
+        log::info!("Restored color profile {} on {}", profile, device_name);
+    } else {
+        log::warn!(
+            "Could not restore color profile {} on {}: {}",
+            profile,
+            device_name,
+            windows::core::Error::from_win32().message()
+        );
+    }
+}
+
+/// The registry key of the monitor attached to the adapter output, which is
+/// how the WCS functions name a display.
+fn monitor_key(device_name: &str) -> Option<String> {
+    let mut monitor: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
+    monitor.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
+    let found = unsafe { EnumDisplayDevicesW(&HSTRING::from(device_name), 0, &mut monitor, 0) }.as_bool();
+    found.then(|| wide_to_string(&monitor.DeviceKey)).filter(|key| !key.is_empty())
+}
+
+fn wide_to_string(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
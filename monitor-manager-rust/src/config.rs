@@ -0,0 +1,1257 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Datelike, NaiveDateTime, Timelike, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+use crate::matcher::{self, PatternError, TargetPattern};
+
+const APP_DIR_NAME: &str = "MonitorManager";
+const CONFIG_FILE: &str = "config.json";
+const TOML_CONFIG_FILE: &str = "config.toml";
+const PORTABLE_MARKER: &str = "portable.txt";
+/// `--config PATH`: use that file instead of the config in `config_dir()`.
+pub const CONFIG_FLAG: &str = "--config";
+
+/// The profile a config written before profiles existed is wrapped in.
+pub const DEFAULT_PROFILE: &str = "Default";
+/// The newest config layout, which `save` always writes.
+pub const CONFIG_VERSION: u32 = 1;
+/// The profile fields a version 0 config could keep at the top level, from
+/// before profiles existed. Older still, `target_exe` held a single target.
+const FLAT_PROFILE_FIELDS: [&str; 6] = ["targets", "target_exe", "match_mode", "rules", "strategy", "keep_enabled"];
+/// Poll interval used when the config doesn't set one.
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 2000;
+/// Shortest poll interval `clamp_poll_interval_ms` allows.
+pub const MIN_POLL_INTERVAL_MS: u64 = 250;
+/// Longest poll interval `clamp_poll_interval_ms` allows.
+pub const MAX_POLL_INTERVAL_MS: u64 = 60_000;
+
+/// What a target is compared against: the full exe path or just its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MatchMode {
+    /// Compare the whole executable path (case-insensitive).
+    #[default]
+    FullPath,
+    /// Compare only the file name, so the install folder can move.
+    FileName,
+}
+
+/// What happens to the displays while a target runs.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Detach the selected monitors from the desktop.
+    #[default]
+    Disable,
+    /// Make the `keep_enabled` monitor primary and leave the others attached.
+    SwitchPrimary,
+    /// Drop the selected monitors to `hz` to free up GPU bandwidth.
+    ReduceRefreshRate { hz: u32 },
+    /// Switch the selected monitors to a lower resolution.
+    ReduceResolution { width: u32, height: u32 },
+    /// Put the selected monitors into standby over DDC/CI, detaching any that
+    /// don't support it.
+    DdcPowerOff,
+    /// Cover the selected monitors with black windows without changing the
+    /// display topology, for games whose anti-cheat dislikes display changes.
+    Blank,
+}
+
+/// What makes a profile change the displays.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// One of the profile's `targets` running.
+    #[default]
+    Processes,
+    /// Any app going exclusive fullscreen. `borderless` also counts a
+    /// foreground window that exactly covers the primary monitor.
+    Fullscreen {
+        #[serde(default)]
+        borderless: bool,
+    },
+    /// A visible window whose title contains this text, or matches it with
+    /// a `regex:` prefix. Its process counts until it exits, even if the
+    /// title changes.
+    WindowTitle(String),
+}
+
+/// What has to run under a launcher target before it counts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildTrigger {
+    /// A process the launcher started, directly or further down, matching
+    /// this `targets`-style entry.
+    Pattern(String),
+    /// Any process the launcher started owning a fullscreen or borderless
+    /// window.
+    Fullscreen,
+}
+
+/// Which Windows API changes the display topology.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayBackend {
+    /// `ChangeDisplaySettingsExW`, detaching a monitor by giving it a 0x0 mode.
+    #[default]
+    Legacy,
+    /// `SetDisplayConfig`, dropping the monitor's path from the active topology.
+    Ccd,
+}
+
+/// How the monitor loop notices targets starting and exiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessDetection {
+    /// WMI process events, falling back to polling if WMI isn't working.
+    #[default]
+    Events,
+    /// Reading the whole process list every `poll_interval_ms`.
+    Polling,
+}
+
+/// Which monitors a target rule turns off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorSelection {
+    AllSecondaries,
+    Devices(Vec<String>),
+}
+
+/// A display rotation, as Windows' display settings name them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    #[default]
+    Landscape,
+    Portrait,
+    LandscapeFlipped,
+    PortraitFlipped,
+}
+
+impl Orientation {
+    /// From a `DMDO_*` value.
+    pub fn from_dmdo(value: u32) -> Self {
+        match value % 4 {
+            1 => Self::Portrait,
+            2 => Self::LandscapeFlipped,
+            3 => Self::PortraitFlipped,
+            _ => Self::Landscape,
+        }
+    }
+
+    /// The `DMDO_*` value, i.e. quarter turns clockwise from landscape.
+    pub fn dmdo(self) -> u32 {
+        self as u32
+    }
+
+    pub fn is_portrait(self) -> bool {
+        matches!(self, Self::Portrait | Self::PortraitFlipped)
+    }
+}
+
+/// Rotates `monitor` (device name or stable ID) while a target runs. The
+/// monitor is then left out of what the strategy changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrientationRule {
+    pub monitor: String,
+    pub orientation: Orientation,
+}
+
+/// What happens to a background program while a target runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspendAction {
+    /// Freeze its threads and let them run again on restore.
+    #[default]
+    Suspend,
+    /// Ask its windows to close, and start it again on restore.
+    Close,
+}
+
+/// A background program to set aside while a target runs. `process` is an
+/// exe name or path, with the same glob and regex forms as `targets`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SuspendRule {
+    pub process: String,
+    #[serde(default)]
+    pub action: SuspendAction,
+}
+
+/// Overrides which monitors get disabled when `target` is the one running.
+/// `target` must match an entry of `Profile::targets` (case-insensitive).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetRule {
+    pub target: String,
+    pub monitors: MonitorSelection,
+}
+
+/// A named set of targets and what happens to the displays while they run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    #[serde(default)]
+    pub rules: Vec<TargetRule>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Device name or stable ID of the monitor that stays on while the other
+    /// monitors are disabled. `None` keeps the primary.
+    #[serde(default)]
+    pub keep_enabled: Option<String>,
+    /// Only keep the displays changed while a target owns the foreground
+    /// window, so alt-tabbing out brings the other monitors back.
+    #[serde(default)]
+    pub require_foreground: bool,
+    /// `targets` only matter with the `Processes` trigger.
+    #[serde(default)]
+    pub trigger: Trigger,
+    /// Treat `targets` as launchers and wait for one of the processes they
+    /// start. `None` counts the targets themselves.
+    #[serde(default)]
+    pub children: Option<ChildTrigger>,
+    #[serde(default)]
+    pub orientations: Vec<OrientationRule>,
+    /// Turn on HDR on the `keep_enabled` monitor (or the primary) while a
+    /// target runs.
+    #[serde(default)]
+    pub enable_hdr: bool,
+    /// Keep the mouse on the `keep_enabled` monitor (or the primary) while a
+    /// target has the focus, for strategies that leave the other monitors
+    /// attached.
+    #[serde(default)]
+    pub confine_cursor: bool,
+    /// Endpoint ID of the playback device to make the default while a target
+    /// runs. `None` leaves audio alone.
+    #[serde(default)]
+    pub audio_device: Option<String>,
+    /// GUID of the power plan to make active while a target runs, e.g.
+    /// Ultimate Performance's. `None` leaves the plan alone.
+    #[serde(default)]
+    pub power_scheme: Option<String>,
+    /// Commands run once monitors are changed for a target and once they're
+    /// back, each as the program followed by its arguments. `{target}`,
+    /// `{monitors_disabled}` and `{profile}` in an argument are filled in.
+    #[serde(default)]
+    pub on_disable: Vec<Vec<String>>,
+    #[serde(default)]
+    pub on_restore: Vec<Vec<String>>,
+    /// Run `on_disable` and `on_restore` through `cmd /C`, for pipes and
+    /// built-in commands.
+    #[serde(default)]
+    pub use_shell: bool,
+    #[serde(default)]
+    pub suspend_processes: Vec<SuspendRule>,
+}
+
+impl Profile {
+    /// A profile with no targets, which never changes the displays.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            targets: Vec::new(),
+            match_mode: MatchMode::default(),
+            rules: Vec::new(),
+            strategy: Strategy::default(),
+            keep_enabled: None,
+            require_foreground: false,
+            trigger: Trigger::default(),
+            children: None,
+            orientations: Vec::new(),
+            enable_hdr: false,
+            confine_cursor: false,
+            audio_device: None,
+            power_scheme: None,
+            on_disable: Vec::new(),
+            on_restore: Vec::new(),
+            use_shell: false,
+            suspend_processes: Vec::new(),
+        }
+    }
+
+    /// Parses every `targets` entry, failing on the first invalid pattern.
+    pub fn compile_targets(&self) -> Result<Vec<TargetPattern>, PatternError> {
+        matcher::compile_targets(&self.targets)
+    }
+
+    /// The monitors to turn off while `target` runs: its rule's selection,
+    /// or every secondary monitor if it has no rule.
+    pub fn monitors_for_target(&self, target: &str) -> MonitorSelection {
+        self.rules
+            .iter()
+            .find(|rule| rule.target.eq_ignore_ascii_case(target))
+            .map(|rule| rule.monitors.clone())
+            .unwrap_or(MonitorSelection::AllSecondaries)
+    }
+}
+
+/// A time of day to the minute, written "HH:MM".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        (hour < 24 && minute < 60).then_some(Self(hour * 60 + minute))
+    }
+
+    /// Wraps around past midnight.
+    pub const fn from_minutes(minutes: u32) -> Self {
+        Self(minutes % (24 * 60))
+    }
+
+    /// Parses "18:30" or "7:05".
+    pub fn parse(text: &str) -> Option<Self> {
+        let (hour, minute) = text.trim().split_once(':')?;
+        Self::new(hour.parse().ok()?, minute.parse().ok()?)
+    }
+
+    pub fn minutes_since_midnight(self) -> u32 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("expected a time like \"18:30\", got \"{}\"", text)))
+    }
+}
+
+/// Part of a day. An `end` at or before `start` runs into the next day, so
+/// 22:00-02:00 covers four hours and 00:00-00:00 the whole day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+}
+
+impl TimeWindow {
+    fn crosses_midnight(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+/// When targets may change the displays, one window per weekday. Days
+/// without a window are outside the schedule all day, apart from the end of
+/// the previous day's window if it runs past midnight.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mon: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tue: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wed: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thu: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fri: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sat: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sun: Option<TimeWindow>,
+}
+
+impl Schedule {
+    pub fn day(&self, weekday: Weekday) -> Option<TimeWindow> {
+        match weekday {
+            Weekday::Mon => self.mon,
+            Weekday::Tue => self.tue,
+            Weekday::Wed => self.wed,
+            Weekday::Thu => self.thu,
+            Weekday::Fri => self.fri,
+            Weekday::Sat => self.sat,
+            Weekday::Sun => self.sun,
+        }
+    }
+
+    pub fn day_mut(&mut self, weekday: Weekday) -> &mut Option<TimeWindow> {
+        match weekday {
+            Weekday::Mon => &mut self.mon,
+            Weekday::Tue => &mut self.tue,
+            Weekday::Wed => &mut self.wed,
+            Weekday::Thu => &mut self.thu,
+            Weekday::Fri => &mut self.fri,
+            Weekday::Sat => &mut self.sat,
+            Weekday::Sun => &mut self.sun,
+        }
+    }
+
+    /// Whether `at` falls in its day's window or in the part of the
+    /// previous day's window that runs past midnight.
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let minutes = at.hour() * 60 + at.minute();
+        let today = self.day(at.weekday()).is_some_and(|window| {
+            let (start, end) = (window.start.minutes_since_midnight(), window.end.minutes_since_midnight());
+            if window.crosses_midnight() {
+                minutes >= start
+            } else {
+                (start..end).contains(&minutes)
+            }
+        });
+        let carried_over = self
+            .day(at.weekday().pred())
+            .is_some_and(|window| window.crosses_midnight() && minutes < window.end.minutes_since_midnight());
+        today || carried_over
+    }
+}
+
+/// The file format a config was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Toml,
+}
+
+/// The settings stored in config.json or config.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// The layout the file was written in; 0 when it has none. After
+    /// loading, a version newer than `CONFIG_VERSION` means `save` refuses
+    /// to overwrite the file.
+    #[serde(default)]
+    pub version: u32,
+    /// Name of the profile the monitor loop watches for.
+    #[serde(default)]
+    pub active_profile: String,
+    /// Never empty once loaded.
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    #[serde(default)]
+    pub process_detection: ProcessDetection,
+    /// When a process's exe path can't be read (it runs elevated or is
+    /// protected), match it by name against the target's file name. Off
+    /// ignores such processes, so a same-named one can't trigger by mistake.
+    #[serde(default = "default_name_fallback")]
+    pub name_fallback: bool,
+    /// How long a target must keep running before monitors are disabled, so
+    /// launchers that briefly spawn the game exe don't flip the displays.
+    #[serde(default)]
+    pub disable_delay_secs: u64,
+    /// Grace period after the last target exits before monitors come back,
+    /// so a quick relaunch doesn't flip the display topology twice.
+    #[serde(default)]
+    pub restore_delay_secs: u64,
+    /// Keeps monitors changed this long after the last target exits, for
+    /// when another session usually follows. The longer of this and
+    /// `restore_delay_secs` applies.
+    #[serde(default)]
+    pub hold_after_exit_minutes: u64,
+    /// How long a target must stay in or out of the foreground before it
+    /// counts, for profiles with `require_foreground`; quick alt-tabs are
+    /// ignored.
+    #[serde(default = "default_foreground_debounce_secs")]
+    pub foreground_debounce_secs: u64,
+    #[serde(default)]
+    pub display_backend: DisplayBackend,
+    /// Write display changes to the registry so they survive a reboot. Off
+    /// makes every change last only for the current session.
+    #[serde(default = "default_persist_changes")]
+    pub persist_changes: bool,
+    /// Also bring back monitors turned off from the tray when the target exits.
+    #[serde(default)]
+    pub restore_manually_disabled: bool,
+    /// Show toast notifications for tray actions. Errors still get a message box.
+    #[serde(default = "default_notifications")]
+    pub notifications: bool,
+    /// Bring disabled monitors back when Monitor Manager exits. Off leaves
+    /// them as they are until the next start.
+    #[serde(default = "default_restore_on_exit")]
+    pub restore_on_exit: bool,
+    /// Keep the PC from sleeping and the display from turning off while
+    /// monitors are changed, e.g. through long loading screens.
+    #[serde(default)]
+    pub keep_awake: bool,
+    /// Write monitor changes and failures to the Windows Event Log as well
+    /// as the log file.
+    #[serde(default = "default_event_log")]
+    pub event_log: bool,
+    /// `on_disable` and `on_restore` commands still running after this long
+    /// are killed.
+    #[serde(default = "default_command_timeout_secs")]
+    pub command_timeout_secs: u64,
+    /// URLs sent a JSON POST when monitors are changed for a target and when
+    /// they're put back.
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    /// Global shortcut that disables or restores the secondary monitors,
+    /// e.g. "ctrl+alt+m". `None` turns it off, and is saved as "".
+    #[serde(default = "default_hotkey", serialize_with = "serialize_hotkey", deserialize_with = "deserialize_hotkey")]
+    pub hotkey: Option<String>,
+    /// Keys of the monitors that may be turned off when a target runs. `None`
+    /// makes every secondary monitor eligible. Explicit `rules` ignore it.
+    #[serde(default)]
+    pub managed_monitors: Option<Vec<String>>,
+    /// Device names or stable IDs of monitors that are never changed, by a
+    /// target, a rule or "Disable now", e.g. a pen display whose driver
+    /// breaks when it's detached.
+    #[serde(default)]
+    pub excluded_monitors: Vec<String>,
+    /// Outside these hours targets are ignored, as if paused. `None` means
+    /// any time.
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// The format the config was loaded from; `save` writes back to the same file.
+    #[serde(skip)]
+    pub format: ConfigFormat,
+    /// The file the config was loaded from, or is created at by the first save.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            active_profile: DEFAULT_PROFILE.to_string(),
+            profiles: vec![Profile {
+                targets: vec![r"C:\Riot Games\League of Legends\Game\League of Legends.exe".to_string()],
+                ..Profile::new(DEFAULT_PROFILE)
+            }],
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            process_detection: ProcessDetection::default(),
+            name_fallback: default_name_fallback(),
+            disable_delay_secs: 0,
+            restore_delay_secs: 0,
+            hold_after_exit_minutes: 0,
+            foreground_debounce_secs: default_foreground_debounce_secs(),
+            display_backend: DisplayBackend::default(),
+            persist_changes: true,
+            restore_manually_disabled: false,
+            notifications: true,
+            restore_on_exit: true,
+            keep_awake: false,
+            event_log: default_event_log(),
+            command_timeout_secs: default_command_timeout_secs(),
+            webhooks: Vec::new(),
+            hotkey: default_hotkey(),
+            managed_monitors: None,
+            excluded_monitors: Vec::new(),
+            schedule: None,
+            format: ConfigFormat::default(),
+            path: None,
+        }
+    }
+}
+
+fn default_poll_interval_ms() -> u64 {
+    DEFAULT_POLL_INTERVAL_MS
+}
+
+fn default_persist_changes() -> bool {
+    true
+}
+
+fn default_foreground_debounce_secs() -> u64 {
+    3
+}
+
+fn default_command_timeout_secs() -> u64 {
+    30
+}
+
+fn default_name_fallback() -> bool {
+    true
+}
+
+fn default_notifications() -> bool {
+    true
+}
+
+fn default_restore_on_exit() -> bool {
+    true
+}
+
+fn default_event_log() -> bool {
+    true
+}
+
+fn default_hotkey() -> Option<String> {
+    Some("ctrl+alt+m".to_string())
+}
+
+/// Writes a turned-off hotkey as "" rather than leaving it out, which TOML
+/// would do with `None` and which reads back as the default.
+fn serialize_hotkey<S>(hotkey: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(hotkey.as_deref().unwrap_or_default())
+}
+
+/// Reads "" and `null` as a turned-off hotkey.
+fn deserialize_hotkey<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|hotkey| !hotkey.trim().is_empty()))
+}
+
+/// Clamps a poll interval into the supported range.
+pub fn clamp_poll_interval_ms(ms: u64) -> u64 {
+    ms.clamp(MIN_POLL_INTERVAL_MS, MAX_POLL_INTERVAL_MS)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for Vec<String> {
+    fn from(value: OneOrMany) -> Self {
+        match value {
+            OneOrMany::One(target) => vec![target],
+            OneOrMany::Many(targets) => targets,
+        }
+    }
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    OneOrMany::deserialize(deserializer).map(Vec::from)
+}
+
+impl Config {
+    /// Reads the config from disk, with the defaults when there's no file
+    /// yet. A file that can't be read or parsed is left as it is and copied
+    /// beside itself as e.g. config.json.bad; the defaults are used until
+    /// it's fixed, and what went wrong is returned with them.
+    pub fn load() -> (Self, Option<String>) {
+        if config_override().is_none() {
+            migrate_from_exe_dir(&exe_dir(), &json_path());
+        }
+        let defaults = Self::default_at(Self::config_path());
+        let error = match Self::read_from_disk() {
+            Ok(config) if config.version > CONFIG_VERSION => {
+                let problem = format!(
+                    "{} is from a newer version of Monitor Manager. The settings this version knows are used, but \
+                     changes aren't saved to it, so the ones it doesn't know aren't lost.",
+                    defaults.path().display()
+                );
+                log::warn!("{}", problem);
+                return (config, Some(problem));
+            }
+            Ok(config) => return (config, None),
+            Err(AppError::ConfigIo { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+                return (defaults, None);
+            }
+            Err(e) => e,
+        };
+        let path = defaults.path();
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+        let backup = path.with_file_name(format!("{}.bad", name));
+        let problem = match fs::copy(&path, &backup) {
+            Ok(_) => format!(
+                "{} couldn't be loaded, so the default settings are used until it's fixed. A copy is saved as {}.\n{}",
+                name,
+                backup.display(),
+                error
+            ),
+            Err(e) => {
+                log::warn!("Could not back up {} to {}: {}", path.display(), backup.display(), e);
+                format!("{} couldn't be loaded, so the default settings are used until it's fixed.\n{}", name, error)
+            }
+        };
+        log::error!("{}", problem);
+        (defaults, Some(problem))
+    }
+
+    /// The defaults, to be saved to `path` in the format its extension names.
+    pub fn default_at(path: PathBuf) -> Self {
+        Self {
+            format: format_of(&path),
+            path: Some(path),
+            ..Self::default()
+        }
+    }
+
+    /// Reads and parses the config file without falling back to defaults.
+    pub fn read_from_disk() -> Result<Self, AppError> {
+        let config_path = Self::config_path();
+        if config_override().is_none() && format_of(&config_path) == ConfigFormat::Toml && json_path().exists() {
+            log::warn!(
+                "Both {} and {} exist; using {}",
+                TOML_CONFIG_FILE,
+                CONFIG_FILE,
+                TOML_CONFIG_FILE
+            );
+        }
+        Self::read_from(&config_path)
+    }
+
+    /// Reads and parses `config_path`, as TOML if it ends in .toml and as
+    /// JSON otherwise.
+    pub fn read_from(config_path: &Path) -> Result<Self, AppError> {
+        let format = format_of(config_path);
+        let content = fs::read_to_string(config_path).map_err(|source| AppError::ConfigIo {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+        let value: Value = match format {
+            ConfigFormat::Json => {
+                serde_json::from_str(&content).map_err(|e| AppError::ConfigParse(json_error_message(&e, &content)))?
+            }
+            ConfigFormat::Toml => toml::from_str(&content).map_err(|e| AppError::ConfigParse(toml_error_message(&e, &content)))?,
+        };
+        let mut config = migrate(value).map_err(|e| {
+            // A wrong value is usually in the same place before and after
+            // migrating; reading the file directly says where.
+            let located = match format {
+                ConfigFormat::Json => {
+                    serde_json::from_str::<Config>(&content).err().map(|e| json_error_message(&e, &content))
+                }
+                ConfigFormat::Toml => {
+                    toml::from_str::<Config>(&content).err().map(|e| toml_error_message(&e, &content))
+                }
+            };
+            AppError::ConfigParse(located.unwrap_or_else(|| e.to_string()))
+        })?;
+        config.format = format;
+        config.path = Some(config_path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Makes sure there's a profile and `active_profile` names one.
+    fn ensure_active_profile(&mut self) {
+        if self.profiles.is_empty() {
+            self.profiles.push(Profile::new(DEFAULT_PROFILE));
+        }
+        if self.profile_index(&self.active_profile).is_none() {
+            self.active_profile = self.profiles[0].name.clone();
+        }
+    }
+
+    /// Index of the profile called `name`.
+    pub fn profile_index(&self, name: &str) -> Option<usize> {
+        self.profiles.iter().position(|profile| profile.name == name)
+    }
+
+    /// The active profile, or the first one if `active_profile` names none.
+    pub fn profile(&self) -> &Profile {
+        let idx = self.profile_index(&self.active_profile).unwrap_or(0);
+        &self.profiles[idx]
+    }
+
+    /// Last modification time of the config file, if it exists.
+    pub fn modified_time(&self) -> Option<SystemTime> {
+        fs::metadata(self.path()).and_then(|m| m.modified()).ok()
+    }
+
+    /// `poll_interval_ms`, clamped into the supported range.
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(clamp_poll_interval_ms(self.poll_interval_ms))
+    }
+
+    /// `disable_delay_secs` as a `Duration`.
+    pub fn disable_delay(&self) -> Duration {
+        Duration::from_secs(self.disable_delay_secs)
+    }
+
+    /// Whether `schedule`, if there is one, allows changing the displays at `at`.
+    pub fn in_schedule(&self, at: NaiveDateTime) -> bool {
+        self.schedule.as_ref().is_none_or(|schedule| schedule.contains(at))
+    }
+
+    /// `foreground_debounce_secs` as a `Duration`.
+    pub fn foreground_debounce(&self) -> Duration {
+        Duration::from_secs(self.foreground_debounce_secs)
+    }
+
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_secs(self.command_timeout_secs)
+    }
+
+    /// How long to wait after the last target exits: `restore_delay_secs`
+    /// or `hold_after_exit_minutes`, whichever is longer.
+    pub fn restore_delay(&self) -> Duration {
+        Duration::from_secs(self.restore_delay_secs.max(self.hold_after_exit_minutes.saturating_mul(60)))
+    }
+
+    /// Writes the config back to the file it was loaded from.
+    pub fn save(&self) -> Result<(), AppError> {
+        if self.version > CONFIG_VERSION {
+            return Err(AppError::ConfigNewer(self.version));
+        }
+        let config_path = self.path();
+        let content = match self.format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(|e| AppError::ConfigSerialize(e.to_string()))?,
+            ConfigFormat::Toml => self.to_toml_preserving(fs::read_to_string(&config_path).ok().as_deref())?,
+        };
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir).map_err(|source| AppError::ConfigIo {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(&config_path, content).map_err(|source| AppError::ConfigIo {
+            path: config_path.clone(),
+            source,
+        })
+    }
+
+    /// The file this config was loaded from and is saved to.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(Self::config_path)
+    }
+
+    /// The file `load` reads: the one given with `--config`, otherwise
+    /// config.toml when present, otherwise config.json.
+    pub fn config_path() -> PathBuf {
+        match config_override() {
+            Some(path) => path,
+            None => Self::path_in(&config_dir()),
+        }
+    }
+
+    /// The config file in `dir`: config.toml when present, otherwise config.json.
+    pub fn path_in(dir: &Path) -> PathBuf {
+        let toml_path = dir.join(TOML_CONFIG_FILE);
+        if toml_path.exists() {
+            toml_path
+        } else {
+            dir.join(CONFIG_FILE)
+        }
+    }
+
+    /// Serializes to TOML, reusing `existing` as a template so that comments
+    /// and formatting around keys whose values didn't change survive a save.
+    fn to_toml_preserving(&self, existing: Option<&str>) -> Result<String, AppError> {
+        let fresh = toml::to_string_pretty(self).map_err(|e| AppError::ConfigSerialize(e.to_string()))?;
+        let Some(existing) = existing else {
+            return Ok(fresh);
+        };
+        let (Ok(mut document), Ok(old_values)) = (
+            existing.parse::<toml_edit::DocumentMut>(),
+            toml::from_str::<toml::Table>(existing),
+        ) else {
+            return Ok(fresh);
+        };
+        let fresh_document: toml_edit::DocumentMut =
+            fresh.parse().map_err(|e: toml_edit::TomlError| AppError::ConfigSerialize(e.to_string()))?;
+        let new_values: toml::Table = toml::from_str(&fresh).map_err(|e| AppError::ConfigSerialize(e.to_string()))?;
+
+        let removed: Vec<String> = document
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| !new_values.contains_key(key))
+            .collect();
+        for key in removed {
+            document.remove(&key);
+        }
+        for (key, item) in fresh_document.iter() {
+            if old_values.get(key) != new_values.get(key) {
+                document[key] = item.clone();
+            }
+        }
+        Ok(document.to_string())
+    }
+}
+
+fn json_path() -> PathBuf {
+    config_dir().join(CONFIG_FILE)
+}
+
+fn format_of(path: &Path) -> ConfigFormat {
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml")) {
+        ConfigFormat::Toml
+    } else {
+        ConfigFormat::Json
+    }
+}
+
+/// The error with, for a wrong value rather than broken syntax, the line
+/// it's on, so the field is named even where serde only knows the type.
+fn json_error_message(error: &serde_json::Error, content: &str) -> String {
+    if error.classify() != serde_json::error::Category::Data {
+        return error.to_string();
+    }
+    match content.lines().nth(error.line().saturating_sub(1)).map(str::trim) {
+        Some(line) if !line.is_empty() => format!("{} ({})", error, line),
+        _ => error.to_string(),
+    }
+}
+
+fn toml_error_message(error: &toml::de::Error, content: &str) -> String {
+    match error.span() {
+        Some(span) => {
+            let line = content[..span.start.min(content.len())].matches('\n').count() + 1;
+            format!("{} at line {}", error.message(), line)
+        }
+        None => error.message().to_string(),
+    }
+}
+
+/// Upgrades the contents of a config file one version at a time, from the
+/// version it was written in to `CONFIG_VERSION`, and reads the result. A
+/// newer version is read as far as this one understands it and keeps its
+/// `version`, so it isn't saved over.
+pub fn migrate(mut value: Value) -> Result<Config, serde_json::Error> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version < 1 {
+        migrate_v0(&mut value);
+    }
+    let mut config: Config = serde_json::from_value(value)?;
+    config.ensure_active_profile();
+    Ok(config)
+}
+
+/// Version 0 has no `version`. Before profiles existed it kept one
+/// profile's fields at the top level; those move into a "Default" profile.
+/// Version 0 files written after profiles were added already have them, and
+/// stray top-level fields are dropped as they always were.
+fn migrate_v0(value: &mut Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+    let mut flat: Map<String, Value> = FLAT_PROFILE_FIELDS
+        .iter()
+        .filter_map(|field| object.remove(*field).map(|v| (field.to_string(), v)))
+        .collect();
+    let has_profiles = object.get("profiles").and_then(Value::as_array).is_some_and(|p| !p.is_empty());
+    if !has_profiles {
+        if let Some(target) = flat.remove("target_exe") {
+            flat.entry("targets").or_insert(target);
+        }
+        flat.insert("name".to_string(), Value::from(DEFAULT_PROFILE));
+        object.insert("profiles".to_string(), Value::Array(vec![Value::Object(flat)]));
+    }
+    object.insert("version".to_string(), Value::from(1));
+}
+
+/// The file given with `--config PATH` or `--config=PATH`, if any.
+pub fn config_override() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix(CONFIG_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Where config.json and other app data live: `%APPDATA%\MonitorManager`,
+/// or the exe directory in portable mode (`--portable` or a `portable.txt`
+/// marker next to the exe).
+pub fn config_dir() -> PathBuf {
+    let portable_flag = std::env::args().any(|arg| arg == "--portable");
+    resolve_config_dir(exe_dir(), portable_flag, std::env::var_os("APPDATA"))
+}
+
+/// `config_dir` for an exe in `exe_dir`, given whether `--portable` was
+/// passed and the value of `%APPDATA%`.
+fn resolve_config_dir(exe_dir: PathBuf, portable_flag: bool, appdata: Option<OsString>) -> PathBuf {
+    if portable_flag || exe_dir.join(PORTABLE_MARKER).exists() {
+        return exe_dir;
+    }
+    match appdata {
+        Some(appdata) => PathBuf::from(appdata).join(APP_DIR_NAME),
+        None => exe_dir,
+    }
+}
+
+/// Looked up once, so a failure is only logged once.
+fn exe_dir() -> PathBuf {
+    static EXE_DIR: OnceLock<PathBuf> = OnceLock::new();
+    EXE_DIR
+        .get_or_init(|| match std::env::current_exe() {
+            Ok(mut path) => {
+                path.pop();
+                path
+            }
+            Err(e) => {
+                log::error!("Could not find the running exe, using the current directory: {}", e);
+                std::env::current_dir().unwrap_or_default()
+            }
+        })
+        .clone()
+}
+
+/// Copies a config.json left in `exe_dir` by older versions to
+/// `config_path`, unless a config already exists there.
+fn migrate_from_exe_dir(exe_dir: &Path, config_path: &Path) {
+    let legacy_path = exe_dir.join(CONFIG_FILE);
+    let toml_exists = config_path.with_file_name(TOML_CONFIG_FILE).exists();
+    if legacy_path == config_path || config_path.exists() || toml_exists || !legacy_path.exists() {
+        return;
+    }
+    if let Some(dir) = config_path.parent() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            log::warn!("Could not create {}: {}", dir.display(), e);
+            return;
+        }
+    }
+    match fs::copy(&legacy_path, config_path) {
+        Ok(_) => log::info!("Copied {} to {}", legacy_path.display(), config_path.display()),
+        Err(e) => log::warn!("Could not copy {} to {}: {}", legacy_path.display(), config_path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a config fixture from tests/fixtures/config.
+    macro_rules! fixture {
+        ($name:literal) => {
+            serde_json::from_str::<Value>(include_str!(concat!("../tests/fixtures/config/", $name, ".json"))).unwrap()
+        };
+    }
+
+    const TOML_CONFIG: &str = r#"# Written by hand
+version = 1
+active_profile = "Gaming"
+poll_interval_ms = 1000
+
+[[profiles]]
+name = "Gaming"
+targets = ['C:\Games\Hades\Hades.exe']
+strategy = "blank"
+"#;
+
+    #[test]
+    fn reads_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let json = r#"{ "version": 1, "keep_awake": true, "profiles": [{ "name": "Gaming", "targets": "a.exe" }] }"#;
+        fs::write(dir.join(CONFIG_FILE), json).unwrap();
+        let config = Config::read_from(&Config::path_in(dir)).unwrap();
+        assert_eq!(config.format, ConfigFormat::Json);
+        assert_eq!(config.path(), dir.join(CONFIG_FILE));
+        assert_eq!(config.active_profile, "Gaming");
+        assert_eq!(config.profile().targets, ["a.exe"]);
+        assert!(config.keep_awake);
+    }
+
+    #[test]
+    fn reads_toml_in_preference_to_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join(CONFIG_FILE), r#"{ "version": 1, "poll_interval_ms": 5000 }"#).unwrap();
+        fs::write(dir.join(TOML_CONFIG_FILE), TOML_CONFIG).unwrap();
+        let config = Config::read_from(&Config::path_in(dir)).unwrap();
+        assert_eq!(config.format, ConfigFormat::Toml);
+        assert_eq!(config.path(), dir.join(TOML_CONFIG_FILE));
+        assert_eq!(config.poll_interval_ms, 1000);
+        assert_eq!(config.profile().targets, [r"C:\Games\Hades\Hades.exe"]);
+        assert_eq!(config.profile().strategy, Strategy::Blank);
+    }
+
+    #[test]
+    fn saving_toml_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TOML_CONFIG_FILE);
+        fs::write(&path, TOML_CONFIG).unwrap();
+        let mut config = Config::read_from(&path).unwrap();
+        config.poll_interval_ms = 500;
+        config.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# Written by hand"), "{}", saved);
+        assert!(saved.contains("poll_interval_ms = 500"), "{}", saved);
+        assert_eq!(Config::read_from(&path).unwrap().poll_interval_ms, 500);
+    }
+
+    #[test]
+    fn broken_file_is_reported_with_its_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "{\n  \"version\": 1,\n  \"poll_interval_ms\": \"fast\"\n}").unwrap();
+        let Err(AppError::ConfigParse(message)) = Config::read_from(&path) else {
+            panic!("a wrong value should fail to parse");
+        };
+        assert!(message.contains(r#""poll_interval_ms": "fast""#), "{}", message);
+    }
+
+    #[test]
+    fn config_dir_is_under_appdata_unless_portable() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe_dir = dir.path().to_path_buf();
+        let appdata = Some(OsString::from(r"C:\Users\me\AppData\Roaming"));
+        let expected = Path::new(r"C:\Users\me\AppData\Roaming").join(APP_DIR_NAME);
+        assert_eq!(resolve_config_dir(exe_dir.clone(), false, appdata.clone()), expected);
+        assert_eq!(resolve_config_dir(exe_dir.clone(), true, appdata.clone()), exe_dir);
+        // Without %APPDATA% there's nowhere else to go.
+        assert_eq!(resolve_config_dir(exe_dir.clone(), false, None), exe_dir);
+
+        fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(resolve_config_dir(exe_dir.clone(), false, appdata), exe_dir);
+    }
+
+    /// An exe directory in `dir` holding an old config.json, and where the config goes now.
+    fn legacy_install(dir: &Path) -> (PathBuf, PathBuf) {
+        let exe_dir = dir.join("exe");
+        fs::create_dir_all(&exe_dir).unwrap();
+        fs::write(exe_dir.join(CONFIG_FILE), r#"{ "target_exe": "Hades.exe" }"#).unwrap();
+        (exe_dir, dir.join("appdata").join(APP_DIR_NAME).join(CONFIG_FILE))
+    }
+
+    #[test]
+    fn config_next_to_the_exe_is_copied_to_appdata() {
+        let dir = tempfile::tempdir().unwrap();
+        let (exe_dir, config_path) = legacy_install(dir.path());
+        migrate_from_exe_dir(&exe_dir, &config_path);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), r#"{ "target_exe": "Hades.exe" }"#);
+        // The old file is left alone.
+        assert!(exe_dir.join(CONFIG_FILE).exists());
+    }
+
+    #[test]
+    fn existing_config_is_not_overwritten_by_the_old_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let (exe_dir, config_path) = legacy_install(dir.path());
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "{}").unwrap();
+        migrate_from_exe_dir(&exe_dir, &config_path);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "{}");
+
+        let dir = tempfile::tempdir().unwrap();
+        let (exe_dir, config_path) = legacy_install(dir.path());
+        let toml_path = config_path.with_file_name(TOML_CONFIG_FILE);
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&toml_path, "version = 1").unwrap();
+        migrate_from_exe_dir(&exe_dir, &config_path);
+        assert!(!config_path.exists());
+    }
+
+    #[test]
+    fn portable_config_is_left_where_it_is() {
+        let dir = tempfile::tempdir().unwrap();
+        let (exe_dir, _) = legacy_install(dir.path());
+        let config_path = exe_dir.join(CONFIG_FILE);
+        migrate_from_exe_dir(&exe_dir, &config_path);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), r#"{ "target_exe": "Hades.exe" }"#);
+
+        // Nothing to copy.
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("appdata").join(CONFIG_FILE);
+        migrate_from_exe_dir(&dir.path().join("exe"), &config_path);
+        assert!(!config_path.exists());
+    }
+
+    #[test]
+    fn migrates_a_single_target_exe() {
+        let config = migrate(fixture!("v0_target_exe")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.active_profile, DEFAULT_PROFILE);
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.profile().targets, [r"C:\Riot Games\League of Legends\Game\League of Legends.exe"]);
+        assert_eq!(config.profile().strategy, Strategy::Disable);
+        assert_eq!(config.poll_interval_ms, DEFAULT_POLL_INTERVAL_MS);
+    }
+
+    #[test]
+    fn migrates_a_target_list() {
+        let config = migrate(fixture!("v0_targets")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.profile().name, DEFAULT_PROFILE);
+        assert_eq!(config.profile().targets, [r"C:\Games\Elden Ring\eldenring.exe", r"C:\Games\Hades\Hades.exe"]);
+        assert_eq!(config.poll_interval_ms, 1000);
+    }
+
+    #[test]
+    fn migrates_top_level_profile_fields_into_a_profile() {
+        let config = migrate(fixture!("v0_flat_profile")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let profile = config.profile();
+        assert_eq!(profile.name, DEFAULT_PROFILE);
+        assert_eq!(profile.targets, ["eldenring.exe", "Hades.exe"]);
+        assert_eq!(profile.match_mode, MatchMode::FileName);
+        assert_eq!(profile.rules.len(), 1);
+        assert_eq!(profile.rules[0].target, "Hades.exe");
+        assert_eq!(profile.rules[0].monitors, MonitorSelection::Devices(vec![r"\\.\DISPLAY3".to_string()]));
+        assert_eq!(profile.strategy, Strategy::ReduceRefreshRate { hz: 60 });
+        assert_eq!(profile.keep_enabled.as_deref(), Some(r"\\.\DISPLAY2"));
+        // Settings that were always top level stay there.
+        assert_eq!(config.process_detection, ProcessDetection::Polling);
+        assert_eq!((config.disable_delay_secs, config.restore_delay_secs), (5, 10));
+        assert_eq!(config.display_backend, DisplayBackend::Ccd);
+        assert!(!config.persist_changes && !config.notifications);
+        assert_eq!(config.hotkey.as_deref(), Some("ctrl+shift+f12"));
+    }
+
+    #[test]
+    fn migrates_profiles_without_a_version() {
+        let config = migrate(fixture!("v0_profiles")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let names: Vec<&str> = config.profiles.iter().map(|profile| profile.name.as_str()).collect();
+        assert_eq!(names, ["Gaming", "Streaming"]);
+        assert_eq!(config.profile().name, "Streaming");
+        assert_eq!(config.profile().strategy, Strategy::SwitchPrimary);
+        assert!(!config.restore_on_exit);
+    }
+
+    #[test]
+    fn reads_the_current_version_as_is() {
+        let config = migrate(fixture!("v1")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.profile().strategy, Strategy::Blank);
+        assert_eq!(config.excluded_monitors, [r"\\.\DISPLAY4"]);
+    }
+
+    #[test]
+    fn migrated_config_survives_a_save() {
+        let config = migrate(fixture!("v0_flat_profile")).unwrap();
+        let saved = serde_json::to_value(&config).unwrap();
+        let reread = migrate(saved.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reread).unwrap(), saved);
+    }
+
+    #[test]
+    fn turned_off_hotkey_stays_off() {
+        let config = Config { hotkey: None, ..Config::default() };
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["hotkey"], "");
+        assert_eq!(migrate(json).unwrap().hotkey, None);
+        let toml: Value = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(migrate(toml).unwrap().hotkey, None);
+        // Written by hand, or by versions that saved `None` as null.
+        assert_eq!(migrate(serde_json::json!({ "version": 1, "hotkey": null })).unwrap().hotkey, None);
+    }
+
+    #[test]
+    fn hotkey_round_trips() {
+        let config = Config { hotkey: Some("win+shift+f9".to_string()), ..Config::default() };
+        let toml: Value = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(migrate(toml).unwrap().hotkey.as_deref(), Some("win+shift+f9"));
+        assert_eq!(migrate(serde_json::json!({ "version": 1 })).unwrap().hotkey, default_hotkey());
+    }
+
+    #[test]
+    fn refuses_to_save_over_a_newer_version() {
+        let config = migrate(fixture!("v2_newer")).unwrap();
+        assert_eq!(config.version, 2);
+        assert_eq!(config.profile().name, "Gaming");
+        assert!(matches!(config.save(), Err(AppError::ConfigNewer(2))));
+    }
+}
@@ -0,0 +1,110 @@
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW};
+use windows::Win32::UI::WindowsAndMessaging::{ClipCursor, GetClipCursor, GetForegroundWindow, GetWindowThreadProcessId};
+
+use crate::{lock, AppState};
+
+/// Windows drops the clip when another window takes the focus, so it's
+/// checked this often while a target runs.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the loop wants the cursor kept. The clip thread applies it while one
+/// of `pids` has the foreground window.
+#[derive(Debug, Clone, Default)]
+pub struct CursorClip {
+    /// The kept monitor; `None` leaves the cursor free.
+    pub device_name: Option<String>,
+    /// The target's processes.
+    pub pids: Vec<u32>,
+    /// Set from the tray until the target exits.
+    pub released: bool,
+}
+
+impl CursorClip {
+    /// The monitor to confine the cursor to right now, if any.
+    fn wanted(&self) -> Option<&str> {
+        if self.released || self.pids.is_empty() {
+            return None;
+        }
+        let mut pid = 0u32;
+        unsafe {
+            GetWindowThreadProcessId(GetForegroundWindow(), Some(&mut pid));
+        }
+        self.device_name.as_deref().filter(|_| self.pids.contains(&pid))
+    }
+}
+
+/// Starts the thread that keeps the cursor clipped as `AppState.cursor`
+/// says. It lets go of the cursor and returns once `shutdown` is set.
+pub fn spawn(state: Arc<Mutex<AppState>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let shutdown = lock(&state).shutdown.clone();
+        let mut clipped = false;
+        while !shutdown.is_requested() {
+            let wanted = { lock(&state).cursor.wanted().map(str::to_string) };
+            match wanted.as_deref().and_then(monitor_rect) {
+                Some(rect) => {
+                    if !is_clipped_to(&rect) {
+                        if let Err(e) = unsafe { ClipCursor(Some(&rect as *const RECT)) } {
+                            log::warn!("Could not confine the cursor: {}", e.message());
+                        }
+                    }
+                    clipped = true;
+                }
+                None if clipped => {
+                    release();
+                    clipped = false;
+                }
+                None => {}
+            }
+            shutdown.wait(CHECK_INTERVAL);
+        }
+        if clipped {
+            release();
+        }
+    })
+}
+
+fn release() {
+    unsafe {
+        let _ = ClipCursor(None);
+    }
+}
+
+fn is_clipped_to(rect: &RECT) -> bool {
+    let mut current = RECT::default();
+    unsafe { GetClipCursor(&mut current) }.is_ok() && current == *rect
+}
+
+/// The desktop area of the attached monitor called `device_name`.
+fn monitor_rect(device_name: &str) -> Option<RECT> {
+    unsafe extern "system" fn visit(monitor: HMONITOR, _: HDC, _: *mut RECT, data: LPARAM) -> BOOL {
+        let found = &mut *(data.0 as *mut (&str, Option<RECT>));
+        let mut info = MONITORINFOEXW {
+            monitorInfo: MONITORINFO {
+                cbSize: mem::size_of::<MONITORINFOEXW>() as u32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        if GetMonitorInfoW(monitor, &mut info.monitorInfo).as_bool() {
+            let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+            if String::from_utf16_lossy(&info.szDevice[..len]).eq_ignore_ascii_case(found.0) {
+                found.1 = Some(info.monitorInfo.rcMonitor);
+                return false.into();
+            }
+        }
+        true.into()
+    }
+
+    let mut found: (&str, Option<RECT>) = (device_name, None);
+    unsafe {
+        let _ = EnumDisplayMonitors(HDC::default(), None, Some(visit), LPARAM(&mut found as *mut _ as isize));
+    }
+    found.1
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::mem;
+use windows::Win32::Devices::Display::*;
+use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+use windows::Win32::Graphics::Gdi::*;
+
+/// VCP code 0xD6, "Power Mode", from the MCCS standard.
+const VCP_POWER_MODE: u8 = 0xD6;
+const POWER_ON: u32 = 0x01;
+const POWER_OFF: u32 = 0x05;
+
+/// Why a DDC/CI command couldn't be sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DdcError {
+    /// No attached display has this device name.
+    MonitorNotFound,
+    /// The display exists but exposes no physical monitor handles.
+    NoPhysicalMonitors,
+    /// The monitor didn't accept the VCP command, usually because DDC/CI is
+    /// unsupported or switched off in its on-screen menu.
+    Unsupported(windows::core::Error),
+}
+
+impl fmt::Display for DdcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MonitorNotFound => write!(f, "no attached display with that name"),
+            Self::NoPhysicalMonitors => write!(f, "no physical monitor behind the display"),
+            Self::Unsupported(e) => write!(f, "DDC/CI not supported ({})", e.message()),
+        }
+    }
+}
+
+/// Physical monitor handles for one `HMONITOR`, destroyed on drop so every
+/// exit path releases them.
+struct PhysicalMonitors(Vec<PHYSICAL_MONITOR>);
+
+impl PhysicalMonitors {
+    fn open(hmonitor: HMONITOR) -> Result<Self, DdcError> {
+        let mut count = 0u32;
+        unsafe { GetNumberOfPhysicalMonitorsFromHMONITOR(hmonitor, &mut count) }.map_err(DdcError::Unsupported)?;
+        if count == 0 {
+            return Err(DdcError::NoPhysicalMonitors);
+        }
+        let mut monitors = vec![PHYSICAL_MONITOR::default(); count as usize];
+        unsafe { GetPhysicalMonitorsFromHMONITOR(hmonitor, &mut monitors) }.map_err(DdcError::Unsupported)?;
+        Ok(Self(monitors))
+    }
+}
+
+impl Drop for PhysicalMonitors {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { DestroyPhysicalMonitors(&self.0) } {
+            log::warn!("Could not release physical monitor handles: {}", e.message());
+        }
+    }
+}
+
+unsafe extern "system" fn collect_monitor(hmonitor: HMONITOR, _: HDC, _: *mut RECT, data: LPARAM) -> BOOL {
+    let monitors = &mut *(data.0 as *mut Vec<HMONITOR>);
+    monitors.push(hmonitor);
+    true.into()
+}
+
+fn find_hmonitor(device_name: &str) -> Option<HMONITOR> {
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(collect_monitor),
+            LPARAM(&mut monitors as *mut Vec<HMONITOR> as isize),
+        );
+    }
+
+    monitors.into_iter().find(|&hmonitor| {
+        let mut info: MONITORINFOEXW = unsafe { mem::zeroed() };
+        info.monitorInfo.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+        if !unsafe { GetMonitorInfoW(hmonitor, &mut info.monitorInfo) }.as_bool() {
+            return false;
+        }
+        let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+        String::from_utf16_lossy(&info.szDevice[..len]).eq_ignore_ascii_case(device_name)
+    })
+}
+
+fn set_power_mode(device_name: &str, value: u32) -> Result<(), DdcError> {
+    let hmonitor = find_hmonitor(device_name).ok_or(DdcError::MonitorNotFound)?;
+    let physical = PhysicalMonitors::open(hmonitor)?;
+    for monitor in &physical.0 {
+        if unsafe { SetVCPFeature(monitor.hPhysicalMonitor, VCP_POWER_MODE, value) } == 0 {
+            return Err(DdcError::Unsupported(windows::core::Error::from_win32()));
+        }
+    }
+    Ok(())
+}
+
+/// Puts the monitor behind `device_name` into its power-off state over DDC/CI.
+/// It stays attached to the desktop, so Windows doesn't rearrange anything.
+pub fn power_off(device_name: &str) -> Result<(), DdcError> {
+    set_power_mode(device_name, POWER_OFF)
+}
+
+/// Wakes a monitor switched off with `power_off`.
+pub fn power_on(device_name: &str) -> Result<(), DdcError> {
+    set_power_mode(device_name, POWER_ON)
+}
@@ -0,0 +1,566 @@
+use std::mem;
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Gdi::{
+    ChangeDisplaySettingsExW, EnumDisplayDevicesW, EnumDisplaySettingsW, CDS_TYPE, DEVMODEW,
+    DISPLAY_DEVICEW, ENUM_DISPLAY_SETTINGS_MODE,
+};
+use windows::Win32::UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME;
+
+use crate::ccd::{self, Topology};
+use crate::ddc::{self, DdcError};
+use crate::gamma::GammaRamp;
+use crate::monitor::{DisplayChangeError, MonitorInfo};
+use crate::overlay::Overlays;
+use crate::window_layout::{self, SavedWindow};
+use crate::color_profile;
+
+/// A display device as enumerated, before its mode is read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayDevice {
+    pub device_name: String,
+    pub description: String,
+    /// Device interface path of the attached monitor.
+    pub stable_id: Option<String>,
+    pub monitor_name: Option<String>,
+    /// Raw `DISPLAY_DEVICE_*` state flags.
+    pub state_flags: u32,
+}
+
+/// What an attached device has beyond its mode, read only when asked for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceExtras {
+    /// File name of the monitor's default ICC profile.
+    pub color_profile: Option<String>,
+    /// Scaling in percent.
+    pub scale_percent: Option<u32>,
+    /// Whether the monitor can do HDR, and whether it's on.
+    pub hdr_supported: bool,
+    pub hdr_enabled: bool,
+}
+
+/// The display calls `MonitorManager` makes, so they can be swapped out.
+/// Every Win32 call that reads or changes a display goes through here.
+pub trait DisplayApi: Send {
+    /// Every display adapter output, attached to the desktop or not.
+    fn enumerate_devices(&self) -> Vec<DisplayDevice>;
+
+    /// The color profile, scaling and HDR state of each device, in order.
+    /// Takes a topology query and a registry read per device, so it's kept
+    /// out of `enumerate_devices`, which the idle loop calls every tick.
+    fn device_extras(&self, device_names: &[String]) -> Vec<DeviceExtras>;
+
+    /// The device's mode for `mode`: `ENUM_CURRENT_SETTINGS`,
+    /// `ENUM_REGISTRY_SETTINGS` or an index into its mode list.
+    fn get_settings(&self, device_name: &str, mode: ENUM_DISPLAY_SETTINGS_MODE) -> Option<DEVMODEW>;
+
+    /// Changes one device, or with `None` for both commits what was staged.
+    fn apply_settings(
+        &self,
+        device_name: Option<&str>,
+        dev_mode: Option<&DEVMODEW>,
+        flags: CDS_TYPE,
+    ) -> Result<(), DisplayChangeError>;
+
+    /// The paths that currently drive a display, for the CCD backend.
+    fn query_topology(&self) -> windows::core::Result<Topology>;
+
+    /// Makes `topology` the active one; see `Topology::apply`.
+    fn apply_topology(&self, topology: &Topology, persist: bool) -> windows::core::Result<()>;
+
+    /// Drops the devices from the active topology in one call.
+    fn detach_paths(&self, device_names: &[String], persist: bool) -> windows::core::Result<()>;
+
+    /// Turns HDR (advanced color) on or off.
+    fn set_advanced_color(&self, device_name: &str, enabled: bool) -> windows::core::Result<()>;
+
+    /// Sets the device's scaling in percent.
+    fn set_dpi_scale(&self, device_name: &str, percent: u32) -> windows::core::Result<()>;
+
+    /// The gamma ramp the device uses now, if its driver allows reading it.
+    fn capture_gamma(&self, device_name: &str) -> Option<GammaRamp>;
+
+    /// Loads a gamma ramp back; failures are only logged.
+    fn apply_gamma(&self, device_name: &str, ramp: &GammaRamp);
+
+    /// Makes `profile` the device's default ICC profile again.
+    fn associate_color_profile(&self, device_name: &str, profile: &str);
+
+    fn power_off(&self, device_name: &str) -> Result<(), DdcError>;
+
+    fn power_on(&self, device_name: &str) -> Result<(), DdcError>;
+
+    /// Covers each rect with a black window until the returned guard is
+    /// dropped. Returns one result per rect, in order.
+    fn show_overlays(&self, rects: Vec<RECT>) -> (Box<dyn Send>, Vec<windows::core::Result<()>>);
+
+    /// Where the top-level windows on `monitors` are.
+    fn capture_windows(&self, monitors: &[MonitorInfo]) -> Vec<SavedWindow>;
+
+    /// Moves the windows back where `capture_windows` found them.
+    fn restore_windows(&self, windows: &[SavedWindow], monitors: &[MonitorInfo]);
+}
+
+/// `EnumDisplayDevicesW`, `EnumDisplaySettingsW` and `ChangeDisplaySettingsExW`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WinApi;
+
+impl DisplayApi for WinApi {
+    fn enumerate_devices(&self) -> Vec<DisplayDevice> {
+        let mut devices = Vec::new();
+        for i in 0.. {
+            let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
+            display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
+            if !unsafe { EnumDisplayDevicesW(PCWSTR::null(), i, &mut display_device, 0) }.as_bool() {
+                break;
+            }
+            let device_name = wide_to_string(&display_device.DeviceName);
+            let (stable_id, monitor_name) = query_monitor_identity(&device_name);
+            devices.push(DisplayDevice {
+                device_name,
+                description: wide_to_string(&display_device.DeviceString),
+                stable_id,
+                monitor_name,
+                state_flags: display_device.StateFlags,
+            });
+        }
+        devices
+    }
+
+    fn device_extras(&self, device_names: &[String]) -> Vec<DeviceExtras> {
+        let sources = ccd::source_states();
+        device_names
+            .iter()
+            .map(|device_name| {
+                let source = sources.iter().find(|source| source.device_name.eq_ignore_ascii_case(device_name));
+                let advanced_color = source.and_then(|source| source.advanced_color);
+                DeviceExtras {
+                    color_profile: color_profile::default_profile(device_name),
+                    scale_percent: source.and_then(|source| source.scale_percent),
+                    hdr_supported: advanced_color.is_some_and(|color| color.supported),
+                    hdr_enabled: advanced_color.is_some_and(|color| color.enabled),
+                }
+            })
+            .collect()
+    }
+
+    fn get_settings(&self, device_name: &str, mode: ENUM_DISPLAY_SETTINGS_MODE) -> Option<DEVMODEW> {
+        let name_wide = device_name_wide(device_name);
+        let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+        dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        unsafe { EnumDisplaySettingsW(PCWSTR(name_wide.as_ptr()), mode, &mut dev_mode) }
+            .as_bool()
+            .then_some(dev_mode)
+    }
+
+    fn apply_settings(
+        &self,
+        device_name: Option<&str>,
+        dev_mode: Option<&DEVMODEW>,
+        flags: CDS_TYPE,
+    ) -> Result<(), DisplayChangeError> {
+        let name_wide = device_name.map(device_name_wide);
+        let name = name_wide.as_ref().map_or(PCWSTR::null(), |name| PCWSTR(name.as_ptr()));
+        let result = unsafe { ChangeDisplaySettingsExW(name, dev_mode.map(|m| m as *const DEVMODEW), None, flags, None) };
+        DisplayChangeError::check(result)
+    }
+
+    fn query_topology(&self) -> windows::core::Result<Topology> {
+        Topology::query_active()
+    }
+
+    fn apply_topology(&self, topology: &Topology, persist: bool) -> windows::core::Result<()> {
+        topology.apply(persist)
+    }
+
+    fn detach_paths(&self, device_names: &[String], persist: bool) -> windows::core::Result<()> {
+        ccd::detach(device_names, persist)
+    }
+
+    fn set_advanced_color(&self, device_name: &str, enabled: bool) -> windows::core::Result<()> {
+        ccd::set_advanced_color(device_name, enabled)
+    }
+
+    fn set_dpi_scale(&self, device_name: &str, percent: u32) -> windows::core::Result<()> {
+        ccd::set_dpi_scale(device_name, percent)
+    }
+
+    fn capture_gamma(&self, device_name: &str) -> Option<GammaRamp> {
+        GammaRamp::capture(device_name)
+    }
+
+    fn apply_gamma(&self, device_name: &str, ramp: &GammaRamp) {
+        ramp.apply(device_name);
+    }
+
+    fn associate_color_profile(&self, device_name: &str, profile: &str) {
+        color_profile::associate(device_name, profile);
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<(), DdcError> {
+        ddc::power_off(device_name)
+    }
+
+    fn power_on(&self, device_name: &str) -> Result<(), DdcError> {
+        ddc::power_on(device_name)
+    }
+
+    fn show_overlays(&self, rects: Vec<RECT>) -> (Box<dyn Send>, Vec<windows::core::Result<()>>) {
+        let (overlays, results) = Overlays::show(rects);
+        (Box::new(overlays), results)
+    }
+
+    fn capture_windows(&self, monitors: &[MonitorInfo]) -> Vec<SavedWindow> {
+        window_layout::capture(monitors)
+    }
+
+    fn restore_windows(&self, windows: &[SavedWindow], monitors: &[MonitorInfo]) {
+        window_layout::restore(windows, monitors);
+    }
+}
+
+/// Asks the adapter for its first attached monitor and returns that
+/// monitor's device interface path and name.
+fn query_monitor_identity(adapter_name: &str) -> (Option<String>, Option<String>) {
+    let mut monitor_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
+    monitor_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
+    let name_wide = device_name_wide(adapter_name);
+
+    let found = unsafe {
+        EnumDisplayDevicesW(
+            PCWSTR(name_wide.as_ptr()),
+            0,
+            &mut monitor_device,
+            EDD_GET_DEVICE_INTERFACE_NAME,
+        )
+        .as_bool()
+    };
+    if !found {
+        return (None, None);
+    }
+
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    (
+        non_empty(wide_to_string(&monitor_device.DeviceID)),
+        non_empty(wide_to_string(&monitor_device.DeviceString)),
+    )
+}
+
+fn device_name_wide(device_name: &str) -> Vec<u16> {
+    device_name.encode_utf16().chain(Some(0)).collect()
+}
+
+fn wide_to_string(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
+
+/// Scripted displays for the tests.
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use windows::Win32::Foundation::{E_NOTIMPL, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        CDS_NORESET, CDS_SET_PRIMARY, CDS_TYPE, DEVMODEW, DISPLAY_DEVICE_ATTACHED_TO_DESKTOP,
+        DISPLAY_DEVICE_PRIMARY_DEVICE, DM_DISPLAYFREQUENCY, DM_DISPLAYORIENTATION, DM_PELSWIDTH, DM_POSITION,
+        ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE, ENUM_REGISTRY_SETTINGS,
+    };
+
+    use super::{DeviceExtras, DisplayApi, DisplayDevice};
+    use crate::ccd::Topology;
+    use crate::ddc::DdcError;
+    use crate::gamma::GammaRamp;
+    use crate::monitor::{DisplayChangeError, DisplayMode, MonitorInfo};
+    use crate::window_layout::SavedWindow;
+
+    /// Monitors that behave like `ChangeDisplaySettingsExW` would have them:
+    /// a 0x0 mode detaches, `CDS_NORESET` stages until the next commit and
+    /// `CDS_SET_PRIMARY` moves the primary. Clones share their state, so a
+    /// test keeps one and hands another to `MonitorManager::with_api`.
+    #[derive(Clone, Default)]
+    pub struct MockDisplayApi {
+        state: Arc<Mutex<MockState>>,
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        devices: Vec<(DisplayDevice, DisplayMode)>,
+        /// Scaling and HDR state by device name.
+        extras: HashMap<String, DeviceExtras>,
+        /// Devices whose changes fail with `DISP_CHANGE_FAILED`.
+        failing: Vec<String>,
+        /// Changes made with `CDS_NORESET`, applied by the next commit.
+        staged: Vec<(String, DEVMODEW, CDS_TYPE)>,
+        /// Devices whose next change reports success but doesn't happen.
+        ignoring: Vec<String>,
+        /// The devices as they were at the last `query_topology`.
+        topology: Option<Vec<(DisplayDevice, DisplayMode)>>,
+    }
+
+    impl MockDisplayApi {
+        /// Adds an attached 1920x1080 60Hz monitor with its top left corner at `position`.
+        pub fn add_monitor(&self, device_name: &str, primary: bool, position: (i32, i32)) {
+            let mut state = self.state.lock().unwrap();
+            let mut state_flags = DISPLAY_DEVICE_ATTACHED_TO_DESKTOP;
+            if primary {
+                state_flags |= DISPLAY_DEVICE_PRIMARY_DEVICE;
+            }
+            let index = state.devices.len() + 1;
+            let device = DisplayDevice {
+                device_name: device_name.to_string(),
+                description: format!("Mock Adapter {}", index),
+                stable_id: Some(format!(r"\\?\DISPLAY#MCK{:04}#{}", index, index)),
+                monitor_name: Some(format!("Mock Monitor {}", index)),
+                state_flags,
+            };
+            let mode = DisplayMode {
+                width: 1920,
+                height: 1080,
+                position_x: position.0,
+                position_y: position.1,
+                frequency: 60,
+                bits_per_pel: 32,
+                orientation: 0,
+            };
+            state.devices.push((device, mode));
+            let extras = DeviceExtras { scale_percent: Some(100), ..DeviceExtras::default() };
+            state.extras.insert(device_name.to_string(), extras);
+        }
+
+        /// Makes every later change to the device fail.
+        pub fn fail_changes(&self, device_name: &str) {
+            self.state.lock().unwrap().failing.push(device_name.to_string());
+        }
+
+        /// Makes the device's next change report success without happening,
+        /// like a monitor that stays dark after the driver reloads.
+        pub fn ignore_next_change(&self, device_name: &str) {
+            self.state.lock().unwrap().ignoring.push(device_name.to_string());
+        }
+
+        /// Disconnects the device.
+        pub fn unplug(&self, device_name: &str) {
+            self.state.lock().unwrap().devices.retain(|(device, _)| device.device_name != device_name);
+        }
+
+        /// Detaches the device behind the manager's back.
+        pub fn detach(&self, device_name: &str) {
+            self.state.lock().unwrap().apply(device_name, &detached(), CDS_TYPE(0));
+        }
+
+        /// Swaps two devices' names, the way a reconnect or dock renumbers them.
+        pub fn swap_names(&self, first: &str, second: &str) {
+            for (device, _) in &mut self.state.lock().unwrap().devices {
+                if device.device_name == first {
+                    device.device_name = second.to_string();
+                } else if device.device_name == second {
+                    device.device_name = first.to_string();
+                }
+            }
+        }
+
+        pub fn is_active(&self, device_name: &str) -> bool {
+            self.flags(device_name) & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP != 0
+        }
+
+        pub fn is_primary(&self, device_name: &str) -> bool {
+            self.flags(device_name) & DISPLAY_DEVICE_PRIMARY_DEVICE != 0
+        }
+
+        /// The device's current mode, or the last one it had while detached.
+        pub fn mode(&self, device_name: &str) -> DisplayMode {
+            self.state.lock().unwrap().device(device_name).expect("no such device").1
+        }
+
+        fn flags(&self, device_name: &str) -> u32 {
+            self.state.lock().unwrap().device(device_name).expect("no such device").0.state_flags
+        }
+    }
+
+    impl MockState {
+        fn device(&self, device_name: &str) -> Option<&(DisplayDevice, DisplayMode)> {
+            self.devices.iter().find(|(device, _)| device.device_name == device_name)
+        }
+
+        fn device_mut(&mut self, device_name: &str) -> Option<&mut (DisplayDevice, DisplayMode)> {
+            self.devices.iter_mut().find(|(device, _)| device.device_name == device_name)
+        }
+
+        fn apply(&mut self, device_name: &str, dev_mode: &DEVMODEW, flags: CDS_TYPE) {
+            let set_primary = flags.0 & CDS_SET_PRIMARY.0 != 0;
+            if set_primary {
+                for (device, _) in &mut self.devices {
+                    device.state_flags &= !DISPLAY_DEVICE_PRIMARY_DEVICE;
+                }
+            }
+            if let Some(idx) = self.ignoring.iter().position(|name| name == device_name) {
+                self.ignoring.remove(idx);
+                return;
+            }
+            let Some((device, mode)) = self.device_mut(device_name) else {
+                return;
+            };
+            let fields = dev_mode.dmFields.0;
+            if fields & DM_PELSWIDTH.0 != 0 && dev_mode.dmPelsWidth == 0 {
+                device.state_flags &= !(DISPLAY_DEVICE_ATTACHED_TO_DESKTOP | DISPLAY_DEVICE_PRIMARY_DEVICE);
+                return;
+            }
+            device.state_flags |= DISPLAY_DEVICE_ATTACHED_TO_DESKTOP;
+            if set_primary {
+                device.state_flags |= DISPLAY_DEVICE_PRIMARY_DEVICE;
+            }
+            let new = DisplayMode::from_devmode(dev_mode);
+            if fields & DM_PELSWIDTH.0 != 0 {
+                mode.width = new.width;
+                mode.height = new.height;
+            }
+            if fields & DM_POSITION.0 != 0 {
+                mode.position_x = new.position_x;
+                mode.position_y = new.position_y;
+            }
+            if fields & DM_DISPLAYFREQUENCY.0 != 0 {
+                mode.frequency = new.frequency;
+            }
+            if fields & DM_DISPLAYORIENTATION.0 != 0 {
+                mode.orientation = new.orientation;
+            }
+        }
+    }
+
+    fn detached() -> DEVMODEW {
+        let mut dev_mode: DEVMODEW = unsafe { std::mem::zeroed() };
+        dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_PELSWIDTH;
+        dev_mode
+    }
+
+    impl DisplayApi for MockDisplayApi {
+        fn enumerate_devices(&self) -> Vec<DisplayDevice> {
+            self.state.lock().unwrap().devices.iter().map(|(device, _)| device.clone()).collect()
+        }
+
+        fn device_extras(&self, device_names: &[String]) -> Vec<DeviceExtras> {
+            let state = self.state.lock().unwrap();
+            device_names.iter().map(|name| state.extras.get(name).cloned().unwrap_or_default()).collect()
+        }
+
+        fn get_settings(&self, device_name: &str, mode: ENUM_DISPLAY_SETTINGS_MODE) -> Option<DEVMODEW> {
+            let state = self.state.lock().unwrap();
+            let (device, current) = state.device(device_name)?;
+            let attached = device.state_flags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP != 0;
+            match mode {
+                ENUM_CURRENT_SETTINGS => attached.then(|| current.to_devmode()),
+                ENUM_REGISTRY_SETTINGS => Some(if attached { current.to_devmode() } else { detached() }),
+                ENUM_DISPLAY_SETTINGS_MODE(0) => Some(current.to_devmode()),
+                _ => None,
+            }
+        }
+
+        fn apply_settings(
+            &self,
+            device_name: Option<&str>,
+            dev_mode: Option<&DEVMODEW>,
+            flags: CDS_TYPE,
+        ) -> Result<(), DisplayChangeError> {
+            let mut state = self.state.lock().unwrap();
+            let (Some(device_name), Some(dev_mode)) = (device_name, dev_mode) else {
+                for (device_name, dev_mode, flags) in std::mem::take(&mut state.staged) {
+                    state.apply(&device_name, &dev_mode, flags);
+                }
+                return Ok(());
+            };
+            if state.failing.iter().any(|name| name.eq_ignore_ascii_case(device_name)) {
+                return Err(DisplayChangeError::Failed);
+            }
+            if state.device(device_name).is_none() {
+                return Err(DisplayChangeError::BadParam);
+            }
+            if flags.0 & CDS_NORESET.0 != 0 {
+                // Staging again, as a rollback does, replaces what was staged.
+                state.staged.retain(|(name, _, _)| name != device_name);
+                state.staged.push((device_name.to_string(), *dev_mode, flags));
+            } else {
+                state.apply(device_name, dev_mode, flags);
+            }
+            Ok(())
+        }
+
+        fn query_topology(&self) -> windows::core::Result<Topology> {
+            let mut state = self.state.lock().unwrap();
+            state.topology = Some(state.devices.clone());
+            Ok(Topology::default())
+        }
+
+        fn apply_topology(&self, _topology: &Topology, _persist: bool) -> windows::core::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            let Some(topology) = state.topology.clone() else {
+                return Err(E_NOTIMPL.into());
+            };
+            for (saved, mode) in topology {
+                let mut dev_mode = mode.to_devmode();
+                if saved.state_flags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP == 0 {
+                    dev_mode = detached();
+                }
+                let primary = saved.state_flags & DISPLAY_DEVICE_PRIMARY_DEVICE != 0;
+                state.apply(&saved.device_name, &dev_mode, if primary { CDS_SET_PRIMARY } else { CDS_TYPE(0) });
+            }
+            Ok(())
+        }
+
+        fn detach_paths(&self, device_names: &[String], _persist: bool) -> windows::core::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            for device_name in device_names {
+                state.apply(device_name, &detached(), CDS_TYPE(0));
+            }
+            Ok(())
+        }
+
+        fn set_advanced_color(&self, device_name: &str, enabled: bool) -> windows::core::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            match state.extras.get_mut(device_name) {
+                Some(extras) if extras.hdr_supported => {
+                    extras.hdr_enabled = enabled;
+                    Ok(())
+                }
+                _ => Err(E_NOTIMPL.into()),
+            }
+        }
+
+        fn set_dpi_scale(&self, device_name: &str, percent: u32) -> windows::core::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            if let Some(extras) = state.extras.get_mut(device_name) {
+                extras.scale_percent = Some(percent);
+            }
+            Ok(())
+        }
+
+        fn capture_gamma(&self, _device_name: &str) -> Option<GammaRamp> {
+            None
+        }
+
+        fn apply_gamma(&self, _device_name: &str, _ramp: &GammaRamp) {}
+
+        fn associate_color_profile(&self, _device_name: &str, _profile: &str) {}
+
+        fn power_off(&self, _device_name: &str) -> Result<(), DdcError> {
+            Err(DdcError::NoPhysicalMonitors)
+        }
+
+        fn power_on(&self, _device_name: &str) -> Result<(), DdcError> {
+            Ok(())
+        }
+
+        fn show_overlays(&self, rects: Vec<RECT>) -> (Box<dyn Send>, Vec<windows::core::Result<()>>) {
+            (Box::new(()), rects.iter().map(|_| Ok(())).collect())
+        }
+
+        fn capture_windows(&self, _monitors: &[MonitorInfo]) -> Vec<SavedWindow> {
+            Vec::new()
+        }
+
+        fn restore_windows(&self, _windows: &[SavedWindow], _monitors: &[MonitorInfo]) {}
+    }
+}
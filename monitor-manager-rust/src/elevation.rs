@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use windows::core::{w, HSTRING};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND, WAIT_OBJECT_0};
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, OpenProcess, OpenProcessToken, WaitForSingleObject, PROCESS_SYNCHRONIZE,
+};
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+use monitor_manager::{config, MonitorManager};
+
+/// `--handoff=<pid>`: the instance that relaunched this one, which has to
+/// exit before this one can take the single-instance mutex and the tray.
+const HANDOFF_FLAG: &str = "--handoff=";
+/// `--snapshot=<path>`: the relaunching instance's saved monitor settings,
+/// for when the elevated instance runs as another user with its own
+/// `%APPDATA%`.
+const SNAPSHOT_FLAG: &str = "--snapshot=";
+/// How long to wait for the relaunching instance to restore and exit.
+const HANDOFF_TIMEOUT_MS: u32 = 30_000;
+
+/// Whether this process runs with administrator rights.
+pub fn is_elevated() -> bool {
+    let mut token = HANDLE::default();
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) }.is_err() {
+        return false;
+    }
+    let mut elevation = TOKEN_ELEVATION::default();
+    let mut size = 0u32;
+    let result = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        )
+    };
+    unsafe {
+        let _ = CloseHandle(token);
+    }
+    result.is_ok() && elevation.TokenIsElevated != 0
+}
+
+/// Starts this exe again through the UAC prompt, with the same arguments
+/// and what the new instance needs to take over. The caller exits once this
+/// succeeds; declining the prompt is an error.
+pub fn relaunch_elevated() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with(HANDOFF_FLAG) && !arg.starts_with(SNAPSHOT_FLAG))
+        .collect();
+    args.push(format!("{}{}", HANDOFF_FLAG, std::process::id()));
+    args.push(format!("{}{}", SNAPSHOT_FLAG, MonitorManager::saved_settings_path(&config::config_dir()).display()));
+    let parameters: Vec<String> = args
+        .iter()
+        .map(|arg| if arg.contains(' ') { format!("\"{}\"", arg) } else { arg.clone() })
+        .collect();
+
+    let result = unsafe {
+        ShellExecuteW(
+            HWND::default(),
+            w!("runas"),
+            &HSTRING::from(exe.as_os_str()),
+            &HSTRING::from(parameters.join(" ")),
+            None,
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes; declining the prompt is one of them.
+    if result.0 as usize <= 32 {
+        return Err(format!("the elevated instance didn't start (error {})", result.0 as usize));
+    }
+    log::info!("Started an elevated instance; handing over to it");
+    Ok(())
+}
+
+/// When started by `relaunch_elevated`, waits for the instance that started
+/// this one to exit and copies its snapshot over if this one has none.
+pub fn take_handoff(args: &[String]) {
+    let Some(pid) = args.iter().find_map(|arg| arg.strip_prefix(HANDOFF_FLAG)?.parse::<u32>().ok()) else {
+        return;
+    };
+    log::info!("Taking over from the unelevated instance (PID {})", pid);
+    if let Ok(process) = unsafe { OpenProcess(PROCESS_SYNCHRONIZE, false, pid) } {
+        let waited = unsafe { WaitForSingleObject(process, HANDOFF_TIMEOUT_MS) };
+        if waited != WAIT_OBJECT_0 {
+            log::warn!("The unelevated instance (PID {}) is still running", pid);
+        }
+        unsafe {
+            let _ = CloseHandle(process);
+        }
+    }
+
+    let Some(snapshot) = args.iter().find_map(|arg| arg.strip_prefix(SNAPSHOT_FLAG)) else {
+        return;
+    };
+    let own = MonitorManager::saved_settings_path(&config::config_dir());
+    let (snapshot, own) = (Path::new(snapshot), own.as_path());
+    if snapshot == own || own.exists() || !snapshot.exists() {
+        return;
+    }
+    let copied = own.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::copy(snapshot, own));
+    match copied {
+        Ok(_) => log::info!("Took over the monitor snapshot from {}", snapshot.display()),
+        Err(e) => log::warn!("Could not copy the monitor snapshot from {}: {}", snapshot.display(), e),
+    }
+}
@@ -0,0 +1,28 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::monitor::DisplayChangeError;
+
+/// Why one of the crate's operations failed.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{}: {source}", path.display())]
+    ConfigIo { path: PathBuf, source: io::Error },
+    #[error("parse error: {0}")]
+    ConfigParse(String),
+    #[error("could not serialize config: {0}")]
+    ConfigSerialize(String),
+    /// The config file is from a newer version, whose settings saving would lose.
+    #[error("the config file is from a newer version of Monitor Manager (config version {0}) and isn't saved over")]
+    ConfigNewer(u32),
+    #[error("{0}")]
+    DisplayChange(#[from] DisplayChangeError),
+    /// The tray icon, its menu or the GUI toolkit couldn't be set up.
+    #[error("could not start the tray: {0}")]
+    TrayInit(String),
+    /// Process starts and exits couldn't be watched.
+    #[error("could not watch processes: {}", .0.message())]
+    ProcessScan(#[source] windows::core::Error),
+}
@@ -0,0 +1,104 @@
+use std::ffi::c_void;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_SUCCESS};
+use windows::Win32::Security::PSID;
+use windows::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+    REPORT_EVENT_TYPE,
+};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegOpenKeyExW, RegSetKeyValueW, HKEY, HKEY_LOCAL_MACHINE, KEY_READ, REG_DWORD, REG_EXPAND_SZ,
+};
+
+const SOURCE: &str = "MonitorManager";
+const SOURCE_KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog\Application\MonitorManager";
+/// Ships with .NET Framework 4 and maps every event ID to just "%1", so the
+/// message is shown as written.
+const MESSAGE_FILE: &str = r"%SystemRoot%\Microsoft.NET\Framework64\v4.0.30319\EventLogMessages.dll";
+
+/// Monitors were changed for a target.
+pub const SESSION_STARTED: u32 = 1;
+/// Monitors were put back.
+pub const SESSION_ENDED: u32 = 2;
+/// A display change failed on one monitor.
+pub const CHANGE_FAILED: u32 = 100;
+
+/// Registers the event source so Event Viewer can show the messages. Needs
+/// admin rights once; without them events are still written, but Event
+/// Viewer adds a note that the description is missing.
+pub fn register() {
+    let mut key = HKEY::default();
+    let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, &HSTRING::from(SOURCE_KEY), 0, KEY_READ, &mut key) };
+    if status == ERROR_SUCCESS {
+        unsafe {
+            let _ = RegCloseKey(key);
+        }
+        return;
+    }
+    let message_file: Vec<u16> = MESSAGE_FILE.encode_utf16().chain(std::iter::once(0)).collect();
+    let types_supported = (EVENTLOG_ERROR_TYPE.0 | EVENTLOG_INFORMATION_TYPE.0) as u32;
+    let status = unsafe {
+        let status = RegSetKeyValueW(
+            HKEY_LOCAL_MACHINE,
+            &HSTRING::from(SOURCE_KEY),
+            &HSTRING::from("EventMessageFile"),
+            REG_EXPAND_SZ.0,
+            Some(message_file.as_ptr() as *const c_void),
+            (message_file.len() * 2) as u32,
+        );
+        if status == ERROR_SUCCESS {
+            RegSetKeyValueW(
+                HKEY_LOCAL_MACHINE,
+                &HSTRING::from(SOURCE_KEY),
+                &HSTRING::from("TypesSupported"),
+                REG_DWORD.0,
+                Some(&types_supported as *const u32 as *const c_void),
+                std::mem::size_of::<u32>() as u32,
+            )
+        } else {
+            status
+        }
+    };
+    match status {
+        ERROR_SUCCESS => log::info!("Registered the {} event source", SOURCE),
+        ERROR_ACCESS_DENIED => log::info!("Registering the {} event source needs admin rights once", SOURCE),
+        status => log::warn!("Could not register the {} event source: {}", SOURCE, status.to_hresult().message()),
+    }
+}
+
+/// Writes an information event.
+pub fn info(event_id: u32, message: &str) {
+    report(EVENTLOG_INFORMATION_TYPE, event_id, message);
+}
+
+/// Writes an error event.
+pub fn error(event_id: u32, message: &str) {
+    report(EVENTLOG_ERROR_TYPE, event_id, message);
+}
+
+fn report(kind: REPORT_EVENT_TYPE, event_id: u32, message: &str) {
+    let message = HSTRING::from(message);
+    unsafe {
+        let source = match RegisterEventSourceW(PCWSTR::null(), &HSTRING::from(SOURCE)) {
+            Ok(source) => source,
+            Err(e) => {
+                log::warn!("Could not open the event log: {}", e.message());
+                return;
+            }
+        };
+        if let Err(e) = ReportEventW(
+            source,
+            kind,
+            0,
+            event_id,
+            PSID::default(),
+            0,
+            Some(&[PCWSTR(message.as_ptr())]),
+            None,
+        ) {
+            log::warn!("Could not write to the event log: {}", e.message());
+        }
+        let _ = DeregisterEventSource(source);
+    }
+}
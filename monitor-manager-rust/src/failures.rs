@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use monitor_manager::monitor::DeviceResult;
+
+/// The first repeat notification for a device waits this long; each one
+/// after that waits twice as long as the last.
+const NOTICE_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_NOTICE_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Which kind of change a failure came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Applying the profile's strategy to a monitor.
+    Apply,
+    /// Bringing a monitor back from the snapshot.
+    Restore,
+}
+
+/// Display changes that failed for a reason a retry might fix, and when each
+/// device was last notified about.
+#[derive(Debug, Clone, Default)]
+pub struct Failures {
+    pending: Vec<(ChangeKind, String)>,
+    /// Device name to (last notification, notifications since it last worked).
+    notified: HashMap<String, (Instant, u32)>,
+}
+
+impl Failures {
+    /// Updates the retry list from one round of changes: devices that worked
+    /// drop out, devices that failed in a retryable way go in. Failures of the
+    /// other kind are dropped, since this change supersedes them.
+    pub fn record(&mut self, kind: ChangeKind, results: &[DeviceResult]) {
+        self.pending.retain(|(k, _)| *k == kind);
+        for (device, result) in results {
+            self.pending.retain(|(_, d)| d != device);
+            match result {
+                Ok(()) => {
+                    self.notified.remove(device);
+                }
+                Err(e) if e.is_retryable() => self.pending.push((kind, device.clone())),
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// The failures in `results` worth a notification, as
+    /// `"\\.\DISPLAY2: reason; ..."`. A device that keeps failing is only
+    /// mentioned again after a growing wait.
+    pub fn notice(&mut self, results: &[DeviceResult], now: Instant) -> Option<String> {
+        let mut lines = Vec::new();
+        for (device, result) in results {
+            let Err(e) = result else {
+                continue;
+            };
+            let due = match self.notified.get(device) {
+                Some(&(last, count)) => {
+                    let backoff = NOTICE_BACKOFF.saturating_mul(1 << count.saturating_sub(1).min(16));
+                    now.duration_since(last) >= backoff.min(MAX_NOTICE_BACKOFF)
+                }
+                None => true,
+            };
+            if due {
+                let count = self.notified.get(device).map_or(0, |&(_, count)| count);
+                self.notified.insert(device.clone(), (now, count + 1));
+                lines.push(format!("{}: {}", device, e));
+            }
+        }
+        (!lines.is_empty()).then(|| lines.join("; "))
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Empties the retry list, returning the devices to apply the strategy to
+    /// and the devices to restore.
+    pub fn take_pending(&mut self) -> (Vec<String>, Vec<String>) {
+        let (apply, restore): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(kind, _)| *kind == ChangeKind::Apply);
+        (
+            apply.into_iter().map(|(_, device)| device).collect(),
+            restore.into_iter().map(|(_, device)| device).collect(),
+        )
+    }
+}
@@ -0,0 +1,58 @@
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONULL};
+use windows::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_PRESENTATION_MODE, QUNS_RUNNING_D3D_FULL_SCREEN};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetClassNameW, GetForegroundWindow, GetShellWindow, GetWindowRect, GetWindowThreadProcessId, MONITORINFOF_PRIMARY,
+};
+
+/// Desktop windows cover the whole monitor too.
+const DESKTOP_CLASSES: &[&str] = &["Progman", "WorkerW"];
+
+/// Whether an app is in exclusive fullscreen or presentation mode. With
+/// `borderless`, a foreground window exactly covering the primary monitor
+/// counts as well, which is how borderless-windowed games look.
+pub fn is_active(borderless: bool) -> bool {
+    let state = unsafe { SHQueryUserNotificationState() };
+    if matches!(state, Ok(QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE)) {
+        return true;
+    }
+    borderless && foreground_covers_primary()
+}
+
+/// The process owning the foreground window, if [`is_active`].
+pub fn foreground_process(borderless: bool) -> Option<u32> {
+    if !is_active(borderless) {
+        return None;
+    }
+    let mut pid = 0u32;
+    unsafe {
+        GetWindowThreadProcessId(GetForegroundWindow(), Some(&mut pid));
+    }
+    (pid != 0).then_some(pid)
+}
+
+fn foreground_covers_primary() -> bool {
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_invalid() || window == GetShellWindow() {
+            return false;
+        }
+        let mut class = [0u16; 64];
+        let len = GetClassNameW(window, &mut class).max(0) as usize;
+        let class = String::from_utf16_lossy(&class[..len]);
+        if DESKTOP_CLASSES.contains(&class.as_str()) {
+            return false;
+        }
+
+        let monitor = MonitorFromWindow(window, MONITOR_DEFAULTTONULL);
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if monitor.is_invalid() || !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return false;
+        }
+        let mut rect = RECT::default();
+        GetWindowRect(window, &mut rect).is_ok() && info.dwFlags & MONITORINFOF_PRIMARY != 0 && rect == info.rcMonitor
+    }
+}
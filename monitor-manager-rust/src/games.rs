@@ -0,0 +1,290 @@
+use std::ffi::c_void;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use windows::core::{HSTRING, PWSTR};
+use windows::Win32::Foundation::ERROR_SUCCESS;
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ,
+    RRF_RT_REG_SZ,
+};
+
+const STEAM_KEY: &str = r"Software\Valve\Steam";
+const STEAM_MACHINE_KEY: &str = r"SOFTWARE\WOW6432Node\Valve\Steam";
+const UNINSTALL_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Uninstall";
+/// Riot's uninstall entries are named like "Riot Game valorant.live".
+const RIOT_PREFIX: &str = "Riot Game ";
+/// Exes in a game folder that are never the game itself.
+const NOT_GAMES: &[&str] = &["unins", "crash", "setup", "redist", "vc_", "dxsetup", "report", "helper", "install"];
+
+/// An installed game that could be added as a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundGame {
+    pub name: String,
+    pub exe: PathBuf,
+}
+
+/// A game whose exe still has to be worked out.
+struct Candidate {
+    name: String,
+    install_dir: PathBuf,
+    /// Known from the store's own data; otherwise the biggest exe is taken.
+    exe: Option<PathBuf>,
+}
+
+/// Looks for games installed through Steam, the Epic Games Launcher and the
+/// Riot Client, sorted by name. Library files that are missing or can't be
+/// read are skipped. `on_progress` gets (folders searched, folders to search)
+/// as each install folder is searched.
+pub fn scan(mut on_progress: impl FnMut(usize, usize)) -> Vec<FoundGame> {
+    let mut candidates = steam_games();
+    candidates.extend(epic_games());
+    candidates.extend(riot_games());
+
+    let total = candidates.len();
+    on_progress(0, total);
+    let mut games: Vec<FoundGame> = Vec::new();
+    for (done, candidate) in candidates.into_iter().enumerate() {
+        let exe = candidate
+            .exe
+            .filter(|exe| exe.is_file())
+            .or_else(|| largest_exe(&candidate.install_dir));
+        match exe {
+            Some(exe) if !games.iter().any(|game| game.exe == exe) => {
+                games.push(FoundGame { name: candidate.name, exe })
+            }
+            Some(_) => {}
+            None => log::debug!("No exe found for {} in {}", candidate.name, candidate.install_dir.display()),
+        }
+        on_progress(done + 1, total);
+    }
+    games.sort_by_cached_key(|game| game.name.to_lowercase());
+    games
+}
+
+fn steam_games() -> Vec<Candidate> {
+    let Some(steam_dir) = registry_string(HKEY_CURRENT_USER, STEAM_KEY, "SteamPath")
+        .or_else(|| registry_string(HKEY_LOCAL_MACHINE, STEAM_MACHINE_KEY, "InstallPath"))
+    else {
+        return Vec::new();
+    };
+    let steam_dir = PathBuf::from(steam_dir);
+    let mut libraries = vec![steam_dir.clone()];
+    match fs::read_to_string(steam_dir.join("steamapps").join("libraryfolders.vdf")) {
+        Ok(vdf) => {
+            for path in vdf_values(&vdf, "path") {
+                let path = PathBuf::from(path);
+                if !libraries.contains(&path) {
+                    libraries.push(path);
+                }
+            }
+        }
+        Err(e) => log::warn!("Could not read Steam's library list: {}", e),
+    }
+
+    let mut candidates = Vec::new();
+    for library in libraries {
+        let steamapps = library.join("steamapps");
+        let Ok(entries) = fs::read_dir(&steamapps) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let is_manifest = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("appmanifest_") && name.ends_with(".acf"));
+            if !is_manifest {
+                continue;
+            }
+            let Ok(acf) = fs::read_to_string(&path) else {
+                log::warn!("Could not read {}", path.display());
+                continue;
+            };
+            let name = vdf_values(&acf, "name").into_iter().next();
+            let install_dir = vdf_values(&acf, "installdir").into_iter().next();
+            let (Some(name), Some(install_dir)) = (name, install_dir) else {
+                continue;
+            };
+            candidates.push(Candidate {
+                name,
+                install_dir: steamapps.join("common").join(install_dir),
+                exe: None,
+            });
+        }
+    }
+    candidates
+}
+
+/// The launcher keeps one JSON `.item` file per installed game.
+fn epic_games() -> Vec<Candidate> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Manifest {
+        display_name: String,
+        install_location: PathBuf,
+        #[serde(default)]
+        launch_executable: String,
+    }
+
+    let Some(program_data) = std::env::var_os("ProgramData") else {
+        return Vec::new();
+    };
+    let manifests = Path::new(&program_data).join(r"Epic\EpicGamesLauncher\Data\Manifests");
+    let Ok(entries) = fs::read_dir(&manifests) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("item")))
+        .filter_map(|path| {
+            let manifest = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<Manifest>(&content).ok());
+            if manifest.is_none() {
+                log::warn!("Could not read {}", path.display());
+            }
+            manifest
+        })
+        .map(|manifest| Candidate {
+            exe: (!manifest.launch_executable.is_empty())
+                .then(|| manifest.install_location.join(&manifest.launch_executable)),
+            name: manifest.display_name,
+            install_dir: manifest.install_location,
+        })
+        .collect()
+}
+
+/// Riot registers each game for uninstalling; the launcher stub in the
+/// install folder isn't the process that runs, so known games get their
+/// real exe.
+fn riot_games() -> Vec<Candidate> {
+    registry_subkeys(HKEY_CURRENT_USER, UNINSTALL_KEY)
+        .into_iter()
+        .filter(|subkey| subkey.starts_with(RIOT_PREFIX))
+        .filter_map(|subkey| {
+            let key = format!(r"{}\{}", UNINSTALL_KEY, subkey);
+            let install_dir = PathBuf::from(registry_string(HKEY_CURRENT_USER, &key, "InstallLocation")?);
+            let name = registry_string(HKEY_CURRENT_USER, &key, "DisplayName")
+                .unwrap_or_else(|| subkey[RIOT_PREFIX.len()..].to_string());
+            let product = subkey.to_lowercase();
+            let exe = if product.contains("league_of_legends") {
+                Some(install_dir.join(r"Game\League of Legends.exe"))
+            } else if product.contains("valorant") {
+                Some(install_dir.join(r"live\ShooterGame\Binaries\Win64\VALORANT-Win64-Shipping.exe"))
+            } else {
+                None
+            };
+            Some(Candidate { name, install_dir, exe })
+        })
+        .collect()
+}
+
+/// The biggest exe in `dir` or its direct subfolders, leaving out
+/// installers and crash reporters.
+fn largest_exe(dir: &Path) -> Option<PathBuf> {
+    let mut exes = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(dir) {
+        dirs.extend(entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()));
+    }
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()).map(str::to_lowercase) else {
+                continue;
+            };
+            if !name.ends_with(".exe") || NOT_GAMES.iter().any(|skip| name.contains(skip)) {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                exes.push((metadata.len(), path));
+            }
+        }
+    }
+    exes.into_iter().max_by_key(|(size, _)| *size).map(|(_, path)| path)
+}
+
+/// Every value stored under `key` in Valve's KeyValues text format,
+/// wherever it's nested.
+fn vdf_values(text: &str, key: &str) -> Vec<String> {
+    vdf_tokens(text)
+        .windows(2)
+        .filter(|pair| pair[0].eq_ignore_ascii_case(key) && pair[1] != "{")
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+/// Quoted strings, unescaped, and braces.
+fn vdf_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => tokens.push(c.to_string()),
+            '"' => {
+                let mut token = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => token.extend(chars.next()),
+                        _ => token.push(c),
+                    }
+                }
+                tokens.push(token);
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn registry_string(root: HKEY, key: &str, name: &str) -> Option<String> {
+    let (key, name) = (HSTRING::from(key), HSTRING::from(name));
+    let mut size = 0u32;
+    unsafe { RegGetValueW(root, &key, &name, RRF_RT_REG_SZ, None, None, Some(&mut size)) }
+        .ok()
+        .ok()?;
+    let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+    unsafe {
+        RegGetValueW(
+            root,
+            &key,
+            &name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            Some(&mut size),
+        )
+    }
+    .ok()
+    .ok()?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len])).filter(|value| !value.is_empty())
+}
+
+fn registry_subkeys(root: HKEY, key: &str) -> Vec<String> {
+    let mut handle = HKEY::default();
+    if unsafe { RegOpenKeyExW(root, &HSTRING::from(key), 0, KEY_READ, &mut handle) } != ERROR_SUCCESS {
+        return Vec::new();
+    }
+    let mut subkeys = Vec::new();
+    let mut buffer = [0u16; 256];
+    for index in 0.. {
+        let mut len = buffer.len() as u32;
+        let status = unsafe {
+            RegEnumKeyExW(handle, index, PWSTR(buffer.as_mut_ptr()), &mut len, None, PWSTR::null(), None, None)
+        };
+        if status != ERROR_SUCCESS {
+            break;
+        }
+        subkeys.push(String::from_utf16_lossy(&buffer[..len as usize]));
+    }
+    unsafe {
+        let _ = RegCloseKey(handle);
+    }
+    subkeys
+}
@@ -0,0 +1,74 @@
+use std::ffi::c_void;
+
+use serde::{Deserialize, Serialize};
+use windows::core::{w, HSTRING, PCWSTR};
+use windows::Win32::Graphics::Gdi::{CreateDCW, DeleteDC, HDC};
+use windows::Win32::UI::ColorSystem::{GetDeviceGammaRamp, SetDeviceGammaRamp};
+
+/// Entries per channel in a GDI gamma ramp.
+const RAMP_SIZE: usize = 256;
+
+/// A display's gamma ramp, one 256-entry curve per channel, as calibration
+/// tools load it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+/// A device context for one display, deleted on drop.
+struct DeviceContext(HDC);
+
+impl DeviceContext {
+    fn open(device_name: &str) -> Option<Self> {
+        let hdc = unsafe { CreateDCW(w!("DISPLAY"), &HSTRING::from(device_name), PCWSTR::null(), None) };
+        (!hdc.is_invalid()).then_some(Self(hdc))
+    }
+}
+
+impl Drop for DeviceContext {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeleteDC(self.0);
+        }
+    }
+}
+
+impl GammaRamp {
+    /// The ramp the display is using now, or `None` if its driver doesn't
+    /// give gamma access.
+    pub fn capture(device_name: &str) -> Option<Self> {
+        let Some(dc) = DeviceContext::open(device_name) else {
+            log::info!("No device context for {}, not saving its gamma ramp", device_name);
+            return None;
+        };
+        let mut ramp = [0u16; 3 * RAMP_SIZE];
+        if !unsafe { GetDeviceGammaRamp(dc.0, ramp.as_mut_ptr() as *mut c_void) }.as_bool() {
+            log::info!("{} doesn't allow reading its gamma ramp, not saving it", device_name);
+            return None;
+        }
+        Some(Self {
+            red: ramp[..RAMP_SIZE].to_vec(),
+            green: ramp[RAMP_SIZE..2 * RAMP_SIZE].to_vec(),
+            blue: ramp[2 * RAMP_SIZE..].to_vec(),
+        })
+    }
+
+    /// Loads the ramp back onto the display. Failures are only logged: the
+    /// display works without it, just uncalibrated.
+    pub fn apply(&self, device_name: &str) {
+        if [&self.red, &self.green, &self.blue].iter().any(|channel| channel.len() != RAMP_SIZE) {
+            log::warn!("Ignoring a malformed saved gamma ramp for {}", device_name);
+            return;
+        }
+        let Some(dc) = DeviceContext::open(device_name) else {
+            log::warn!("No device context for {}, could not restore its gamma ramp", device_name);
+            return;
+        };
+        let ramp: Vec<u16> = [&self.red, &self.green, &self.blue].into_iter().flatten().copied().collect();
+        if !unsafe { SetDeviceGammaRamp(dc.0, ramp.as_ptr() as *const c_void) }.as_bool() {
+            log::warn!("{} refused its saved gamma ramp", device_name);
+        }
+    }
+}
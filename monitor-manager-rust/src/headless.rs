@@ -0,0 +1,49 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use windows::Win32::Foundation::{BOOL, TRUE};
+use windows::Win32::System::Console::{AttachConsole, SetConsoleCtrlHandler, ATTACH_PARENT_PROCESS};
+
+use crate::{lock, AppState};
+use monitor_manager::monitor;
+
+/// The state the console control handler acts on; it can't capture any.
+static STATE: OnceLock<Arc<Mutex<AppState>>> = OnceLock::new();
+
+/// Hooks Ctrl+C, closing the console, logoff and shutdown up to the restore
+/// and exit path. Without a console to attach to (started from Explorer or
+/// the Run key) only logoff and shutdown can stop the process cleanly.
+pub fn install_ctrl_handler(state: Arc<Mutex<AppState>>) {
+    let _ = STATE.set(state);
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+        if let Err(e) = SetConsoleCtrlHandler(Some(on_console_ctrl), true) {
+            log::warn!("Could not install the console control handler: {}", e.message());
+        }
+    }
+}
+
+/// Runs on its own thread. Windows ends the process as soon as it returns
+/// for anything but Ctrl+C or Ctrl+Break, so the restore happens here rather
+/// than being left to the monitor loop.
+unsafe extern "system" fn on_console_ctrl(ctrl_type: u32) -> BOOL {
+    log::info!("Console control event {}, restoring and exiting", ctrl_type);
+    if let Some(state) = STATE.get() {
+        let (shutdown, monitor_manager, restore_on_exit) = {
+            let state = lock(state);
+            (state.shutdown.clone(), state.monitor_manager.clone(), state.config.restore_on_exit)
+        };
+        shutdown.request();
+        let results = monitor_manager.run(move |manager| {
+            if restore_on_exit && manager.has_pending_restore() {
+                manager.restore_all_monitors()
+            } else {
+                Vec::new()
+            }
+        });
+        let results = monitor_manager.finish_restore(results);
+        if let Some(failures) = monitor::describe_failures(&results) {
+            log::warn!("Some monitors could not be restored on exit: {}", failures);
+        }
+    }
+    TRUE
+}
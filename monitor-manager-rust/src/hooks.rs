@@ -0,0 +1,138 @@
+use std::io::{BufRead, BufReader, Read};
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+/// How often a running command is checked for having exited.
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the placeholders in a command's arguments are replaced with.
+#[derive(Debug, Clone)]
+pub struct Placeholders {
+    /// `{target}`: the target that started or exited, as the profile gives
+    /// it: the program, or the window title pattern.
+    pub target: String,
+    /// `{monitors_disabled}`: how many monitors were changed, or brought back.
+    pub monitors: usize,
+    /// `{profile}`: the active profile's name.
+    pub profile: String,
+}
+
+impl Placeholders {
+    /// `arg` with the placeholders filled in. For `cmd` the text values are
+    /// quoted, so a name with spaces or `&` stays one word.
+    fn fill(&self, arg: &str, use_shell: bool) -> String {
+        let text = |value: &str| if use_shell { shell_quoted(value) } else { value.to_string() };
+        arg.replace("{target}", &text(&self.target))
+            .replace("{monitors_disabled}", &self.monitors.to_string())
+            .replace("{profile}", &text(&self.profile))
+    }
+}
+
+/// `value` in double quotes for `cmd`. A quote can't be escaped inside
+/// quotes there, so any in the value are dropped.
+fn shell_quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', ""))
+}
+
+/// Starts each command (the program, then its arguments) on a thread of its
+/// own and returns right away. With `use_shell` the words are joined and
+/// handed to `cmd /C`. Output goes to the log; a command still running after
+/// `timeout` is killed. `on_failure` hears about commands that couldn't start,
+/// failed or timed out.
+pub fn run<F>(commands: &[Vec<String>], use_shell: bool, timeout: Duration, placeholders: &Placeholders, on_failure: F)
+where
+    F: Fn(&[String], String) + Clone + Send + 'static,
+{
+    for command in commands.iter().filter(|command| !command.is_empty()) {
+        let command: Vec<String> = command.iter().map(|arg| placeholders.fill(arg, use_shell)).collect();
+        let on_failure = on_failure.clone();
+        thread::spawn(move || {
+            if let Err(reason) = run_one(&command, use_shell, timeout) {
+                log::warn!("Command {:?} {}", command, reason);
+                on_failure(&command, reason);
+            }
+        });
+    }
+}
+
+fn run_one(command: &[String], use_shell: bool, timeout: Duration) -> Result<(), String> {
+    let mut process = if use_shell {
+        let mut process = Command::new("cmd");
+        process.arg("/C").raw_arg(command.join(" "));
+        process
+    } else {
+        let mut process = Command::new(&command[0]);
+        process.args(&command[1..]);
+        process
+    };
+    let mut child = process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .creation_flags(CREATE_NO_WINDOW.0)
+        .spawn()
+        .map_err(|e| format!("could not start: {}", e))?;
+    log::info!("Started {:?} (pid {})", command, child.id());
+    let name = command[0].clone();
+    let readers = [
+        child.stdout.take().map(|out| log_lines(name.clone(), out)),
+        child.stderr.take().map(|err| log_lines(name.clone(), err)),
+    ];
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("was killed after running for {}s", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(WAIT_INTERVAL),
+            Err(e) => return Err(format!("could not be waited for: {}", e)),
+        }
+    };
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("failed ({})", status))
+    }
+}
+
+fn log_lines(name: String, output: impl Read + Send + 'static) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            log::info!("[{}] {}", name, line);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(target: &str) -> Placeholders {
+        Placeholders { target: target.to_string(), monitors: 2, profile: "Work & play".to_string() }
+    }
+
+    #[test]
+    fn arguments_are_filled_in_as_is_without_the_shell() {
+        let placeholders = placeholders(r"C:\Games\My Game.exe");
+        assert_eq!(placeholders.fill("--target={target}", false), r"--target=C:\Games\My Game.exe");
+        assert_eq!(placeholders.fill("{profile}: {monitors_disabled}", false), "Work & play: 2");
+    }
+
+    #[test]
+    fn shell_arguments_keep_each_value_one_word() {
+        let placeholders = placeholders(r#"Elden "Ring""#);
+        assert_eq!(placeholders.fill("{target}", true), r#""Elden Ring""#);
+        assert_eq!(placeholders.fill("{profile}:{monitors_disabled}", true), r#""Work & play":2"#);
+    }
+}
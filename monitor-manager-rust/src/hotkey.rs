@@ -0,0 +1,85 @@
+use std::fmt;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+    VK_F1, VK_SPACE,
+};
+
+/// The id passed to `RegisterHotKey`; WM_HOTKEY carries it in `wParam`.
+pub const HOTKEY_ID: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyError {
+    /// Only modifiers, or nothing at all.
+    MissingKey,
+    /// Without a modifier the key would stop working everywhere else.
+    MissingModifier,
+    UnknownKey(String),
+    /// Usually another app already owns the combination.
+    Register(String),
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingKey => write!(f, "no key given"),
+            Self::MissingModifier => write!(f, "add ctrl, alt, shift or win"),
+            Self::UnknownKey(key) => write!(f, "unknown key \"{}\"", key),
+            Self::Register(message) => write!(f, "could not register: {}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    modifiers: HOT_KEY_MODIFIERS,
+    vk: u32,
+}
+
+impl Hotkey {
+    /// Parses combinations like "ctrl+alt+m" or "Win+Shift+F9".
+    pub fn parse(text: &str) -> Result<Self, HotkeyError> {
+        let mut modifiers = HOT_KEY_MODIFIERS(0);
+        let mut vk = None;
+        for part in text.split('+').map(|p| p.trim().to_ascii_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => modifiers |= MOD_CONTROL,
+                "alt" => modifiers |= MOD_ALT,
+                "shift" => modifiers |= MOD_SHIFT,
+                "win" | "windows" => modifiers |= MOD_WIN,
+                "" => return Err(HotkeyError::MissingKey),
+                key => vk = Some(virtual_key(key).ok_or_else(|| HotkeyError::UnknownKey(key.to_string()))?),
+            }
+        }
+        let vk = vk.ok_or(HotkeyError::MissingKey)?;
+        if modifiers.0 == 0 {
+            return Err(HotkeyError::MissingModifier);
+        }
+        Ok(Self { modifiers, vk })
+    }
+
+    /// Registers on the calling thread, so WM_HOTKEY arrives in its message queue.
+    pub fn register(&self) -> Result<(), HotkeyError> {
+        unsafe { RegisterHotKey(HWND(std::ptr::null_mut()), HOTKEY_ID, self.modifiers | MOD_NOREPEAT, self.vk) }
+            .map_err(|e| HotkeyError::Register(e.message()))
+    }
+}
+
+pub fn unregister() {
+    unsafe {
+        let _ = UnregisterHotKey(HWND(std::ptr::null_mut()), HOTKEY_ID);
+    }
+}
+
+fn virtual_key(key: &str) -> Option<u32> {
+    let bytes = key.as_bytes();
+    match key {
+        _ if bytes.len() == 1 && bytes[0].is_ascii_alphanumeric() => Some(bytes[0].to_ascii_uppercase() as u32),
+        "space" => Some(VK_SPACE.0 as u32),
+        _ => {
+            let n: u16 = key.strip_prefix('f')?.parse().ok()?;
+            (1..=24).contains(&n).then(|| (VK_F1.0 + n - 1) as u32)
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use windows::core::{HSTRING, PWSTR};
+use windows::Win32::Foundation::{CloseHandle, LocalFree, ERROR_PIPE_CONNECTED, HANDLE, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{
+    GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER,
+};
+use windows::Win32::Storage::FileSystem::{
+    FlushFileBuffers, ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+    PIPE_TYPE_BYTE, PIPE_WAIT,
+};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+use crate::{lock, AppState};
+use monitor_manager::monitor;
+
+const PIPE_NAME: &str = r"\\.\pipe\monitor-manager";
+/// Large enough for a response in one write.
+const REPLY_BUFFER_SIZE: u32 = 4096;
+const REQUEST_BUFFER_SIZE: u32 = 512;
+
+/// A command sent to the running instance, as `{"cmd":"<name>"}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Request {
+    /// Open the settings dialog; sent by a second launch.
+    ShowSettings,
+    Status,
+    /// The tray's "Disable Secondary Monitors Now".
+    Disable,
+    /// The tray's "Re-enable Monitors".
+    Restore,
+    Pause,
+    Resume,
+}
+
+/// The instance's state after handling a request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    /// The watcher state, e.g. "idle" or "pending restore".
+    pub state: String,
+    pub status: String,
+    pub paused: bool,
+    /// Whether monitors are currently changed.
+    pub monitoring: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn from_state(state: &AppState, error: Option<String>) -> Self {
+        Self {
+            ok: error.is_none(),
+            state: state.watcher.to_string(),
+            status: state.status.clone(),
+            paused: state.paused,
+            monitoring: state.monitoring,
+            error,
+        }
+    }
+}
+
+/// Sends `request` to the running instance and waits for its response.
+pub fn send(request: Request) -> io::Result<Response> {
+    let mut pipe = OpenOptions::new().read(true).write(true).open(PIPE_NAME)?;
+    pipe.write_all(&serde_json::to_vec(&request).map_err(io::Error::other)?)?;
+    // A single read: once the instance disconnects, further reads fail
+    // instead of reporting end of file.
+    let mut reply = vec![0u8; REPLY_BUFFER_SIZE as usize];
+    let read = pipe.read(&mut reply)?;
+    serde_json::from_slice(&reply[..read]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Starts answering requests from later launches and external tools. The
+/// server runs until [`stop`] is called after `shutdown` is set.
+pub fn serve(state: Arc<Mutex<AppState>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        // Only this user's processes may connect; everyone else would be able
+        // to switch monitors off.
+        let descriptor = match current_user_only() {
+            Ok(descriptor) => descriptor,
+            Err(e) => {
+                log::warn!("Not listening for commands, could not restrict the pipe: {}", e.message());
+                return;
+            }
+        };
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor.0,
+            bInheritHandle: false.into(),
+        };
+        let shutdown = lock(&state).shutdown.clone();
+
+        while !shutdown.is_requested() {
+            let pipe = unsafe {
+                CreateNamedPipeW(
+                    &HSTRING::from(PIPE_NAME),
+                    PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+                    PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                    1,
+                    REPLY_BUFFER_SIZE,
+                    REQUEST_BUFFER_SIZE,
+                    0,
+                    Some(&attributes),
+                )
+            };
+            if pipe.is_invalid() {
+                log::warn!("Could not create the command pipe: {}", windows::core::Error::from_win32().message());
+                break;
+            }
+
+            let connected = match unsafe { ConnectNamedPipe(pipe, None) } {
+                Ok(()) => true,
+                Err(e) => e.code() == ERROR_PIPE_CONNECTED.to_hresult(),
+            };
+            // `stop` connects just to wake this thread up.
+            if connected && !shutdown.is_requested() {
+                answer(pipe, &state);
+            }
+            unsafe {
+                let _ = DisconnectNamedPipe(pipe);
+                let _ = CloseHandle(pipe);
+            }
+        }
+
+        unsafe {
+            let _ = LocalFree(HLOCAL(descriptor.0));
+        }
+    })
+}
+
+/// Wakes the server out of waiting for a client and waits for it to exit.
+/// `shutdown` must already be set.
+pub fn stop(server: JoinHandle<()>) {
+    while !server.is_finished() {
+        let _ = OpenOptions::new().write(true).open(PIPE_NAME);
+        thread::sleep(Duration::from_millis(50));
+    }
+    if server.join().is_err() {
+        log::error!("The command pipe thread panicked");
+    }
+}
+
+fn answer(pipe: HANDLE, state: &Arc<Mutex<AppState>>) {
+    let mut buffer = [0u8; REQUEST_BUFFER_SIZE as usize];
+    let mut read = 0u32;
+    if unsafe { ReadFile(pipe, Some(&mut buffer), Some(&mut read), None) }.is_err() {
+        return;
+    }
+    let response = match serde_json::from_slice(&buffer[..read as usize]) {
+        Ok(request) => handle(request, state),
+        Err(e) => Response::from_state(&lock(state), Some(format!("bad request: {}", e))),
+    };
+    let Ok(reply) = serde_json::to_vec(&response) else {
+        return;
+    };
+    unsafe {
+        let _ = WriteFile(pipe, Some(&reply), None, None);
+        // Disconnecting drops whatever the client hasn't read yet.
+        let _ = FlushFileBuffers(pipe);
+    }
+}
+
+fn handle(request: Request, state: &Arc<Mutex<AppState>>) -> Response {
+    log::info!("Pipe command: {:?}", request);
+    let error = match request {
+        Request::ShowSettings => {
+            lock(state).show_settings.store(true, Ordering::Relaxed);
+            None
+        }
+        Request::Status => None,
+        Request::Disable if crate::is_remote_session() => {
+            Some("displays aren't changed over Remote Desktop".to_string())
+        }
+        Request::Disable => {
+            crate::disable_now(state);
+            let state = lock(state);
+            state.failed.then(|| state.status.clone())
+        }
+        Request::Restore => monitor::describe_failures(&crate::restore_now(state)),
+        Request::Pause => {
+            crate::set_paused(state, true);
+            None
+        }
+        Request::Resume => {
+            crate::set_paused(state, false);
+            None
+        }
+    };
+    Response::from_state(&lock(state), error)
+}
+
+/// A security descriptor whose DACL only lets the current user in. Free it
+/// with `LocalFree`.
+fn current_user_only() -> windows::core::Result<PSECURITY_DESCRIPTOR> {
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token)?;
+        let mut size = 0u32;
+        let _ = GetTokenInformation(token, TokenUser, None, 0, &mut size);
+        let mut buffer = vec![0u8; size as usize];
+        let result = GetTokenInformation(token, TokenUser, Some(buffer.as_mut_ptr().cast()), size, &mut size);
+        let _ = CloseHandle(token);
+        result?;
+
+        let user = &*buffer.as_ptr().cast::<TOKEN_USER>();
+        let mut sid = PWSTR::null();
+        ConvertSidToStringSidW(user.User.Sid, &mut sid)?;
+        let sddl = format!("D:P(A;;GA;;;{})", String::from_utf16_lossy(sid.as_wide()));
+        let _ = LocalFree(HLOCAL(sid.0.cast()));
+
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            &HSTRING::from(sddl),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )?;
+        Ok(descriptor)
+    }
+}
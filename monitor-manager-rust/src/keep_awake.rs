@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use windows::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED};
+
+use crate::{lock, AppState};
+
+/// How soon the thread notices monitoring starting or stopping.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set after a panic elsewhere, when `monitoring` can no longer be trusted.
+static GIVEN_UP: AtomicBool = AtomicBool::new(false);
+
+/// Holds off sleep and the display timeout for as long as it lives. The flags
+/// belong to the thread that set them, so the guard must stay on it.
+struct Awake;
+
+impl Awake {
+    fn start() -> Self {
+        unsafe {
+            SetThreadExecutionState(ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED);
+        }
+        Self
+    }
+}
+
+impl Drop for Awake {
+    fn drop(&mut self) {
+        unsafe {
+            SetThreadExecutionState(ES_CONTINUOUS);
+        }
+    }
+}
+
+/// Starts the thread that keeps the PC and its display awake while monitors
+/// are changed, if `keep_awake` is set. It lets go and returns once
+/// `shutdown` is set.
+pub fn spawn(state: Arc<Mutex<AppState>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let shutdown = lock(&state).shutdown.clone();
+        let mut awake: Option<Awake> = None;
+        while !shutdown.is_requested() && !GIVEN_UP.load(Ordering::Relaxed) {
+            let wanted = {
+                let state = lock(&state);
+                state.config.keep_awake && state.monitoring
+            };
+            if wanted != awake.is_some() {
+                log::info!("{} sleep and the display timeout", if wanted { "Holding off" } else { "Allowing" });
+                awake = wanted.then(Awake::start);
+            }
+            shutdown.wait(CHECK_INTERVAL);
+        }
+    })
+}
+
+/// Stops the thread for good, letting the PC sleep again. For the panic hook,
+/// since the thread that panicked may have left `monitoring` set.
+pub fn give_up() {
+    GIVEN_UP.store(true, Ordering::Relaxed);
+}
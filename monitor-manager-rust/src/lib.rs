@@ -0,0 +1,100 @@
+//! Disables, dims or blanks secondary monitors while chosen programs run and
+//! restores them afterwards.
+//!
+//! [`MonitorManager`] changes and restores the displays, [`Config`] holds the
+//! settings the tray app edits, and [`ProcessWatcher`] reports when a target
+//! program starts or exits.
+
+/// Listing and switching the default audio output.
+pub mod audio;
+/// Suspending or closing background programs while a target runs.
+pub mod background;
+mod ccd;
+/// Reading and reassociating monitors' ICC color profiles.
+pub mod color_profile;
+/// Settings file: targets, strategy and everything the settings dialog edits.
+pub mod config;
+/// Monitor power control over DDC/CI.
+pub mod ddc;
+/// The Win32 display calls behind `MonitorManager`.
+pub mod display_api;
+/// The error type shared by the crate's fallible operations.
+pub mod error;
+/// Detecting fullscreen apps, for the fullscreen trigger.
+pub mod fullscreen;
+/// Saving and reloading display gamma ramps.
+pub mod gamma;
+/// Finding installed games to suggest as targets.
+pub mod games;
+/// Size-capped log file in the config directory.
+pub mod logging;
+/// Running the monitor manager on a thread of its own.
+pub mod manager_handle;
+/// Matching running processes against `targets` entries.
+pub mod matcher;
+/// Enumerating, changing and restoring displays.
+pub mod monitor;
+mod overlay;
+/// Listing and switching Windows power plans.
+pub mod power_plan;
+/// WMI notifications for processes starting and exiting.
+pub mod process_events;
+/// Telling the background threads to stop.
+pub mod shutdown;
+/// Recording how long targets ran, for the tray's statistics.
+pub mod stats;
+/// Polling for running targets.
+pub mod watcher;
+/// POSTing state changes to webhook URLs.
+pub mod webhook;
+mod window_layout;
+
+pub use config::Config;
+pub use error::AppError;
+pub use manager_handle::ManagerHandle;
+pub use monitor::{MonitorInfo, MonitorManager};
+pub use process_events::{ProcessEvents, Tick, TickWait};
+pub use shutdown::Shutdown;
+pub use watcher::{Debounce, ProcessWatcher, RunningTargets, WatchAction, WatchEvent, WatcherState};
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Strategy;
+    use crate::display_api::mock::MockDisplayApi;
+    use crate::monitor::SavedSettings;
+    use crate::{Config, MonitorManager};
+
+    #[test]
+    fn lists_the_monitors() {
+        let api = MockDisplayApi::default();
+        api.add_monitor(r"\\.\DISPLAY1", true, (0, 0));
+        api.add_monitor(r"\\.\DISPLAY2", false, (1920, 0));
+        api.detach(r"\\.\DISPLAY2");
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MonitorManager::with_api(dir.path().to_path_buf(), SavedSettings::new(), Box::new(api));
+
+        let monitors = manager.get_all_monitors();
+        assert_eq!(monitors.len(), 2);
+        let (primary, detached) = (&monitors[0], &monitors[1]);
+        assert_eq!(primary.device_name, r"\\.\DISPLAY1");
+        assert!(primary.is_primary && primary.is_active);
+        assert_eq!((primary.width, primary.height, primary.refresh_hz), (Some(1920), Some(1080), Some(60)));
+        assert_eq!((primary.position_x, primary.position_y), (Some(0), Some(0)));
+        assert!(!detached.is_primary && !detached.is_active);
+        assert_eq!((detached.width, detached.position_x), (None, None));
+        assert!(manager.secondary_device_names(None).is_empty());
+    }
+
+    #[test]
+    fn config_survives_a_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default_at(Config::path_in(dir.path()));
+        config.poll_interval_ms = 750;
+        config.profiles[0].targets = vec![r"C:\Games\Hades\Hades.exe".to_string()];
+        config.profiles[0].strategy = Strategy::Blank;
+        config.save().unwrap();
+
+        let loaded = Config::read_from(&config.path()).unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::config;
+
+const LOG_FILE: &str = "monitor-manager.log";
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(
+                file,
+                "{} [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Directory the log file is written to.
+pub fn log_dir() -> PathBuf {
+    config::config_dir().join("logs")
+}
+
+/// Full path of the log file.
+pub fn log_file_path() -> PathBuf {
+    log_dir().join(LOG_FILE)
+}
+
+/// Routes the `log` macros to a file under the app data directory. The
+/// previous log is kept as `.old` once it grows past 1 MB. Logging is
+/// best-effort: if the file can't be opened the app runs without it.
+pub fn init() {
+    let path = log_file_path();
+    let _ = fs::create_dir_all(log_dir());
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+        let _ = fs::rename(&path, path.with_extension("log.old"));
+    }
+
+    let Ok(file) = OpenOptions::new().create(true).append(true).open(&path) else {
+        return;
+    };
+    if log::set_boxed_logger(Box::new(FileLogger { file: Mutex::new(file) })).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+}
@@ -1,174 +1,1404 @@
 #![windows_subsystem = "windows"]
 
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::mem;
+use std::panic;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
-use std::path::{Path, PathBuf};
-use sysinfo::System;
-use serde::{Deserialize, Serialize};
-use std::fs;
+use std::time::{Duration, Instant};
 
-mod monitor;
+mod about_dialog;
+mod autostart;
+mod cli;
+mod cursor_clip;
+mod elevation;
+mod event_log;
+mod failures;
+mod headless;
+mod hooks;
+mod hotkey;
+mod ipc;
+mod keep_awake;
+mod notify;
+mod process_picker;
+mod service;
+mod single_instance;
+mod system_events;
 mod tray_app;
 
-use monitor::MonitorManager;
+use monitor_manager::config::{self, ChildTrigger, Config, MonitorSelection, ProcessDetection, Strategy, Trigger};
+use monitor_manager::matcher::{PatternError, TargetPattern, TitlePattern};
+use monitor_manager::monitor::{self, DeviceResult, MonitorInfo, MonitorManager};
+use monitor_manager::background::{self, SetAside};
+use monitor_manager::stats;
+use monitor_manager::watcher::{self, ChildPattern};
+use cursor_clip::CursorClip;
+use failures::{ChangeKind, Failures};
+use monitor_manager::{
+    audio, fullscreen, logging, power_plan, webhook, AppError, Debounce, ManagerHandle, ProcessEvents, ProcessWatcher,
+    RunningTargets, Shutdown, Tick, TickWait, WatchAction, WatchEvent, WatcherState,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_REMOTESESSION};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub target_exe: String,
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Config,
+    /// Bumped whenever `config` is replaced or edited, so the threads that
+    /// act on it know to reconfigure.
+    pub config_generation: u64,
+    /// Why the config file couldn't be loaded at startup, until the tray
+    /// has shown it.
+    pub config_problem: Option<String>,
+    /// Whether monitors are currently changed, by the loop or from the tray.
+    pub monitoring: bool,
+    /// Where the monitor loop is; only the loop moves it.
+    pub watcher: WatcherState,
+    /// Whether the last disable or restore left some monitor unchanged.
+    pub failed: bool,
+    /// Set from the tray; the loop leaves the displays alone while it's on.
+    pub paused: bool,
+    /// Set when the displays may have changed behind the loop's back (waking
+    /// from sleep, a session switch), so it re-checks them.
+    pub revalidate: bool,
+    pub status: String,
+    /// Changes that failed and can be retried from the tray.
+    pub failures: Failures,
+    /// Whether this process has administrator rights, which it needs to read
+    /// the exe path of a target running as administrator.
+    pub elevated: bool,
+    /// Set once the tray is up. Headless and service runs only log what the
+    /// loop would otherwise notify about.
+    pub has_tray: bool,
+    /// Where the cursor is kept while a target runs.
+    pub cursor: CursorClip,
+    /// The default playback device before a profile switched it.
+    pub previous_audio: Option<String>,
+    /// The active power plan before a profile switched it.
+    pub previous_power_scheme: Option<String>,
+    /// Background programs suspended or closed for the current target.
+    pub set_aside: Vec<SetAside>,
+    /// The target monitors were last changed for, as the status line names it.
+    pub last_target: String,
+    /// The same target as the profile gives it, for `{target}` in `on_restore`.
+    pub last_target_value: String,
+    /// Names of the monitors the strategy changed, for the tooltip.
+    pub changed_monitors: Vec<String>,
+    /// Where the loop reports targets coming and going; set by the tray,
+    /// which turns them into notifications.
+    pub status_events: Option<mpsc::Sender<StatusEvent>>,
+    /// `on_disable` and `on_restore` commands already reported as failing.
+    pub failed_commands: Vec<Vec<String>>,
+    /// The manager runs on its own thread; calls to it go through
+    /// `with_manager`, never while `AppState` is locked.
+    pub monitor_manager: ManagerHandle,
+    pub shutdown: Arc<Shutdown>,
+    /// Set when a second launch asks this instance to open its settings.
+    pub show_settings: Arc<AtomicBool>,
+}
+
+/// A change the loop made that the user should hear about.
+#[derive(Debug, Clone)]
+pub enum StatusEvent {
+    /// `target` started and `monitors` are about to be changed.
+    Detected { target: String, monitors: usize, strategy: Strategy },
+    /// The monitors changed for `target` are back.
+    Restored { target: String, monitors: usize },
 }
 
-impl Default for Config {
-    fn default() -> Self {
+impl AppState {
+    pub fn new(monitor_manager: MonitorManager) -> Self {
+        let (config, config_problem) = Config::load();
         Self {
-            target_exe: r"C:\Riot Games\League of Legends\Game\League of Legends.exe".to_string(),
+            config,
+            config_generation: 0,
+            config_problem,
+            monitoring: false,
+            watcher: WatcherState::Idle,
+            failed: false,
+            paused: false,
+            revalidate: false,
+            status: "Idle - waiting for process".to_string(),
+            failures: Failures::default(),
+            elevated: elevation::is_elevated(),
+            has_tray: false,
+            cursor: CursorClip::default(),
+            previous_audio: None,
+            previous_power_scheme: None,
+            set_aside: Vec::new(),
+            last_target: String::new(),
+            last_target_value: String::new(),
+            changed_monitors: Vec::new(),
+            status_events: None,
+            failed_commands: Vec::new(),
+            monitor_manager: ManagerHandle::spawn(monitor_manager),
+            shutdown: Arc::new(Shutdown::default()),
+            show_settings: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Whether this session is shown over Remote Desktop, where the displays are
+/// virtual and disabling them blacks out the session.
+pub fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// Locks `mutex`, carrying on with its data if a thread panicked while
+/// holding it. The panic hook has already put the displays back by then, and
+/// the tray should stay usable rather than panic in turn.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs `f` on the monitor manager's thread and waits for it. `AppState` is
+/// only locked long enough to get at the manager, and `f` must not lock it.
+pub fn with_manager<R: Send + 'static>(
+    state: &Mutex<AppState>,
+    f: impl FnOnce(&mut MonitorManager) -> R + Send + 'static,
+) -> R {
+    let monitor_manager = { lock(state).monitor_manager.clone() };
+    monitor_manager.run(f)
+}
+
+/// Retries what a restore of the shared manager left behind; see
+/// [`ManagerHandle::finish_restore`].
+pub fn finish_restore(state: &Mutex<AppState>, results: Vec<DeviceResult>) -> Vec<DeviceResult> {
+    let monitor_manager = { lock(state).monitor_manager.clone() };
+    monitor_manager.finish_restore(results)
+}
+
+/// Restores monitors when any thread panics, so a bug doesn't leave displays
+/// off. It works from the snapshot on disk rather than the shared manager,
+/// since the panicking thread may be the display thread itself.
+fn install_panic_hook(show_dialog: bool) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("Panic: {}", info);
+        keep_awake::give_up();
+        let mut manager = MonitorManager::new(MonitorManager::load_saved_settings_from_disk(&config::config_dir()));
+        if manager.are_monitors_disabled() {
+            let results = manager.restore_all_monitors_with_retries();
+            match monitor::describe_failures(&results) {
+                Some(failures) => log::error!("Could not restore every monitor after the panic: {}", failures),
+                None => log::info!("Restored {} monitor(s) after the panic", results.len()),
+            }
+        }
+        if show_dialog {
+            native_windows_gui::error_message(
+                "Monitor Manager",
+                &format!(
+                    "Monitor Manager ran into a problem and tried to restore your monitors.\n\n{}\n\nDetails are in {}.",
+                    info,
+                    logging::log_file_path().display()
+                ),
+            );
+        }
+        default_hook(info);
+    }));
+}
+
+fn main() {
+    logging::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Just the watcher: no tray, dialogs or notifications, only the log.
+    let headless = args.iter().any(|arg| arg == "--no-tray");
+    let as_service = args.iter().any(|arg| arg == service::SERVICE_FLAG);
+    install_panic_hook(!headless && !as_service);
+
+    if as_service {
+        if let Err(e) = service::run() {
+            log::error!("Could not start as a service: {}", e);
+        }
+        return;
+    }
+
+    if let Some(command) = cli::parse(&args) {
+        std::process::exit(cli::run(command));
+    }
+
+    if let Err(e) = check_config_override() {
+        log::error!("{}", e);
+        if !headless {
+            native_windows_gui::error_message("Monitor Manager", &e);
+        }
+        return;
+    }
+
+    elevation::take_handoff(&args);
+    // A second instance would fight the first over the displays and could
+    // save an already-disabled layout as its baseline.
+    let Some(_instance) = single_instance::acquire() else {
+        if headless {
+            log::info!("Another instance is already running, exiting");
+            return;
+        }
+        log::info!("Another instance is already running, asking it to open settings");
+        if let Err(e) = ipc::send(ipc::Request::ShowSettings) {
+            log::warn!("Could not reach the running instance: {}", e);
+            native_windows_gui::simple_message("Monitor Manager", "Monitor Manager is already running.");
+        }
+        return;
+    };
+
+    let running = Running::start();
+    if headless {
+        log::info!("Running without the tray");
+        headless::install_ctrl_handler(Arc::clone(&running.state));
+    } else if let Err(e) = tray_app::run(Arc::clone(&running.state)) {
+        log::error!("{}", e);
+        native_windows_gui::error_message("Monitor Manager", &e.to_string());
+        lock(&running.state).shutdown.request();
+    }
+    running.join();
+}
+
+/// Fails when `--config` names a file that exists but can't be read, rather
+/// than starting on the defaults and overwriting it at the first save. One
+/// that doesn't exist yet is created then.
+fn check_config_override() -> Result<(), String> {
+    let Some(path) = config::config_override() else {
+        return Ok(());
+    };
+    match Config::read_from_disk() {
+        Err(AppError::ConfigIo { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+            log::info!("Using {}, which is created when the settings are first saved", path.display());
+            Ok(())
+        }
+        Err(e) => Err(format!("Could not load the config file given with --config:\n{}", e)),
+        Ok(_) => {
+            log::info!("Using the config file {}", path.display());
+            Ok(())
         }
     }
 }
 
-impl Config {
-    pub fn load() -> Self {
-        let config_path = Self::config_path();
-        if let Ok(content) = fs::read_to_string(&config_path) {
-            serde_json::from_str(&content).unwrap_or_default()
+/// The monitor loop and command pipe of the instance that owns the displays,
+/// whichever way it was started.
+pub struct Running {
+    pub state: Arc<Mutex<AppState>>,
+    monitor_thread: thread::JoinHandle<()>,
+    cursor_thread: thread::JoinHandle<()>,
+    awake_thread: thread::JoinHandle<()>,
+    command_server: thread::JoinHandle<()>,
+}
+
+impl Running {
+    /// Restores monitors a previous run left changed, then starts the loop
+    /// and the pipe. Only call it while holding the single-instance mutex.
+    pub fn start() -> Self {
+        let saved_settings = MonitorManager::load_saved_settings_from_disk(&config::config_dir());
+        let mut monitor_manager = MonitorManager::new(saved_settings);
+        let recovered = if monitor_manager.are_monitors_disabled() {
+            // A previous run exited while monitors were disabled.
+            let results = monitor_manager.restore_all_monitors_with_retries();
+            if let Some(failures) = monitor::describe_failures(&results) {
+                log::warn!("Some monitors could not be restored at startup: {}", failures);
+            }
+            let restored = results.iter().filter(|(_, r)| r.is_ok()).count();
+            log::info!("Restored {} monitor(s) left disabled by a previous session", restored);
+            Some(restored)
         } else {
-            Self::default()
+            None
+        };
+        // Before a new session could start and take the marker's place.
+        stats::recover();
+        let state = Arc::new(Mutex::new(AppState::new(monitor_manager)));
+        if lock(&state).elevated {
+            log::info!("Running as administrator");
         }
+        if lock(&state).config.event_log {
+            event_log::register();
+        }
+        let command_server = ipc::serve(Arc::clone(&state));
+        if let Some(count) = recovered {
+            lock(&state).status = format!("Idle - restored {} monitor(s) left disabled by a previous session", count);
+        }
+
+        let state_clone = Arc::clone(&state);
+        let monitor_thread = thread::spawn(move || {
+            monitor_loop(state_clone);
+        });
+        let cursor_thread = cursor_clip::spawn(Arc::clone(&state));
+        let awake_thread = keep_awake::spawn(Arc::clone(&state));
+        Self { state, monitor_thread, cursor_thread, awake_thread, command_server }
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = Self::config_path();
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(&config_path, content)?;
-        Ok(())
+    /// Waits for the monitor loop, which restores and returns once
+    /// `shutdown` is set, then stops the pipe.
+    pub fn join(self) {
+        if self.monitor_thread.join().is_err() {
+            log::error!("The monitor thread panicked");
+        }
+        if self.cursor_thread.join().is_err() {
+            log::error!("The cursor thread panicked");
+        }
+        if self.awake_thread.join().is_err() {
+            log::error!("The keep-awake thread panicked");
+        }
+        ipc::stop(self.command_server);
     }
+}
 
-    fn config_path() -> PathBuf {
-        let mut path = std::env::current_exe().unwrap_or_default();
-        path.pop();
-        path.push("config.json");
-        path
+const RELOAD_FAILED_PREFIX: &str = "config reload failed";
+const OUTSIDE_SCHEDULE: &str = "Outside schedule";
+
+/// Swaps a freshly parsed config.json into `AppState`, keeping the previous
+/// config if the file can't be read or parsed.
+fn reload_config(state: &Arc<Mutex<AppState>>) {
+    let result = Config::read_from_disk();
+    let mut state = lock(state);
+    match result {
+        Ok(config) => {
+            state.config = config;
+            state.config_generation += 1;
+            if state.status.starts_with(RELOAD_FAILED_PREFIX) {
+                state.status = "Config reloaded".to_string();
+            }
+        }
+        Err(e) => state.status = format!("{}: {}", RELOAD_FAILED_PREFIX, e),
     }
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    pub config: Config,
-    pub monitoring: bool,
-    pub status: String,
-    pub monitor_manager: Arc<Mutex<MonitorManager>>,
-    pub shutdown: Arc<AtomicBool>,
+/// The monitors the strategy applies to while this target runs.
+fn selection_for_target(config: &Config, target_idx: usize) -> MonitorSelection {
+    let profile = config.profile();
+    match profile.trigger {
+        Trigger::Processes => profile.monitors_for_target(&profile.targets[target_idx]),
+        Trigger::Fullscreen { .. } | Trigger::WindowTitle(_) => MonitorSelection::AllSecondaries,
+    }
 }
 
-impl AppState {
-    pub fn new(monitor_manager: MonitorManager) -> Self {
-        Self {
-            config: Config::load(),
-            monitoring: false,
-            status: "Idle - waiting for process".to_string(),
-            monitor_manager: Arc::new(Mutex::new(monitor_manager)),
-            shutdown: Arc::new(AtomicBool::new(false)),
+fn disable_for_target(state: &Arc<Mutex<AppState>>, config: &Config, target_idx: usize) {
+    let profile = config.profile();
+    let selection = selection_for_target(config, target_idx);
+    let target = target_label(profile, target_idx);
+    let (planned_selection, keep_enabled) = (selection.clone(), profile.keep_enabled.clone());
+    let planned = with_manager(state, move |manager| match planned_selection {
+        MonitorSelection::Devices(devices) => manager.without_excluded(devices).len(),
+        MonitorSelection::AllSecondaries => manager.secondary_device_names(keep_enabled.as_deref()).len(),
+    });
+    let strategy = profile.strategy.clone();
+    report_status(state, StatusEvent::Detected { target: target.clone(), monitors: planned, strategy });
+    let results = apply_strategy(state, config, selection);
+    notify_failures(state, config, "Monitor Not Changed", &results);
+    let value = target_value(profile, target_idx);
+    {
+        let mut state = lock(state);
+        state.last_target = target.clone();
+        state.last_target_value = value.clone();
+    }
+    let changed = results.iter().filter(|(_, r)| r.is_ok()).count();
+    webhook::send(&config.webhooks, webhook::Payload::new(webhook::Event::Active, target.clone(), changed));
+    stats::begin(&target, changed);
+    if config.event_log {
+        let message = format!("Changed {} monitor(s) for {} (profile {})", changed, target, config.active_profile);
+        event_log::info(event_log::SESSION_STARTED, &message);
+        log_failed_changes("change", &results);
+    }
+    run_commands(state, config, &profile.on_disable, value, &results);
+}
+
+/// Applies the strategy again for a target that was already running when
+/// the displays were re-enumerated. The session goes on, so there are no
+/// webhooks, statistics, event log entries or `on_disable` commands.
+fn reapply_for_target(state: &Arc<Mutex<AppState>>, config: &Config, target_idx: usize) {
+    let results = apply_strategy(state, config, selection_for_target(config, target_idx));
+    notify_failures(state, config, "Monitor Not Changed", &results);
+}
+
+/// Puts back what the loop changed once no target needs it any more.
+fn restore_after_target(state: &Arc<Mutex<AppState>>, config: &Config) {
+    let results = restore_monitors(state);
+    notify_failures(state, config, "Monitor Not Restored", &results);
+    let (target, value) = {
+        let state = lock(state);
+        (state.last_target.clone(), state.last_target_value.clone())
+    };
+    let restored = results.iter().filter(|(_, r)| r.is_ok()).count();
+    if restored > 0 {
+        report_status(state, StatusEvent::Restored { target: target.clone(), monitors: restored });
+    }
+    webhook::send(&config.webhooks, webhook::Payload::new(webhook::Event::Idle, target.clone(), restored));
+    stats::end();
+    if config.event_log {
+        event_log::info(event_log::SESSION_ENDED, &format!("Restored {} monitor(s) after {}", restored, target));
+        log_failed_changes("restore", &results);
+    }
+    run_commands(state, config, &config.profile().on_restore, value, &results);
+}
+
+/// Hands `event` to the tray, if there is one.
+fn report_status(state: &Arc<Mutex<AppState>>, event: StatusEvent) {
+    if let Some(sender) = &lock(state).status_events {
+        let _ = sender.send(event);
+    }
+}
+
+/// Writes an error event for each monitor in `results` that failed.
+fn log_failed_changes(action: &str, results: &[DeviceResult]) {
+    for (device, result) in results {
+        let Err(e) = result else {
+            continue;
+        };
+        let code = e.disp_change_code().map(|code| format!(" [DISP_CHANGE {}]", code)).unwrap_or_default();
+        event_log::error(event_log::CHANGE_FAILED, &format!("Could not {} {}: {}{}", action, device, e, code));
+    }
+}
+
+/// Starts the profile's `on_disable` or `on_restore` commands in the
+/// background. Each failing command is mentioned once per run.
+fn run_commands(
+    state: &Arc<Mutex<AppState>>,
+    config: &Config,
+    commands: &[Vec<String>],
+    target: String,
+    results: &[DeviceResult],
+) {
+    if commands.is_empty() {
+        return;
+    }
+    let placeholders = hooks::Placeholders {
+        target,
+        monitors: results.iter().filter(|(_, r)| r.is_ok()).count(),
+        profile: config.active_profile.clone(),
+    };
+    let state = Arc::clone(state);
+    hooks::run(commands, config.profile().use_shell, config.command_timeout(), &placeholders, move |command, reason| {
+        let first = {
+            let mut state = lock(&state);
+            let first = !state.failed_commands.iter().any(|failed| failed == command);
+            if first {
+                state.failed_commands.push(command.to_vec());
+            }
+            first
+        };
+        if first {
+            notify_warning(&state, "Command Failed", &format!("{} {}", command.join(" "), reason));
+        }
+    });
+}
+
+/// Tells the user which monitors a change failed on. A monitor that keeps
+/// failing is mentioned less and less often.
+fn notify_failures(state: &Arc<Mutex<AppState>>, config: &Config, title: &str, results: &[DeviceResult]) {
+    let (notice, has_tray) = {
+        let mut state = lock(state);
+        (state.failures.notice(results, Instant::now()), state.has_tray)
+    };
+    if let Some(notice) = notice {
+        log::warn!("{}: {}", title, notice);
+        if has_tray {
+            notify::notify(config.notifications, notify::Severity::Warning, title, &notice);
         }
     }
 }
 
-fn main() {
-    let monitor_manager = MonitorManager::new();
-    let app_state = Arc::new(Mutex::new(AppState::new(monitor_manager)));
+/// Tells the user, once per run, that a monitor's scaling couldn't be put back.
+fn notify_scale_warning(state: &Arc<Mutex<AppState>>, warning: Option<String>) {
+    if let Some(warning) = warning {
+        notify_warning(state, "Scaling Not Restored", &warning);
+    }
+}
 
-    let state_clone = Arc::clone(&app_state);
-    let monitor_thread = thread::spawn(move || {
-        monitor_loop(state_clone);
+fn notify_warning(state: &Arc<Mutex<AppState>>, title: &str, message: &str) {
+    let (has_tray, enabled) = {
+        let state = lock(state);
+        (state.has_tray, state.config.notifications)
+    };
+    if has_tray {
+        notify::notify(enabled, notify::Severity::Warning, title, message);
+    }
+}
+
+/// Explains that a process that may be a target runs as administrator, so
+/// it can only be matched by name, and points at Restart Elevated.
+fn warn_not_elevated(state: &Arc<Mutex<AppState>>, process_name: &str) {
+    log::warn!("{} may be running as administrator while Monitor Manager isn't", process_name);
+    notify_warning(
+        state,
+        "Target Running as Administrator",
+        &format!(
+            "{} runs as administrator, so Monitor Manager can't fully see it. \
+             Choose Restart Elevated in the tray menu to run with the same rights.",
+            process_name
+        ),
+    );
+}
+
+/// Makes the profile's audio device the default, remembering the one it
+/// replaces. A device that isn't connected is skipped with a notice rather
+/// than holding up the displays.
+fn switch_audio_for_target(state: &Arc<Mutex<AppState>>, config: &Config) {
+    let Some(id) = &config.profile().audio_device else {
+        return;
+    };
+    if lock(state).previous_audio.is_some() {
+        return;
+    }
+    let connected = match audio::playback_devices() {
+        Ok(devices) => devices.iter().any(|device| device.id == *id),
+        Err(e) => {
+            log::warn!("Could not list the audio devices: {}", e.message());
+            return;
+        }
+    };
+    if !connected {
+        log::warn!("Audio device {} is not connected, leaving the default alone", id);
+        notify_warning(state, "Audio Not Switched", "The profile's audio device isn't connected.");
+        return;
+    }
+    let switched = audio::default_playback_device().and_then(|previous| {
+        if previous != *id {
+            audio::set_default_playback_device(id)?;
+            lock(state).previous_audio = Some(previous);
+        }
+        Ok(())
     });
+    if let Err(e) = switched {
+        log::warn!("Could not switch the audio device: {}", e.message());
+        notify_warning(state, "Audio Not Switched", &e.message());
+    }
+}
+
+/// Activates the profile's power plan, remembering the one it replaces.
+fn switch_power_scheme_for_target(state: &Arc<Mutex<AppState>>, config: &Config) {
+    let Some(guid) = &config.profile().power_scheme else {
+        return;
+    };
+    if lock(state).previous_power_scheme.is_some() {
+        return;
+    }
+    let switched = power_plan::active_scheme().and_then(|previous| {
+        if !previous.eq_ignore_ascii_case(guid) {
+            power_plan::set_active_scheme(guid)?;
+            lock(state).previous_power_scheme = Some(previous);
+        }
+        Ok(())
+    });
+    if let Err(e) = switched {
+        log::warn!("Could not switch the power plan to {}: {}", guid, e.message());
+        notify_warning(state, "Power Plan Not Switched", &e.message());
+    }
+}
+
+/// Puts back the power plan a profile replaced, or Balanced if that plan
+/// was deleted in the meantime.
+fn restore_power_scheme(state: &Arc<Mutex<AppState>>) {
+    let Some(previous) = lock(state).previous_power_scheme.take() else {
+        return;
+    };
+    let still_exists = match power_plan::schemes() {
+        Ok(schemes) => schemes.iter().any(|scheme| scheme.guid.eq_ignore_ascii_case(&previous)),
+        // Let setting it report what's wrong.
+        Err(_) => true,
+    };
+    let previous = if still_exists {
+        previous
+    } else {
+        log::warn!("Power plan {} no longer exists, switching to Balanced", previous);
+        power_plan::BALANCED.to_string()
+    };
+    if let Err(e) = power_plan::set_active_scheme(&previous) {
+        log::warn!("Could not switch the power plan back: {}", e.message());
+    }
+}
+
+/// Suspends or closes the profile's `suspend_processes`, unless that's
+/// already done for this target.
+fn set_aside_for_target(state: &Arc<Mutex<AppState>>, config: &Config) {
+    let profile = config.profile();
+    if profile.suspend_processes.is_empty() || !lock(state).set_aside.is_empty() {
+        return;
+    }
+    let targets = profile.compile_targets().unwrap_or_default();
+    let set_aside = background::set_aside(&profile.suspend_processes, &targets, profile.match_mode);
+    lock(state).set_aside = set_aside;
+}
+
+/// Resumes or restarts whatever `set_aside_for_target` put aside.
+fn bring_back_set_aside(state: &Arc<Mutex<AppState>>) {
+    let set_aside = mem::take(&mut lock(state).set_aside);
+    if !set_aside.is_empty() {
+        background::bring_back(set_aside);
+    }
+}
+
+/// Switches back to the playback device a profile replaced, if any.
+fn restore_audio(state: &Arc<Mutex<AppState>>) {
+    let Some(previous) = lock(state).previous_audio.take() else {
+        return;
+    };
+    if let Err(e) = audio::set_default_playback_device(&previous) {
+        log::warn!("Could not switch the audio device back: {}", e.message());
+    }
+}
 
-    tray_app::run(app_state);
+/// Reruns the changes that last failed on the monitors they failed on and
+/// returns the per-device results.
+pub fn retry_failed(state: &Arc<Mutex<AppState>>) -> Vec<DeviceResult> {
+    let (config, (apply, restore)) = {
+        let mut state = lock(state);
+        (state.config.clone(), state.failures.take_pending())
+    };
+    let mut results = Vec::new();
+    if !apply.is_empty() {
+        log::info!("Retrying changes on {}", apply.join(", "));
+        results.extend(apply_strategy(state, &config, MonitorSelection::Devices(apply)));
+    }
+    if !restore.is_empty() {
+        // Monitors that failed to restore are still in the snapshot, so only
+        // they are put back.
+        log::info!("Retrying the restore of {}", restore.join(", "));
+        results.extend(restore_monitors(state));
+    }
+    results
+}
+
+/// Runs the configured strategy on every secondary monitor right away, as if
+/// a target had started, and returns how many monitors it changed.
+pub fn disable_now(state: &Arc<Mutex<AppState>>) -> usize {
+    let config = { lock(state).config.clone() };
+    let results = apply_strategy(state, &config, MonitorSelection::AllSecondaries);
+    results.iter().filter(|(_, r)| r.is_ok()).count()
+}
+
+/// Puts every changed monitor back right away and returns the per-device
+/// results.
+pub fn restore_now(state: &Arc<Mutex<AppState>>) -> Vec<DeviceResult> {
+    let results = with_manager(state, |manager| manager.restore_all_monitors());
+    let results = finish_restore(state, results);
+    let scale_warning = with_manager(state, |manager| manager.take_scale_warning());
+    let restored = results.iter().filter(|(_, r)| r.is_ok()).count();
+    notify_scale_warning(state, scale_warning);
+    restore_audio(state);
+    restore_power_scheme(state);
+    bring_back_set_aside(state);
+
+    let mut state = lock(state);
+    state.monitoring = false;
+    state.changed_monitors.clear();
+    // Nothing is left for a pending restore to do.
+    if matches!(state.watcher, WatcherState::PendingRestore { .. }) {
+        state.watcher = WatcherState::Idle;
+    }
+    state.status = format!("Idle - restored {} monitor(s)", restored);
+    state.failures.record(ChangeKind::Restore, &results);
+    let failures = monitor::describe_failures(&results);
+    state.failed = failures.is_some();
+    if let Some(failures) = failures {
+        state.status = format!("{} (failed: {})", state.status, failures);
+    }
+    results
+}
+
+/// Pauses or resumes the monitor loop. Any changed monitors are restored on
+/// its next tick.
+pub fn set_paused(state: &Arc<Mutex<AppState>>, paused: bool) {
+    let mut state = lock(state);
+    if state.paused == paused {
+        return;
+    }
+    state.paused = paused;
+    state.status = if paused {
+        "Paused - monitors won't be changed".to_string()
+    } else {
+        "Idle - waiting for process".to_string()
+    };
+}
 
-    monitor_thread.join().unwrap();
+/// Makes the profile called `name` the active one and saves the config. If
+/// monitors are changed for a target, what the old profile changed is put
+/// back and the loop applies the new profile on its next tick.
+pub fn switch_profile(state: &Arc<Mutex<AppState>>, name: &str) -> Result<(), AppError> {
+    let (holds_monitors, config) = {
+        let mut state = lock(state);
+        if state.config.active_profile == name || state.config.profile_index(name).is_none() {
+            return Ok(());
+        }
+        state.config.active_profile = name.to_string();
+        state.config_generation += 1;
+        (state.watcher.holds_monitors(), state.config.clone())
+    };
+    log::info!("Switched to profile {}", name);
+    if holds_monitors {
+        restore_monitors(state);
+        lock(state).revalidate = true;
+    }
+    config.save()
 }
 
-fn is_target_running(system: &System, target_exe: &str) -> bool {
-    let target_lower = target_exe.to_lowercase();
-    let target_filename = Path::new(target_exe)
-        .file_name()
-        .map(|f| f.to_string_lossy().to_lowercase());
+fn apply_strategy(state: &Arc<Mutex<AppState>>, config: &Config, selection: MonitorSelection) -> Vec<DeviceResult> {
+    let profile = config.profile();
+    let monitor_manager = { lock(state).monitor_manager.clone() };
+    // Before the monitor the audio may be playing on goes away.
+    switch_audio_for_target(state, config);
+    switch_power_scheme_for_target(state, config);
+    set_aside_for_target(state, config);
+    // Rotated first, so the strategy leaves those monitors alone.
+    let rotated = if profile.orientations.is_empty() {
+        Vec::new()
+    } else {
+        let orientations = profile.orientations.clone();
+        monitor_manager.run(move |manager| {
+            manager.save_current_settings();
+            manager.apply_orientation_rules(&orientations)
+        })
+    };
+    let selection = match selection {
+        MonitorSelection::Devices(mut devices) => {
+            devices.retain(|device| !rotated.iter().any(|(name, r)| r.is_ok() && name == device));
+            MonitorSelection::Devices(devices)
+        }
+        selection => selection,
+    };
+    if profile.strategy == Strategy::SwitchPrimary {
+        let results = switch_primary_for_target(state, config, rotated);
+        return enable_hdr_for_target(state, config, results);
+    }
+
+    let (strategy, keep_enabled) = (profile.strategy.clone(), profile.keep_enabled.clone());
+    let (results, monitors) = monitor_manager.run(move |manager| {
+        manager.save_current_settings();
+        // Names are read while every monitor is still attached.
+        let monitors = manager.get_all_monitors();
+        let devices = |manager: &MonitorManager, selection| match selection {
+            MonitorSelection::AllSecondaries => manager.secondary_device_names(keep_enabled.as_deref()),
+            MonitorSelection::Devices(devices) => manager.without_excluded(devices),
+        };
+        let results = match (&strategy, selection) {
+            (Strategy::ReduceRefreshRate { hz }, selection) => {
+                let devices = devices(manager, selection);
+                manager.set_refresh_rates(&devices, *hz)
+            }
+            (Strategy::ReduceResolution { width, height }, selection) => {
+                let devices = devices(manager, selection);
+                manager.set_resolutions(&devices, *width, *height)
+            }
+            (Strategy::DdcPowerOff, selection) => {
+                let devices = devices(manager, selection);
+                manager.power_off_monitors(&devices)
+            }
+            (Strategy::Blank, selection) => {
+                let devices = devices(manager, selection);
+                manager.blank_monitors(&devices)
+            }
+            (_, MonitorSelection::AllSecondaries) => match &keep_enabled {
+                Some(keep) => manager.disable_all_except(keep),
+                None => manager.disable_secondary_monitors(),
+            },
+            (_, MonitorSelection::Devices(devices)) => {
+                let devices = manager.without_excluded(devices);
+                manager.disable_monitors(&devices)
+            }
+        };
+        (results, monitors)
+    });
+    let changed_count = results.iter().filter(|(_, r)| r.is_ok()).count();
+    let changed_names: Vec<String> = results
+        .iter()
+        .filter(|(_, r)| r.is_ok())
+        .map(|(device, _)| {
+            monitors
+                .iter()
+                .find(|monitor| monitor.device_name == *device)
+                .map_or_else(|| device.clone(), |monitor| monitor.display_name().to_string())
+        })
+        .collect();
+    let rotated_count = rotated.iter().filter(|(_, r)| r.is_ok()).count();
+    let results: Vec<DeviceResult> = rotated.into_iter().chain(results).collect();
 
-    system.processes().values().any(|process| {
-        if let Some(exe_path) = process.exe() {
-            if exe_path.to_string_lossy().to_lowercase() == target_lower {
-                return true;
+    {
+        let mut state = lock(state);
+        state.monitoring = true;
+        for name in changed_names {
+            if !state.changed_monitors.contains(&name) {
+                state.changed_monitors.push(name);
             }
-            if let Some(ref target_fn) = target_filename {
-                if let Some(proc_fn) = exe_path.file_name() {
-                    return proc_fn.to_string_lossy().to_lowercase() == *target_fn;
-                }
+        }
+        state.status = match (&profile.strategy, changed_count) {
+            (_, 0) if rotated_count > 0 => format!("Active - rotated {} monitor(s)", rotated_count),
+            (_, 0) => "Active - no secondary monitors to change".to_string(),
+            (Strategy::ReduceRefreshRate { hz }, n) => format!("Active - set {} monitor(s) to {}Hz", n, hz),
+            (Strategy::ReduceResolution { width, height }, n) => {
+                format!("Active - set {} monitor(s) to {}x{}", n, width, height)
             }
-        } else if let Some(ref target_fn) = target_filename {
-            return process.name().to_string_lossy().to_lowercase() == *target_fn;
+            (Strategy::DdcPowerOff, n) => format!("Active - turned off {} monitor(s)", n),
+            (Strategy::Blank, n) => format!("Active - blanked {} monitor(s)", n),
+            (_, n) => format!("Active - disabled {} monitor(s)", n),
+        };
+        if rotated_count > 0 && changed_count > 0 {
+            state.status = format!("{}, rotated {}", state.status, rotated_count);
+        }
+        state.failures.record(ChangeKind::Apply, &results);
+        let failures = monitor::describe_failures(&results);
+        state.failed = failures.is_some();
+        if let Some(failures) = failures {
+            state.status = format!("{} ({})", state.status, failures);
+        }
+    }
+    enable_hdr_for_target(state, config, results)
+}
+
+/// Turns on HDR on the kept monitor if the profile asks for it, adding the
+/// outcome to `results` and the status.
+fn enable_hdr_for_target(
+    state: &Arc<Mutex<AppState>>,
+    config: &Config,
+    mut results: Vec<DeviceResult>,
+) -> Vec<DeviceResult> {
+    let profile = config.profile();
+    if !profile.enable_hdr {
+        return results;
+    }
+    let keep_enabled = profile.keep_enabled.clone();
+    let (device_name, result) = with_manager(state, move |manager| manager.enable_hdr(keep_enabled.as_deref()));
+
+    let mut state = lock(state);
+    match &result {
+        Ok(()) => state.status = format!("{}, HDR on", state.status),
+        Err(e) => {
+            state.failed = true;
+            state.status = format!("{} (HDR not turned on for {}: {})", state.status, device_name, e);
         }
-        false
-    })
+    }
+    results.push((device_name, result));
+    state.failures.record(ChangeKind::Apply, &results);
+    results
+}
+
+/// `results` holds what the profile's orientation rules already did.
+fn switch_primary_for_target(
+    state: &Arc<Mutex<AppState>>,
+    config: &Config,
+    mut results: Vec<DeviceResult>,
+) -> Vec<DeviceResult> {
+    let Some(keep) = config.profile().keep_enabled.clone() else {
+        let mut state = lock(state);
+        state.monitoring = true;
+        state.status = "Active - no monitor chosen to become primary".to_string();
+        state.failures.record(ChangeKind::Apply, &results);
+        return results;
+    };
+
+    let (device_name, result) = with_manager(state, move |manager| {
+        manager.save_current_settings();
+        manager.switch_primary(&keep)
+    });
+
+    let mut state = lock(state);
+    state.monitoring = true;
+    state.failed = result.is_err();
+    state.status = match &result {
+        Ok(()) => format!("Active - switched primary to {}", device_name),
+        Err(e) => format!("Active - could not switch primary to {}: {}", device_name, e),
+    };
+    results.push((device_name, result));
+    if let Some(failures) = monitor::describe_failures(&results[..results.len() - 1]) {
+        state.failed = true;
+        state.status = format!("{} (not rotated: {})", state.status, failures);
+    }
+    state.failures.record(ChangeKind::Apply, &results);
+    results
 }
 
+fn restore_monitors(state: &Arc<Mutex<AppState>>) -> Vec<DeviceResult> {
+    let (monitor_manager, include_manual) = {
+        let state = lock(state);
+        (state.monitor_manager.clone(), state.config.restore_manually_disabled)
+    };
+    let results = monitor_manager.run(move |manager| manager.restore_session_monitors(include_manual));
+    let results = monitor_manager.finish_restore(results);
+    let scale_warning = monitor_manager.run(|manager| manager.take_scale_warning());
+    let restored_count = results.iter().filter(|(_, r)| r.is_ok()).count();
+    notify_scale_warning(state, scale_warning);
+    restore_audio(state);
+    restore_power_scheme(state);
+    bring_back_set_aside(state);
+
+    let mut state = lock(state);
+    state.monitoring = false;
+    state.changed_monitors.clear();
+    state.status = if restored_count > 0 {
+        format!("Idle - restore verified for {} monitor(s)", restored_count)
+    } else {
+        "Idle - no monitors needed restoration".to_string()
+    };
+    state.failures.record(ChangeKind::Restore, &results);
+    let failures = monitor::describe_failures(&results);
+    state.failed = failures.is_some();
+    if let Some(failures) = failures {
+        state.status = format!("{} (failed: {})", state.status, failures);
+    }
+    results
+}
+
+/// What the monitor loop matches against, compiled from the active profile.
+struct Compiled {
+    patterns: Vec<TargetPattern>,
+    title: Option<TitlePattern>,
+    children: Option<ChildPattern>,
+}
+
+/// The profile fields that get compiled.
+type CompiledFrom = (Vec<String>, Trigger, Option<ChildTrigger>);
+
+/// The fields a compile result was produced from, so the loop only
+/// recompiles when the config actually changes.
+type CompiledTargets = (CompiledFrom, Result<Compiled, PatternError>);
+
 fn monitor_loop(state: Arc<Mutex<AppState>>) {
-    let mut system = System::new_all();
-    let mut was_running = false;
+    let shutdown = lock(&state).shutdown.clone();
+    let mut watcher = ProcessWatcher::new();
+    let mut compiled: Option<CompiledTargets> = None;
+    let mut config_mtime = { lock(&state).config.modified_time() };
+    let mut clamped_interval_warned = config::DEFAULT_POLL_INTERVAL_MS;
+    let mut idle_layout: Option<Vec<MonitorInfo>> = None;
+    // The elevation notice is shown once per run.
+    let mut elevation_warned = false;
+    let mut config_generation = None;
+    let mut remote = false;
+    let mut ticks = TickWait::new(shutdown.clone());
+    let mut subscribed_for: Option<(ProcessDetection, CompiledFrom)> = None;
+    // Whether no process event has arrived since the last poll, so the
+    // running targets it found still hold.
+    let mut targets_unchanged = false;
+    let mut running_targets = RunningTargets::default();
+    // The targets the status line last named.
+    let mut named_targets: Vec<usize> = Vec::new();
+    let mut foreground = Debounce::default();
+    // The process whose window matched the title trigger.
+    let mut title_owner: Option<u32> = None;
+    let mut stats_heartbeat = Instant::now();
 
-    loop {
-        let shutdown = { state.lock().unwrap().shutdown.load(Ordering::Relaxed) };
-        if shutdown {
-            let monitor_manager = { state.lock().unwrap().monitor_manager.clone() };
-            let mut manager = monitor_manager.lock().unwrap();
-            if manager.are_monitors_disabled() {
-                let _ = manager.restore_all_monitors();
+    while !shutdown.is_requested() {
+        if stats_heartbeat.elapsed() >= stats::HEARTBEAT_INTERVAL {
+            stats_heartbeat = Instant::now();
+            stats::heartbeat();
+        }
+
+        let current_mtime = { lock(&state).config.modified_time() };
+        if current_mtime != config_mtime {
+            // Remember the new mtime even on failure so a broken file isn't re-parsed every tick.
+            config_mtime = current_mtime;
+            reload_config(&state);
+        }
+
+        let (paused, in_schedule, poll_interval) = {
+            let state = lock(&state);
+            let in_schedule = state.config.in_schedule(chrono::Local::now().naive_local());
+            (state.paused, in_schedule, state.config.poll_interval())
+        };
+        if paused || !in_schedule {
+            lock(&state).cursor = CursorClip::default();
+            let previous = mem::replace(&mut lock(&state).watcher, WatcherState::Paused);
+            if previous.holds_monitors() {
+                let config = { lock(&state).config.clone() };
+                restore_after_target(&state, &config);
+            }
+            let mut state = lock(&state);
+            if paused {
+                if previous.holds_monitors() {
+                    state.status = "Paused - monitors restored".to_string();
+                }
+            } else if previous.holds_monitors() {
+                log::info!("Outside the schedule, monitors restored");
+                state.status = format!("{} - monitors restored", OUTSIDE_SCHEDULE);
+            } else if !state.status.starts_with(OUTSIDE_SCHEDULE) {
+                log::info!("Outside the schedule, leaving the displays alone");
+                state.status = format!("{} - monitors won't be changed", OUTSIDE_SCHEDULE);
+            }
+            drop(state);
+            shutdown.wait(poll_interval);
+            continue;
+        }
+        {
+            let mut state = lock(&state);
+            if state.watcher == WatcherState::Paused {
+                state.watcher = WatcherState::Idle;
+                if state.status.starts_with(OUTSIDE_SCHEDULE) {
+                    state.status = "Idle - waiting for process".to_string();
+                }
             }
-            break;
         }
 
-        system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+        if is_remote_session() {
+            if !remote {
+                remote = true;
+                lock(&state).cursor = CursorClip::default();
+                log::info!("Remote session detected, leaving the displays alone");
+                lock(&state).status = "Paused - remote session".to_string();
+            }
+            shutdown.wait(poll_interval);
+            continue;
+        }
+        if remote {
+            remote = false;
+            log::info!("Back on the console session");
+            lock(&state).revalidate = true;
+        }
 
-        let target_exe = { state.lock().unwrap().config.target_exe.clone() };
-        let is_running = is_target_running(&system, &target_exe);
+        let (config, generation) = {
+            let state = lock(&state);
+            (state.config.clone(), state.config_generation)
+        };
+        let profile = config.profile();
+        if config_generation != Some(generation) {
+            config_generation = Some(generation);
+            watcher.set_name_fallback(config.name_fallback);
+            let (backend, persist_changes) = (config.display_backend, config.persist_changes);
+            let (managed, excluded) = (config.managed_monitors.clone(), config.excluded_monitors.clone());
+            let released = with_manager(&state, move |manager| {
+                manager.set_backend(backend);
+                manager.set_persist_changes(persist_changes);
+                manager.set_managed_monitors(managed);
+                manager.set_excluded_monitors(excluded);
+                manager.release_unmanaged_monitors()
+            });
+            if let Some(failures) = monitor::describe_failures(&released) {
+                log::warn!("Could not bring back monitors that are no longer managed: {}", failures);
+            } else if !released.is_empty() {
+                log::info!("Brought back {} monitor(s) that are no longer managed", released.len());
+            }
+        }
+        let poll_interval = config.poll_interval();
+        if config.poll_interval_ms != clamped_interval_warned && poll_interval.as_millis() as u64 != config.poll_interval_ms {
+            clamped_interval_warned = config.poll_interval_ms;
+            let mut state = lock(&state);
+            state.status = format!(
+                "Poll interval {}ms is out of range, using {}ms",
+                config.poll_interval_ms,
+                poll_interval.as_millis()
+            );
+        }
 
-        if is_running && !was_running {
-            let monitor_manager = { state.lock().unwrap().monitor_manager.clone() };
-            let disabled_count = {
-                let mut manager = monitor_manager.lock().unwrap();
-                manager.save_current_settings();
-                manager.disable_secondary_monitors()
-            };
+        let compiled_for = (profile.targets.clone(), profile.trigger.clone(), profile.children.clone());
+        if compiled.as_ref().is_none_or(|(source, _)| *source != compiled_for) {
+            let result = profile.compile_targets().and_then(|patterns| {
+                Ok(Compiled {
+                    patterns,
+                    title: match &profile.trigger {
+                        Trigger::WindowTitle(title) => Some(TitlePattern::parse(title)?),
+                        _ => None,
+                    },
+                    children: profile.children.as_ref().map(ChildPattern::compile).transpose()?,
+                })
+            });
+            let had_error = compiled.as_ref().is_some_and(|(_, previous)| previous.is_err());
+            let mut state = lock(&state);
+            match &result {
+                Err(e) => state.status = format!("Error - {}", e),
+                Ok(_) if had_error && !state.watcher.holds_monitors() => state.status = "Idle - waiting for process".to_string(),
+                Ok(_) => {}
+            }
+            compiled = Some((compiled_for.clone(), result));
+            title_owner = None;
+            // Launched and running processes are remembered by target index.
+            watcher = ProcessWatcher::new();
+            watcher.set_name_fallback(config.name_fallback);
+            running_targets = RunningTargets::default();
+        }
 
-            {
-                let mut state = state.lock().unwrap();
-                state.monitoring = true;
-                state.status = if disabled_count > 0 {
-                    format!("Active - disabled {} monitor(s)", disabled_count)
-                } else {
-                    "Active - no secondary monitors to disable".to_string()
-                };
-            }
-
-            was_running = true;
-        } else if !is_running && was_running {
-            let monitor_manager = { state.lock().unwrap().monitor_manager.clone() };
-            let restored_count = {
-                let mut manager = monitor_manager.lock().unwrap();
-                manager.restore_all_monitors().len()
-            };
+        // Leave the displays alone until the patterns are fixed.
+        let Some((_, Ok(Compiled { patterns, title: title_pattern, children }))) = &compiled else {
+            shutdown.wait(poll_interval);
+            continue;
+        };
+
+        let subscription = (config.process_detection, compiled_for);
+        if subscribed_for.as_ref() != Some(&subscription) {
+            // Events only cover the targets themselves: other triggers don't
+            // look at processes and launchers need their children checked.
+            let watches_targets = profile.trigger == Trigger::Processes && children.is_none();
+            ticks.set_events(match config.process_detection {
+                ProcessDetection::Events if watches_targets => match ProcessEvents::subscribe(patterns) {
+                    Ok(events) => Some(events),
+                    Err(e) => {
+                        log::warn!("Could not subscribe to process events, polling instead: {}", e);
+                        None
+                    }
+                },
+                _ => None,
+            });
+            subscribed_for = Some(subscription);
+            targets_unchanged = false;
+        }
+        let revalidate = mem::take(&mut lock(&state).revalidate);
+        if revalidate {
+            // Waking re-enumerates the displays, which can turn monitors back
+            // on or give them new device names, so pending delays are dropped
+            // and the state is worked out afresh.
+            idle_layout = None;
+            let missing = with_manager(&state, |manager| manager.missing_saved_monitors());
+            if !missing.is_empty() {
+                log::warn!("Saved monitors missing after resume: {}", missing.join(", "));
+            }
+        }
+        // The fullscreen and title triggers report their one pretend target
+        // as index 0.
+        let reuse_poll = mem::take(&mut targets_unchanged) && !revalidate;
+        let found: Vec<usize> = match &profile.trigger {
+            Trigger::Fullscreen { borderless } => {
+                fullscreen::is_active(*borderless).then_some(0).into_iter().collect()
+            }
+            // A match sticks to its process, so titles that change mid-session
+            // (FPS counters, level names) don't end it; until then every
+            // window is checked on each tick.
+            Trigger::WindowTitle(_) => {
+                if title_owner.is_some_and(|pid| !watcher.is_running(pid)) {
+                    title_owner = None;
+                }
+                if title_owner.is_none() {
+                    title_owner = title_pattern.as_ref().and_then(watcher::window_title_owner);
+                }
+                title_owner.map(|_| 0).into_iter().collect()
+            }
+            Trigger::Processes if reuse_poll => running_targets.running(),
+            Trigger::Processes => match children {
+                Some(child) => watcher.poll_launched(patterns, profile.match_mode, child).into_iter().collect(),
+                None => watcher.poll_all(patterns, profile.match_mode),
+            },
+        };
+        if let Some(name) = watcher.take_unreadable() {
+            if !elevation_warned && !lock(&state).elevated {
+                elevation_warned = true;
+                warn_not_elevated(&state, &name);
+            }
+        }
+        match running_targets.update(&found) {
+            Some(WatchEvent::Started(idx)) => log::debug!("{} started", target_label(profile, idx)),
+            Some(WatchEvent::Exited) => log::debug!("No target is running any more"),
+            None => {}
+        }
+        let process_target = running_targets.first();
+        // Alt-tabbing only counts once it has held for the debounce; while it
+        // hasn't, whichever target is running keeps its rules.
+        let foreground_debounce = config.foreground_debounce();
+        let running_target = match process_target {
+            Some(_) if profile.require_foreground && profile.trigger == Trigger::Processes && children.is_none() => {
+                let focused = watcher.foreground_target(patterns, profile.match_mode);
+                foreground
+                    .update(focused.is_some(), Instant::now(), foreground_debounce)
+                    .then(|| focused.or(process_target))
+                    .flatten()
+            }
+            _ => {
+                foreground.reset(false);
+                process_target
+            }
+        };
+        if revalidate {
+            let holds_monitors = lock(&state).watcher.holds_monitors();
+            match (running_target, holds_monitors) {
+                (Some(idx), true) => {
+                    lock(&state).watcher = WatcherState::Active;
+                    reapply_for_target(&state, &config, idx);
+                }
+                (None, true) => {
+                    lock(&state).watcher = WatcherState::Idle;
+                    restore_after_target(&state, &config);
+                }
+                _ => {
+                    let mut state = lock(&state);
+                    state.watcher = WatcherState::Idle;
+                    state.status = "Idle - waiting for process".to_string();
+                }
+            }
+        }
 
-            {
-                let mut state = state.lock().unwrap();
-                state.monitoring = false;
-                state.status = if restored_count > 0 {
-                    format!("Idle - restored {} monitor(s)", restored_count)
-                } else {
-                    "Idle - no monitors needed restoration".to_string()
-                };
+        let (disable_delay, restore_delay) = (config.disable_delay(), config.restore_delay());
+        let now = Instant::now();
+        let (previous, current, action) = {
+            let mut state = lock(&state);
+            let previous = state.watcher;
+            let (current, action) = previous.step(running_target, now, disable_delay, restore_delay);
+            state.watcher = current;
+            (previous, current, action)
+        };
+        // `running_targets` tracks each target and the watcher only sees
+        // whether any is running, so one exiting while another runs changes
+        // nothing but the names shown.
+        let detected = if running_target.is_some() { running_targets.running() } else { Vec::new() };
+        let names = || detected.iter().map(|&idx| target_label(profile, idx)).collect::<Vec<_>>().join(", ");
+        match action {
+            Some(WatchAction::Disable(idx)) => {
+                disable_for_target(&state, &config, idx);
+                let mut state = lock(&state);
+                state.status = format!("{} ({} running)", state.status, names());
+            }
+            Some(WatchAction::Restore) => restore_after_target(&state, &config),
+            None => {
+                if let Some(status) = transition_status(previous, current) {
+                    lock(&state).status = status.to_string();
+                } else if current == WatcherState::Active && !named_targets.is_empty() && named_targets != detected {
+                    log::info!("Running targets changed: {}", names());
+                    lock(&state).status = format!("Active - {} running", names());
+                }
             }
+        }
+        named_targets = detected;
+        if profile.confine_cursor && current == WatcherState::Active {
+            let pids = match &profile.trigger {
+                Trigger::Processes => watcher.target_pids(patterns, profile.match_mode),
+                Trigger::Fullscreen { borderless } => fullscreen::foreground_process(*borderless).into_iter().collect(),
+                Trigger::WindowTitle(_) => title_owner.into_iter().collect(),
+            };
+            update_cursor_clip(&state, profile, pids);
+        } else {
+            lock(&state).cursor = CursorClip::default();
+        }
+        if let Some(remaining) = current.remaining(now, disable_delay, restore_delay) {
+            lock(&state).status = countdown_status(current, remaining, process_target.is_some());
+            shutdown.wait(poll_interval.min(remaining));
+            continue;
+        }
 
-            was_running = false;
+        if current == WatcherState::Idle {
+            refresh_baseline(&state, &mut idle_layout);
+        } else {
+            idle_layout = None;
         }
 
-        thread::sleep(Duration::from_secs(2));
+        // The loop still wakes every `poll_interval` for config, display and
+        // session changes, but only reads the process list after an event.
+        // Wake up in time for a pending foreground change to settle.
+        let poll_interval = foreground
+            .remaining(now, foreground_debounce)
+            .map_or(poll_interval, |remaining| poll_interval.min(remaining));
+        match ticks.wait(poll_interval) {
+            Tick::Stop => break,
+            Tick::Unchanged => targets_unchanged = true,
+            Tick::Poll => {}
+        }
     }
+
+    let (monitor_manager, restore_on_exit) = {
+        let state = lock(&state);
+        (state.monitor_manager.clone(), state.config.restore_on_exit)
+    };
+    let results = monitor_manager.run(move |manager| {
+        if restore_on_exit && manager.has_pending_restore() {
+            manager.restore_all_monitors()
+        } else {
+            Vec::new()
+        }
+    });
+    monitor_manager.finish_restore(results);
+    if restore_on_exit {
+        restore_audio(&state);
+        restore_power_scheme(&state);
+    }
+    // Suspended programs would otherwise stay frozen until reboot.
+    bring_back_set_aside(&state);
+    stats::end();
+}
+
+/// Keeps the cursor on the kept monitor while one of `pids` has the focus.
+/// Fullscreen apps are only seen while in front, so their processes are
+/// remembered until the target exits.
+fn update_cursor_clip(state: &Arc<Mutex<AppState>>, profile: &config::Profile, mut pids: Vec<u32>) {
+    let needs_monitor = lock(state).cursor.device_name.is_none();
+    let device_name = if needs_monitor {
+        let keep_enabled = profile.keep_enabled.clone();
+        let kept = with_manager(state, move |manager| manager.kept_monitor(keep_enabled.as_deref()));
+        kept.map(|monitor| monitor.device_name)
+    } else {
+        None
+    };
+
+    let mut state = lock(state);
+    if device_name.is_some() {
+        state.cursor.device_name = device_name;
+    }
+    if matches!(profile.trigger, Trigger::Fullscreen { .. }) {
+        pids.retain(|pid| !state.cursor.pids.contains(pid));
+        state.cursor.pids.append(&mut pids);
+    } else {
+        state.cursor.pids = pids;
+    }
+}
+
+/// How the status line names the profile's target at `idx`.
+fn target_label(profile: &config::Profile, idx: usize) -> String {
+    match &profile.trigger {
+        Trigger::Processes => {
+            let target = profile.targets.get(idx).map_or("", String::as_str);
+            Path::new(target)
+                .file_name()
+                .map_or_else(|| target.to_string(), |name| name.to_string_lossy().into_owned())
+        }
+        Trigger::Fullscreen { .. } => "fullscreen app".to_string(),
+        Trigger::WindowTitle(title) => format!("\"{}\" window", title),
+    }
+}
+
+/// The profile's target at `idx` as configured, for `{target}` in commands.
+fn target_value(profile: &config::Profile, idx: usize) -> String {
+    match &profile.trigger {
+        Trigger::Processes => profile.targets.get(idx).cloned().unwrap_or_default(),
+        Trigger::Fullscreen { .. } => "fullscreen".to_string(),
+        Trigger::WindowTitle(title) => title.clone(),
+    }
+}
+
+/// Status for a state change that didn't touch the displays.
+fn transition_status(previous: WatcherState, current: WatcherState) -> Option<&'static str> {
+    match (previous, current) {
+        (WatcherState::PendingDisable { .. }, WatcherState::Idle) => {
+            Some("Idle - process exited before monitors were disabled")
+        }
+        (WatcherState::PendingRestore { .. }, WatcherState::Active) => {
+            Some("Active - target restarted, monitors kept disabled")
+        }
+        _ => None,
+    }
+}
+
+/// `still_running` means a `require_foreground` target left the foreground
+/// rather than exiting.
+fn countdown_status(current: WatcherState, remaining: Duration, still_running: bool) -> String {
+    let secs = remaining.as_secs_f32().ceil() as u64;
+    // Holding after exit counts down minutes.
+    let left = if secs >= 60 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    };
+    match current {
+        WatcherState::PendingRestore { .. } if still_running => {
+            format!("Target in the background, restoring in {}...", left)
+        }
+        WatcherState::PendingRestore { .. } => format!("Target exited, restoring in {}...", left),
+        _ => format!("Process detected, disabling in {}...", left),
+    }
+}
+
+/// Re-snapshots the monitor settings whenever the layout changes while no
+/// target is running (a monitor plugged in, a resolution changed), so a later
+/// restore doesn't bring back a stale layout.
+fn refresh_baseline(state: &Arc<Mutex<AppState>>, last_layout: &mut Option<Vec<MonitorInfo>>) {
+    let previous = last_layout.take();
+    *last_layout = with_manager(state, move |manager| {
+        if manager.are_monitors_disabled() {
+            return previous;
+        }
+        let layout = manager.get_all_monitors();
+        if previous.as_ref() != Some(&layout) {
+            if previous.is_some() {
+                log::info!("Display layout changed while idle; saving a new baseline");
+            }
+            for monitor in &layout {
+                log::debug!(
+                    "{} ({}): {:?}, state flags {:#010x}",
+                    monitor.device_name,
+                    monitor.description,
+                    monitor.kind,
+                    monitor.state_flags
+                );
+            }
+            manager.save_current_settings();
+        }
+        Some(layout)
+    });
 }
@@ -0,0 +1,163 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, ThreadId};
+
+use crate::monitor::{self, DeviceResult};
+use crate::MonitorManager;
+
+/// A call waiting to run on the display thread.
+type Command = Box<dyn FnOnce(&mut MonitorManager) + Send>;
+
+/// The way to a [`MonitorManager`] that lives on a thread of its own. Every
+/// call is sent over a channel and run there in turn, so there is no lock to
+/// take in the wrong order: the caller only waits for its own call.
+#[derive(Clone)]
+pub struct ManagerHandle {
+    commands: Sender<Command>,
+    thread: ThreadId,
+}
+
+impl ManagerHandle {
+    /// Moves `manager` onto a new thread, which runs until every handle is dropped.
+    pub fn spawn(mut manager: MonitorManager) -> Self {
+        let (commands, received) = mpsc::channel::<Command>();
+        let thread = thread::Builder::new()
+            .name("display".to_string())
+            .spawn(move || {
+                for command in received {
+                    command(&mut manager);
+                }
+            })
+            .expect("could not start the display thread");
+        Self { commands, thread: thread.thread().id() }
+    }
+
+    /// Runs `f` on the manager and returns its result once it's done.
+    ///
+    /// Where panics unwind, in debug builds and tests, a panic in `f` is
+    /// passed on to the caller and the display thread carries on. Release
+    /// builds are built with `panic = "abort"`: there the app's panic hook
+    /// puts the displays back and the process ends.
+    ///
+    /// `f` must not wait on anything the caller may hold, such as a lock the
+    /// caller took before calling this.
+    pub fn run<R: Send + 'static>(&self, f: impl FnOnce(&mut MonitorManager) -> R + Send + 'static) -> R {
+        assert_ne!(thread::current().id(), self.thread, "ManagerHandle::run called from the display thread");
+        let (reply, result) = mpsc::sync_channel(1);
+        let command: Command = Box::new(move |manager| {
+            let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(|| f(manager))));
+        });
+        self.commands.send(command).expect("the display thread is gone");
+        match result.recv().expect("the display thread is gone") {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Carries on a restore that returned `results`: while monitors are left
+    /// that didn't come back, waits out `restore_retry_delay` on the calling
+    /// thread, so the display thread stays free for other calls, and runs
+    /// `retry_restore`. Returns `results` updated with the retries'.
+    pub fn finish_restore(&self, mut results: Vec<DeviceResult>) -> Vec<DeviceResult> {
+        while let Some(delay) = self.run(|manager| manager.restore_retry_delay()) {
+            thread::sleep(delay);
+            monitor::merge_results(&mut results, self.run(|manager| manager.retry_restore()));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::display_api::mock::MockDisplayApi;
+    use crate::monitor::SavedSettings;
+    use crate::WatcherState;
+
+    /// Stands in for the app's `AppState`: shared state that holds the handle.
+    struct State {
+        manager: ManagerHandle,
+        watcher: WatcherState,
+        actions: usize,
+    }
+
+    /// The app's `with_manager`: clones the handle, lets go of the state and
+    /// only then calls into the manager.
+    fn with_manager<R: Send + 'static>(
+        state: &Mutex<State>,
+        f: impl FnOnce(&mut MonitorManager) -> R + Send + 'static,
+    ) -> R {
+        let manager = { state.lock().unwrap().manager.clone() };
+        manager.run(f)
+    }
+
+    fn manager(dir: &Path) -> ManagerHandle {
+        let api = MockDisplayApi::default();
+        api.add_monitor(r"\\.\DISPLAY1", true, (0, 0));
+        api.add_monitor(r"\\.\DISPLAY2", false, (1920, 0));
+        api.add_monitor(r"\\.\DISPLAY3", false, (0, 1080));
+        ManagerHandle::spawn(MonitorManager::with_api(dir.to_path_buf(), SavedSettings::new(), Box::new(api)))
+    }
+
+    #[test]
+    fn tray_actions_during_watcher_transitions_never_deadlock() {
+        const ROUNDS: usize = 200;
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path());
+        let state = Arc::new(Mutex::new(State { manager, watcher: WatcherState::Idle, actions: 0 }));
+        let (done, finished) = mpsc::channel();
+
+        // The monitor loop going through disable and restore.
+        let watcher_state = Arc::clone(&state);
+        let watcher_done = done.clone();
+        thread::spawn(move || {
+            for _ in 0..ROUNDS {
+                with_manager(&watcher_state, |manager| manager.save_current_settings());
+                watcher_state.lock().unwrap().watcher = WatcherState::Active;
+                let results = with_manager(&watcher_state, |manager| manager.disable_secondary_monitors());
+                assert!(results.iter().all(|(_, result)| result.is_ok()), "{:?}", results);
+                watcher_state.lock().unwrap().watcher = WatcherState::Idle;
+                with_manager(&watcher_state, |manager| manager.restore_all_monitors());
+            }
+            watcher_done.send(()).unwrap();
+        });
+
+        // Tray menus and the About dialog reading the displays meanwhile.
+        for _ in 0..4 {
+            let tray_state = Arc::clone(&state);
+            let tray_done = done.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    with_manager(&tray_state, |manager| manager.are_monitors_disabled());
+                    let monitors = with_manager(&tray_state, |manager| manager.get_all_monitors());
+                    assert_eq!(monitors.len(), 3);
+                    tray_state.lock().unwrap().actions += 1;
+                }
+                tray_done.send(()).unwrap();
+            });
+        }
+        drop(done);
+
+        for _ in 0..5 {
+            finished
+                .recv_timeout(Duration::from_secs(60))
+                .expect("a thread deadlocked or panicked");
+        }
+        assert_eq!(state.lock().unwrap().actions, 4 * ROUNDS);
+        assert!(!with_manager(&state, |manager| manager.are_monitors_disabled()));
+    }
+
+    /// Only where panics unwind; see `run`.
+    #[test]
+    fn panic_in_a_call_reaches_the_caller_and_the_thread_carries_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path());
+        let caught = panic::catch_unwind(AssertUnwindSafe(|| manager.run(|_| panic!("boom"))));
+        assert!(caught.is_err());
+        assert_eq!(manager.run(|manager| manager.get_all_monitors().len()), 3);
+    }
+}
@@ -0,0 +1,393 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+
+use crate::config::MatchMode;
+
+const GLOB_PREFIX: &str = "glob:";
+const REGEX_PREFIX: &str = "regex:";
+
+/// A compiled `targets` entry.
+///
+/// Entries are plain paths by default. A `glob:` or `regex:` prefix selects
+/// the pattern kind explicitly; a bare entry containing `*` or `?` is treated
+/// as a glob since those characters can't appear in a Windows path.
+#[derive(Debug, Clone)]
+pub enum TargetPattern {
+    Literal(String),
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// A `targets` entry that isn't a valid glob or regex.
+#[derive(Debug, Clone)]
+pub struct PatternError {
+    pub target: String,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid target pattern '{}': {}", self.target, self.message)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl TargetPattern {
+    /// Parses one `targets` entry.
+    pub fn parse(target: &str) -> Result<Self, PatternError> {
+        let error = |message: String| PatternError {
+            target: target.to_string(),
+            message,
+        };
+
+        if let Some(pattern) = target.strip_prefix(REGEX_PREFIX) {
+            return RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(TargetPattern::Regex)
+                .map_err(|e| error(e.to_string()));
+        }
+
+        let glob = target
+            .strip_prefix(GLOB_PREFIX)
+            .or_else(|| target.contains(['*', '?']).then_some(target));
+        if let Some(pattern) = glob {
+            return Pattern::new(&normalize_path(pattern))
+                .map(TargetPattern::Glob)
+                .map_err(|e| error(e.msg.to_string()));
+        }
+
+        Ok(TargetPattern::Literal(target.to_string()))
+    }
+
+    /// In `FileName` mode patterns are evaluated against the file name only.
+    /// `exe_path` is `None` when the process path can't be read; only literal
+    /// targets can fall back to the process name then.
+    pub fn matches(&self, exe_path: Option<&Path>, process_name: &OsStr, mode: MatchMode) -> bool {
+        let Some(exe_path) = exe_path else {
+            return match self {
+                TargetPattern::Literal(target) => file_name_lower(Path::new(target))
+                    .is_some_and(|target_fn| process_name.to_string_lossy().to_lowercase() == target_fn),
+                _ => false,
+            };
+        };
+
+        let candidate = match mode {
+            MatchMode::FullPath => exe_path.to_string_lossy().into_owned(),
+            MatchMode::FileName => match exe_path.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => return false,
+            },
+        };
+
+        match self {
+            TargetPattern::Literal(target) => match mode {
+                MatchMode::FullPath => {
+                    normalize_path(target) == normalize_path(&candidate)
+                        || (may_be_same_file(target, &candidate)
+                            && paths_refer_to_same_file(Path::new(target), exe_path))
+                }
+                MatchMode::FileName => {
+                    file_name_lower(Path::new(target)).is_some_and(|target_fn| candidate.to_lowercase() == target_fn)
+                }
+            },
+            TargetPattern::Glob(pattern) => pattern.matches_with(&normalize_path(&candidate), glob_options()),
+            TargetPattern::Regex(regex) => regex.is_match(&candidate) || regex.is_match(&normalize_path(&candidate)),
+        }
+    }
+
+    /// Whether a process with this name could match, so its exe path is worth
+    /// reading. Assumes the process name is its exe's file name, which is
+    /// what Windows reports.
+    pub fn may_match_name(&self, process_name: &OsStr, mode: MatchMode) -> bool {
+        match (self, mode) {
+            (TargetPattern::Literal(target), _) => file_name_lower(Path::new(target))
+                .is_some_and(|target_fn| process_name.to_string_lossy().to_lowercase() == target_fn),
+            (_, MatchMode::FileName) => self.matches(Some(Path::new(process_name)), process_name, mode),
+            // A path pattern could match any file name.
+            (_, MatchMode::FullPath) => true,
+        }
+    }
+}
+
+/// A compiled window title trigger: a case-insensitive substring, or a
+/// regex with the `regex:` prefix.
+#[derive(Debug, Clone)]
+pub enum TitlePattern {
+    /// Stored lowercased.
+    Substring(String),
+    Regex(Regex),
+}
+
+impl TitlePattern {
+    pub fn parse(title: &str) -> Result<Self, PatternError> {
+        match title.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(TitlePattern::Regex)
+                .map_err(|e| PatternError {
+                    target: title.to_string(),
+                    message: e.to_string(),
+                }),
+            None => Ok(TitlePattern::Substring(title.to_lowercase())),
+        }
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        match self {
+            TitlePattern::Substring(text) => title.to_lowercase().contains(text.as_str()),
+            TitlePattern::Regex(regex) => regex.is_match(title),
+        }
+    }
+}
+
+/// Why a literal target probably won't match anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetWarning {
+    NotExe,
+    /// Only checked in `FullPath` mode, where the path must be exact.
+    NotFound,
+}
+
+impl fmt::Display for TargetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetWarning::NotExe => write!(f, "doesn't end in .exe"),
+            TargetWarning::NotFound => write!(f, "not found on disk"),
+        }
+    }
+}
+
+/// Trims whitespace and the quotes Explorer's "Copy as path" adds.
+pub fn clean_target_input(input: &str) -> String {
+    input.trim().trim_matches('"').trim().to_string()
+}
+
+/// Checks a literal target; glob and regex entries are never flagged. `exists`
+/// is asked about the path in `FullPath` mode.
+pub fn target_warning(target: &str, mode: MatchMode, exists: impl Fn(&Path) -> bool) -> Option<TargetWarning> {
+    if !matches!(TargetPattern::parse(target), Ok(TargetPattern::Literal(_))) {
+        return None;
+    }
+    if !target.to_lowercase().ends_with(".exe") {
+        return Some(TargetWarning::NotExe);
+    }
+    if mode == MatchMode::FullPath && !exists(Path::new(target)) {
+        return Some(TargetWarning::NotFound);
+    }
+    None
+}
+
+/// Parses every entry, failing on the first invalid pattern.
+pub fn compile_targets(targets: &[String]) -> Result<Vec<TargetPattern>, PatternError> {
+    targets.iter().map(|t| TargetPattern::parse(t)).collect()
+}
+
+/// Whether `a` and `b` name the same file. Both are resolved through
+/// `fs::canonicalize`, which on Windows opens the file and asks
+/// `GetFinalPathNameByHandleW`, so 8.3 short names, junctions and symlinks
+/// all come out as the real path. A path that can't be opened is only
+/// compared normalized.
+pub fn paths_refer_to_same_file(a: &Path, b: &Path) -> bool {
+    if normalize_path(&a.to_string_lossy()) == normalize_path(&b.to_string_lossy()) {
+        return true;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => normalize_path(&a.to_string_lossy()) == normalize_path(&b.to_string_lossy()),
+        _ => false,
+    }
+}
+
+/// Cheap check for whether two differing paths are worth opening to compare:
+/// either has an 8.3 short name in it, or they end in the same file name and
+/// could be one file reached through a junction or symlink. Keeps the poll
+/// from opening every running exe.
+fn may_be_same_file(target: &str, candidate: &str) -> bool {
+    let target_name = file_name_lower(Path::new(target));
+    let same_name = target_name.is_some() && target_name == file_name_lower(Path::new(candidate));
+    target.contains('~') || candidate.contains('~') || same_name
+}
+
+/// Lowercases, strips the `\\?\` prefix, uses `/` as the only separator and
+/// drops a trailing one.
+fn normalize_path(path: &str) -> String {
+    let path = path.strip_prefix(r"\\?\").unwrap_or(path);
+    let mut path = path.replace('\\', "/").to_lowercase();
+    while path.len() > 1 && path.ends_with('/') {
+        path.pop();
+    }
+    path
+}
+
+fn file_name_lower(path: &Path) -> Option<String> {
+    path.file_name().map(|f| f.to_string_lossy().to_lowercase())
+}
+
+fn glob_options() -> MatchOptions {
+    MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::GetShortPathNameW;
+
+    use super::*;
+
+    fn matches(target: &str, exe_path: &str, mode: MatchMode) -> bool {
+        let exe_path = Path::new(exe_path);
+        let pattern = TargetPattern::parse(target).unwrap();
+        pattern.matches(Some(exe_path), exe_path.file_name().unwrap(), mode)
+    }
+
+    #[test]
+    fn literal_full_path_ignores_case() {
+        assert!(matches(r"C:\Games\Hades\Hades.exe", r"c:\GAMES\hades\HADES.EXE", MatchMode::FullPath));
+        assert!(!matches(r"C:\Games\Hades\Hades.exe", r"C:\Games\Hades2\Hades.exe", MatchMode::FullPath));
+    }
+
+    #[test]
+    fn literal_full_path_normalizes_slashes() {
+        assert!(matches("C:/Games/Hades/Hades.exe", r"C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+        assert!(matches(r"C:\Games/Hades\Hades.exe", r"\\?\C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+    }
+
+    #[test]
+    fn literal_file_name_ignores_the_folder() {
+        assert!(matches(r"C:\Old Install\Hades.exe", r"D:\Games\Hades\hades.EXE", MatchMode::FileName));
+        assert!(matches("Hades.exe", r"D:\Games\Hades\Hades.exe", MatchMode::FileName));
+        assert!(!matches("Hades.exe", r"D:\Games\Hades\Hades2.exe", MatchMode::FileName));
+    }
+
+    #[test]
+    fn glob_ignores_case_and_separator_style() {
+        assert!(matches(r"C:\Games\*\*.exe", r"c:\games\Hades\Hades.exe", MatchMode::FullPath));
+        assert!(matches("glob:c:/GAMES/*/hades.exe", r"C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+        // `*` stays within one folder.
+        assert!(!matches(r"C:\Games\*.exe", r"C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+        assert!(matches("hades*.exe", r"C:\Games\Hades\Hades2.exe", MatchMode::FileName));
+    }
+
+    #[test]
+    fn regex_ignores_case_and_sees_both_separators() {
+        assert!(matches(r"regex:^hades\d?\.exe$", r"C:\Games\Hades\HADES2.EXE", MatchMode::FileName));
+        assert!(matches(r"regex:c:\\games\\", r"C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+        assert!(matches("regex:c:/games/", r"C:\Games\Hades\Hades.exe", MatchMode::FullPath));
+    }
+
+    #[test]
+    fn unreadable_exe_path_falls_back_to_the_name_for_literals_only() {
+        let name = OsStr::new("HADES.exe");
+        assert!(TargetPattern::parse(r"C:\Games\Hades\Hades.exe").unwrap().matches(None, name, MatchMode::FullPath));
+        assert!(!TargetPattern::parse(r"C:\Games\*\Hades.exe").unwrap().matches(None, name, MatchMode::FullPath));
+    }
+
+    #[test]
+    fn normalize_path_handles_case_separators_and_trailing_slashes() {
+        assert_eq!(normalize_path(r"\\?\C:\Games\Hades\"), "c:/games/hades");
+        assert_eq!(normalize_path("C:/Games\\Hades//"), "c:/games/hades");
+        assert_eq!(normalize_path("/"), "/");
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        assert!(TargetPattern::parse("regex:(").is_err());
+        assert!(TargetPattern::parse("glob:[").is_err());
+        assert!(compile_targets(&["Hades.exe".to_string(), "regex:(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn clean_target_input_strips_pasted_quotes_and_whitespace() {
+        assert_eq!(clean_target_input("  \"C:\\Games\\Hades\\Hades.exe\"\r\n"), r"C:\Games\Hades\Hades.exe");
+        assert_eq!(clean_target_input("\" Hades.exe \""), "Hades.exe");
+        assert_eq!(clean_target_input("Hades.exe"), "Hades.exe");
+        assert_eq!(clean_target_input("  "), "");
+    }
+
+    #[test]
+    fn target_warning_flags_non_exe_literals() {
+        let found = |_: &Path| true;
+        assert_eq!(target_warning(r"C:\Games\Hades\Hades", MatchMode::FullPath, found), Some(TargetWarning::NotExe));
+        assert_eq!(target_warning("Hades.lnk", MatchMode::FileName, found), Some(TargetWarning::NotExe));
+        assert_eq!(target_warning("HADES.EXE", MatchMode::FileName, found), None);
+    }
+
+    #[test]
+    fn target_warning_checks_the_disk_only_for_full_paths() {
+        let missing = |_: &Path| false;
+        let target = r"C:\Games\Hades\Hades.exe";
+        assert_eq!(target_warning(target, MatchMode::FullPath, missing), Some(TargetWarning::NotFound));
+        assert_eq!(target_warning(target, MatchMode::FileName, missing), None);
+        assert_eq!(target_warning(target, MatchMode::FullPath, |path: &Path| path == Path::new(target)), None);
+    }
+
+    #[test]
+    fn target_warning_leaves_patterns_alone() {
+        let missing = |_: &Path| false;
+        assert_eq!(target_warning(r"C:\Games\*\*", MatchMode::FullPath, missing), None);
+        assert_eq!(target_warning(r"regex:^hades\d?$", MatchMode::FileName, missing), None);
+    }
+
+    /// A real file in a folder with a name too long for 8.3, under `dir`.
+    fn long_named_exe(dir: &Path) -> PathBuf {
+        let dir = dir.join("Long Folder Name");
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("Long Game Name.exe");
+        fs::write(&exe, "").unwrap();
+        exe
+    }
+
+    /// The 8.3 form of `path`, or `None` if the volume doesn't make short names.
+    fn short_path(path: &Path) -> Option<PathBuf> {
+        let mut buffer = [0u16; 1024];
+        let len = unsafe { GetShortPathNameW(&HSTRING::from(path), Some(&mut buffer)) } as usize;
+        let short = PathBuf::from(String::from_utf16_lossy(buffer.get(..len)?));
+        (len > 0 && short.file_name() != path.file_name()).then_some(short)
+    }
+
+    #[test]
+    fn same_file_despite_trailing_slashes_and_separators() {
+        assert!(paths_refer_to_same_file(Path::new(r"C:\Games\Hades\"), Path::new("c:/games/hades")));
+        let mixed = (Path::new(r"C:\Games/Hades\Hades.exe"), Path::new(r"C:/Games\Hades/HADES.exe"));
+        assert!(paths_refer_to_same_file(mixed.0, mixed.1));
+        assert!(!paths_refer_to_same_file(Path::new(r"C:\Games\Hades\Hades.exe"), Path::new(r"C:\Games\Hades")));
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = long_named_exe(dir.path());
+        let folder = exe.parent().unwrap();
+        assert!(paths_refer_to_same_file(folder, Path::new(&format!("{}/", folder.display()))));
+        assert!(paths_refer_to_same_file(&exe, Path::new(&exe.to_string_lossy().replace('\\', "/"))));
+    }
+
+    /// Needs 8.3 names on the temp directory's volume, which many systems
+    /// turn off. Run with `cargo test -- --ignored` where they're on.
+    #[test]
+    #[ignore = "needs 8.3 short names on the temp volume"]
+    fn same_file_by_short_and_long_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = long_named_exe(dir.path());
+        let short = short_path(&exe).expect("8.3 names are off on the temp directory's volume");
+        assert!(paths_refer_to_same_file(&short, &exe));
+        assert!(paths_refer_to_same_file(&exe, &short));
+        let other = exe.with_file_name("Other Game.exe");
+        fs::write(&other, "").unwrap();
+        assert!(!paths_refer_to_same_file(&short, &other));
+
+        // A target typed with the short name matches the process's long path.
+        let pattern = TargetPattern::parse(&short.to_string_lossy()).unwrap();
+        assert!(pattern.matches(Some(&exe), exe.file_name().unwrap(), MatchMode::FullPath));
+    }
+}
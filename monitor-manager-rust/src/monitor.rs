@@ -1,188 +1,2341 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use windows::Win32::Foundation::RECT;
 use windows::Win32::Graphics::Gdi::*;
-use windows::core::PCWSTR;
 use std::mem;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::ccd::Topology;
+use crate::config::{self, DisplayBackend, Orientation, OrientationRule};
+use crate::display_api::{DisplayApi, WinApi};
+use crate::gamma::GammaRamp;
+use crate::ddc::DdcError;
+use crate::window_layout::{self, SavedWindow};
+
+const SAVED_MONITORS_FILE: &str = "saved_monitors.json";
+const SAVED_TOPOLOGY_FILE: &str = "saved_topology.json";
+const LAYOUTS_FILE: &str = "layouts.json";
+/// How many times a restore is applied before a monitor that didn't come back
+/// is reported as failed.
+const RESTORE_ATTEMPTS: u32 = 3;
+/// Wait before the first retry; doubled for each one after that.
+const RESTORE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Why a single display change didn't happen. The `DISP_CHANGE_*` codes come
+/// straight from `ChangeDisplaySettingsExW`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayChangeError {
+    UnknownDevice,
+    NotPhysical,
+    PrimaryMonitor,
+    Excluded,
+    NotActive,
+    UnsupportedRefreshRate { requested: u32, available: Vec<u32> },
+    UnsupportedResolution { width: u32, height: u32 },
+    AlreadyAtOrBelowResolution { width: u32, height: u32 },
+    RestartRequired,
+    Failed,
+    BadMode,
+    NotUpdated,
+    BadFlags,
+    BadParam,
+    BadDualView,
+    RolledBack,
+    NotVerified { attempts: u32 },
+    Ddc(DdcError),
+    Overlay(windows::core::Error),
+    Ccd(windows::core::Error),
+    NoHdr,
+    Hdr(windows::core::Error),
+    Other(i32),
+}
+
+impl DisplayChangeError {
+    /// Whether trying the same change again might work, as opposed to it
+    /// being refused for good (an unknown device, an unsupported mode).
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Failed
+                | Self::NotUpdated
+                | Self::RolledBack
+                | Self::NotVerified { .. }
+                | Self::Ddc(_)
+                | Self::Overlay(_)
+                | Self::Ccd(_)
+                | Self::Other(_)
+        )
+    }
+
+    /// The `DISP_CHANGE` value `ChangeDisplaySettingsExW` returned, for the
+    /// errors that came from it.
+    pub fn disp_change_code(&self) -> Option<i32> {
+        Some(match self {
+            Self::RestartRequired => DISP_CHANGE_RESTART.0,
+            Self::Failed => DISP_CHANGE_FAILED.0,
+            Self::BadMode => DISP_CHANGE_BADMODE.0,
+            Self::NotUpdated => DISP_CHANGE_NOTUPDATED.0,
+            Self::BadFlags => DISP_CHANGE_BADFLAGS.0,
+            Self::BadParam => DISP_CHANGE_BADPARAM.0,
+            Self::BadDualView => DISP_CHANGE_BADDUALVIEW.0,
+            Self::Other(code) => *code,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn check(result: DISP_CHANGE) -> Result<(), Self> {
+        Err(match result {
+            DISP_CHANGE_SUCCESSFUL => return Ok(()),
+            DISP_CHANGE_RESTART => Self::RestartRequired,
+            DISP_CHANGE_FAILED => Self::Failed,
+            DISP_CHANGE_BADMODE => Self::BadMode,
+            DISP_CHANGE_NOTUPDATED => Self::NotUpdated,
+            DISP_CHANGE_BADFLAGS => Self::BadFlags,
+            DISP_CHANGE_BADPARAM => Self::BadParam,
+            DISP_CHANGE_BADDUALVIEW => Self::BadDualView,
+            other => Self::Other(other.0),
+        })
+    }
+}
+
+impl fmt::Display for DisplayChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownDevice => write!(f, "unknown device"),
+            Self::NotPhysical => write!(f, "is a mirror or remote display driver"),
+            Self::PrimaryMonitor => write!(f, "is the primary monitor"),
+            Self::Excluded => write!(f, "is excluded in settings"),
+            Self::NotActive => write!(f, "is not active"),
+            Self::UnsupportedRefreshRate { requested, available } => {
+                let available: Vec<String> = available.iter().map(|hz| format!("{}Hz", hz)).collect();
+                write!(
+                    f,
+                    "{}Hz is not supported at the current resolution (available: {})",
+                    requested,
+                    available.join(", ")
+                )
+            }
+            Self::UnsupportedResolution { width, height } => {
+                write!(f, "{}x{} is not a supported resolution", width, height)
+            }
+            Self::AlreadyAtOrBelowResolution { width, height } => {
+                write!(f, "already at or below {}x{}, left unchanged", width, height)
+            }
+            Self::RestartRequired => write!(f, "a restart is required (DISP_CHANGE_RESTART)"),
+            Self::Failed => write!(f, "the display driver failed (DISP_CHANGE_FAILED)"),
+            Self::BadMode => write!(f, "the graphics mode is not supported (DISP_CHANGE_BADMODE)"),
+            Self::NotUpdated => write!(f, "could not write to the registry (DISP_CHANGE_NOTUPDATED)"),
+            Self::BadFlags => write!(f, "invalid flags (DISP_CHANGE_BADFLAGS)"),
+            Self::BadParam => write!(f, "invalid parameter (DISP_CHANGE_BADPARAM)"),
+            Self::BadDualView => write!(f, "the system is DualView capable (DISP_CHANGE_BADDUALVIEW)"),
+            Self::NotVerified { attempts } => write!(f, "restore failed after {} attempts", attempts),
+            Self::RolledBack => write!(f, "rolled back because another monitor in the same change failed"),
+            Self::Ddc(e) => write!(f, "{}", e),
+            Self::Overlay(e) => write!(f, "could not create the overlay ({})", e.message()),
+            Self::Ccd(e) => write!(f, "SetDisplayConfig failed ({})", e.message()),
+            Self::NoHdr => write!(f, "doesn't support HDR"),
+            Self::Hdr(e) => write!(f, "could not turn on HDR ({})", e.message()),
+            Self::Other(code) => write!(f, "unexpected result code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for DisplayChangeError {}
+
+/// Outcome of a change to one device, keyed by its device name.
+pub type DeviceResult = (String, Result<(), DisplayChangeError>);
+
+/// Formats the failures in `results` as `"\\.\DISPLAY2: reason; ..."`, or
+/// `None` if everything succeeded.
+pub fn describe_failures(results: &[DeviceResult]) -> Option<String> {
+    let failures: Vec<String> = results
+        .iter()
+        .filter_map(|(device, result)| result.as_ref().err().map(|e| format!("{}: {}", device, e)))
+        .collect();
+    (!failures.is_empty()).then(|| failures.join("; "))
+}
+
+/// Why a named layout couldn't be saved or applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    EmptyName,
+    NotFound(String),
+    NoActiveMonitors,
+    /// Monitors are changed for a target; applying a layout would fight the restore.
+    MonitorsChanged,
+    Io(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyName => write!(f, "the layout needs a name"),
+            Self::NotFound(name) => write!(f, "there is no layout named \"{}\"", name),
+            Self::NoActiveMonitors => write!(f, "no active monitors to save"),
+            Self::MonitorsChanged => write!(f, "monitors are changed for a target; try again once they're restored"),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// What kind of display device `EnumDisplayDevicesW` reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    /// A real display output.
+    Display,
+    /// A mirror driver (screen capture, some remote-control tools).
+    Mirror,
+    /// The virtual display of an RDP or Citrix session.
+    Remote,
+}
+
+impl DeviceKind {
+    fn from_state_flags(flags: u32) -> Self {
+        if flags & DISPLAY_DEVICE_MIRRORING_DRIVER != 0 {
+            Self::Mirror
+        } else if flags & DISPLAY_DEVICE_REMOTE != 0 {
+            Self::Remote
+        } else {
+            Self::Display
+        }
+    }
+}
+
+/// One display device as Windows reports it, attached to the desktop or not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct MonitorInfo {
     pub device_name: String,
     pub description: String,
+    /// Device interface path of the attached monitor (e.g.
+    /// `\\?\DISPLAY#DEL40F4#...`). Unlike `device_name` it survives the
+    /// `\\.\DISPLAYn` numbering changing after a reconnect or dock.
+    pub stable_id: Option<String>,
+    /// The monitor's own name, where the adapter reports one.
+    pub monitor_name: Option<String>,
     pub is_primary: bool,
     pub is_active: bool,
+    pub kind: DeviceKind,
+    /// Raw `DISPLAY_DEVICE_*` state flags, kept for the debug log.
+    pub state_flags: u32,
+    /// Current mode; `None` for monitors that aren't attached to the desktop.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub position_x: Option<i32>,
+    pub position_y: Option<i32>,
+    pub refresh_hz: Option<u32>,
+    /// File name of the monitor's default ICC profile; `None` if it has none
+    /// or is detached. This and the fields below are only read by
+    /// `get_all_monitors_detailed`.
+    pub color_profile: Option<String>,
+    /// Scaling in percent, e.g. 150; `None` if detached or it can't be read.
+    pub scale_percent: Option<u32>,
+    /// Whether the monitor can do HDR, and whether it's on.
+    pub hdr_supported: bool,
+    pub hdr_enabled: bool,
 }
 
+impl MonitorInfo {
+    /// The monitor's name if known, otherwise the adapter description.
+    pub fn display_name(&self) -> &str {
+        self.monitor_name.as_deref().unwrap_or(&self.description)
+    }
+
+    /// E.g. "DELL U2720Q — 3840x2160 @ 60Hz (primary)".
+    pub fn label(&self) -> String {
+        let role = if self.kind == DeviceKind::Mirror {
+            "mirror driver"
+        } else if self.kind == DeviceKind::Remote {
+            "remote display"
+        } else if self.is_primary {
+            "primary"
+        } else if self.is_active {
+            "secondary"
+        } else {
+            "disabled"
+        };
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => {
+                let refresh = self.refresh_hz.map(|hz| format!(" @ {}Hz", hz)).unwrap_or_default();
+                format!("{} — {}x{}{} ({})", self.display_name(), width, height, refresh, role)
+            }
+            _ => format!("{} ({})", self.display_name(), role),
+        }
+    }
+
+    /// Whether this is a real monitor the app may change. Mirror and remote
+    /// drivers are listed but never touched.
+    pub fn is_physical(&self) -> bool {
+        self.kind == DeviceKind::Display
+    }
+
+    /// Desktop rectangle the monitor occupies; `None` while it's detached.
+    pub fn rect(&self) -> Option<RECT> {
+        let (x, y) = (self.position_x?, self.position_y?);
+        Some(RECT {
+            left: x,
+            top: y,
+            right: x + self.width? as i32,
+            bottom: y + self.height? as i32,
+        })
+    }
+}
+
+/// The parts of a `DEVMODEW` needed to put a display back where it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub position_x: i32,
+    pub position_y: i32,
+    pub frequency: u32,
+    pub bits_per_pel: u32,
+    pub orientation: u32,
+}
+
+impl DisplayMode {
+    pub(crate) fn from_devmode(dev_mode: &DEVMODEW) -> Self {
+        let display = unsafe { dev_mode.Anonymous1.Anonymous2 };
+        Self {
+            width: dev_mode.dmPelsWidth,
+            height: dev_mode.dmPelsHeight,
+            position_x: display.dmPosition.x,
+            position_y: display.dmPosition.y,
+            frequency: dev_mode.dmDisplayFrequency,
+            bits_per_pel: dev_mode.dmBitsPerPel,
+            orientation: display.dmDisplayOrientation.0,
+        }
+    }
+
+    pub(crate) fn to_devmode(self) -> DEVMODEW {
+        let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+        dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_PELSWIDTH
+            | DM_PELSHEIGHT
+            | DM_POSITION
+            | DM_DISPLAYFREQUENCY
+            | DM_BITSPERPEL
+            | DM_DISPLAYORIENTATION;
+        dev_mode.dmPelsWidth = self.width;
+        dev_mode.dmPelsHeight = self.height;
+        dev_mode.dmDisplayFrequency = self.frequency;
+        dev_mode.dmBitsPerPel = self.bits_per_pel;
+        dev_mode.Anonymous1.Anonymous2.dmPosition.x = self.position_x;
+        dev_mode.Anonymous1.Anonymous2.dmPosition.y = self.position_y;
+        dev_mode.Anonymous1.Anonymous2.dmDisplayOrientation = DEVMODE_DISPLAY_ORIENTATION(self.orientation);
+        dev_mode
+    }
+}
+
+/// A saved mode plus the device name it was captured under. Snapshots are
+/// keyed by `MonitorInfo::key()` so the entry still finds its monitor when
+/// the `\\.\DISPLAYn` number has changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMonitor {
+    // Absent in snapshots written before stable IDs existed, where the map
+    // key is the device name.
+    #[serde(default)]
+    pub device_name: String,
+    #[serde(default)]
+    pub is_primary: bool,
+    #[serde(flatten)]
+    pub mode: DisplayMode,
+    /// Reloaded after a restore, since detaching a display resets it to linear.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<GammaRamp>,
+    /// Reassociated after a restore, since reattaching can drop it. `None`
+    /// when the monitor had no profile of its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_profile: Option<String>,
+    /// Reapplied after a restore, since Windows sometimes resets a reattached
+    /// monitor to 100%.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_percent: Option<u32>,
+    /// HDR on or off, for monitors that support it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdr_enabled: Option<bool>,
+}
+
+impl SavedMonitor {
+    /// Puts back what the display mode doesn't cover once the monitor is
+    /// attached again as `device_name`: HDR, scaling, color profile and gamma
+    /// ramp. Only a failure to set the scaling is returned; the others are
+    /// logged.
+    fn restore_extras(&self, api: &dyn DisplayApi, device_name: &str) -> windows::core::Result<()> {
+        if let Some(enabled) = self.hdr_enabled {
+            if let Err(e) = api.set_advanced_color(device_name, enabled) {
+                log::warn!("Could not turn HDR back {} on {}: {}", if enabled { "on" } else { "off" }, device_name, e.message());
+            }
+        }
+        if let Some(profile) = &self.color_profile {
+            api.associate_color_profile(device_name, profile);
+        }
+        if let Some(gamma) = &self.gamma {
+            api.apply_gamma(device_name, gamma);
+        }
+        match self.scale_percent {
+            Some(percent) => api.set_dpi_scale(device_name, percent),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Saved modes keyed by monitor key (`MonitorInfo::key`).
+pub type SavedSettings = HashMap<String, SavedMonitor>;
+
+/// One monitor's place in a named layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutMonitor {
+    /// `MonitorInfo::key()` when the layout was captured.
+    pub key: String,
+    pub device_name: String,
+    pub is_primary: bool,
+    #[serde(flatten)]
+    pub mode: DisplayMode,
+}
+
+/// Named layouts as stored in layouts.json, sorted by name.
+pub type Layouts = BTreeMap<String, Vec<LayoutMonitor>>;
+
+impl MonitorInfo {
+    /// The key this monitor's settings are saved under.
+    pub fn key(&self) -> &str {
+        self.stable_id.as_deref().unwrap_or(&self.device_name)
+    }
+
+    /// Whether `id` (a device name or stable ID from the config) names this monitor.
+    pub fn matches_id(&self, id: &str) -> bool {
+        self.device_name.eq_ignore_ascii_case(id)
+            || self.stable_id.as_deref().is_some_and(|stable| stable.eq_ignore_ascii_case(id))
+    }
+}
+
+/// Finds the current device name for a saved entry: the monitor with the
+/// same stable ID if it's connected, otherwise the name it was saved under.
+pub fn resolve_device_name(key: &str, saved: &SavedMonitor, monitors: &[MonitorInfo]) -> String {
+    monitors
+        .iter()
+        .find(|m| m.stable_id.as_deref() == Some(key))
+        .or_else(|| monitors.iter().find(|m| m.device_name == key))
+        .map(|m| m.device_name.clone())
+        .unwrap_or_else(|| {
+            if saved.device_name.is_empty() {
+                key.to_string()
+            } else {
+                saved.device_name.clone()
+            }
+        })
+}
+
+/// Whether the monitor a saved entry refers to is still connected. An entry
+/// keyed by a stable ID whose old device name now belongs to a different
+/// monitor is not.
+fn is_saved_monitor_present(key: &str, saved: &SavedMonitor, monitors: &[MonitorInfo]) -> bool {
+    let device_name = resolve_device_name(key, saved, monitors);
+    monitors
+        .iter()
+        .find(|m| m.device_name == device_name)
+        .is_some_and(|m| match &m.stable_id {
+            Some(stable) if key.starts_with(r"\\?\") => stable.eq_ignore_ascii_case(key),
+            _ => true,
+        })
+}
+
+/// Sort key for restoring a layout: the primary first, then outward from it
+/// by saved position, so each monitor is placed next to one already there
+/// and Windows has no reason to shove it aside to avoid an overlap.
+fn restore_order(change: &DisplayChange) -> (bool, i64, i32, i32) {
+    let mode = DisplayMode::from_devmode(&change.dev_mode);
+    let distance = i64::from(mode.position_x).abs() + i64::from(mode.position_y).abs();
+    (change.flags != CDS_SET_PRIMARY, distance, mode.position_x, mode.position_y)
+}
+
+/// Folds the results of a retry into an earlier attempt's, a device's newer
+/// result replacing its older one.
+pub fn merge_results(results: &mut Vec<DeviceResult>, retried: Vec<DeviceResult>) {
+    for (device_name, result) in retried {
+        match results.iter_mut().find(|(name, _)| *name == device_name) {
+            Some(earlier) => earlier.1 = result,
+            None => results.push((device_name, result)),
+        }
+    }
+}
+
+/// A 0x0 mode, which detaches the device from the desktop.
+fn detach_mode() -> DEVMODEW {
+    let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+    dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+    dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_POSITION;
+    dev_mode
+}
+
+/// A new mode for one device, staged and committed by `apply_batch`.
+#[derive(Clone)]
+pub struct DisplayChange {
+    pub device_name: String,
+    pub dev_mode: DEVMODEW,
+    pub flags: CDS_TYPE,
+}
+
+/// Why `apply_batch` committed nothing.
+#[derive(Debug, Clone)]
+pub enum BatchError {
+    /// `device_name` couldn't be staged; changes staged before it were rolled back.
+    Stage { device_name: String, error: DisplayChangeError },
+    /// Everything was staged but the final commit failed.
+    Commit(DisplayChangeError),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stage { device_name, error } => write!(f, "{}: {}", device_name, error),
+            Self::Commit(error) => write!(f, "commit failed: {}", error),
+        }
+    }
+}
+
+impl BatchError {
+    /// The outcome for each change in the failed batch.
+    fn device_results(&self, changes: &[DisplayChange]) -> Vec<DeviceResult> {
+        changes
+            .iter()
+            .map(|change| {
+                let error = match self {
+                    Self::Stage { device_name, error } if *device_name == change.device_name => error.clone(),
+                    Self::Stage { .. } => DisplayChangeError::RolledBack,
+                    Self::Commit(error) => error.clone(),
+                };
+                (change.device_name.clone(), Err(error))
+            })
+            .collect()
+    }
+}
+
+/// A planned change per device: `Ok(None)` when the device is already where
+/// it should be.
+type PlannedChange = (String, Result<Option<DisplayChange>, DisplayChangeError>);
+
+/// Changes displays for a target and puts them back afterwards, keeping a
+/// snapshot on disk so a crash mid-session can be recovered from.
 pub struct MonitorManager {
-    saved_settings: HashMap<String, DEVMODEW>,
+    api: Box<dyn DisplayApi>,
+    saved_settings: SavedSettings,
+    /// Keys of snapshot entries recorded while monitors were disabled that
+    /// haven't been restored yet: left by a failed restore, or loaded from a
+    /// previous run's snapshot. Only these survive a re-save.
+    unrestored: Vec<String>,
     monitors_disabled: bool,
+    /// Monitors switched off over DDC/CI; they stay attached, so the saved
+    /// modes alone won't bring them back.
+    ddc_powered_off: Vec<String>,
+    /// Dropping it closes the overlays.
+    overlays: Option<Box<dyn Send>>,
+    saved_windows: Vec<SavedWindow>,
+    backend: DisplayBackend,
+    /// Whether changes are written to the registry. When they are, a reboot
+    /// mid-session comes back with monitors still disabled, and the on-disk
+    /// snapshot (`saved_monitors.json`) is what brings them back on the next
+    /// start. When they aren't, a reboot alone restores the old layout and the
+    /// snapshot finds nothing detached to fix.
+    persist_changes: bool,
+    /// Topology from before the change, kept when the CCD backend is in use.
+    saved_topology: Option<Topology>,
+    baseline_captured_at: Option<DateTime<Local>>,
+    /// Keys of monitors turned off from the tray's Monitors submenu.
+    manually_disabled: Vec<String>,
+    /// `Config::managed_monitors`; `None` means every secondary monitor.
+    managed_monitors: Option<Vec<String>>,
+    /// `Config::excluded_monitors`.
+    excluded_monitors: Vec<String>,
+    /// Keys of monitors rotated by an orientation rule, which the strategy
+    /// leaves alone until they're restored.
+    rotated: Vec<String>,
+    /// Whether a scaling failure has been reported; it usually means this
+    /// Windows build doesn't allow setting it, so it's only reported once.
+    scale_warned: bool,
+    scale_warning: Option<String>,
+    /// Keys of snapshot entries whose last restore reported success but
+    /// didn't take, for `retry_restore`.
+    unverified: Vec<String>,
+    /// Restore attempts so far for the entries in `unverified`.
+    restore_attempts: u32,
+    /// Where the snapshot, topology, window positions and layouts are kept.
+    data_dir: PathBuf,
 }
 
 impl MonitorManager {
-    pub fn new() -> Self {
-        Self {
-            saved_settings: HashMap::new(),
+    /// `saved_settings` is a snapshot left by a previous run (see
+    /// `load_saved_settings_from_disk`). If any of its monitors are currently
+    /// detached the manager starts out in the disabled state, so
+    /// `restore_all_monitors` brings them back and `save_current_settings`
+    /// won't overwrite the snapshot first.
+    pub fn new(saved_settings: SavedSettings) -> Self {
+        Self::with_api(config::config_dir(), saved_settings, Box::new(WinApi))
+    }
+
+    /// Like `new`, but keeps its files in `data_dir` instead of `config_dir()`
+    /// and makes every enumeration and mode change through `api`.
+    pub fn with_api(data_dir: PathBuf, saved_settings: SavedSettings, api: Box<dyn DisplayApi>) -> Self {
+        let mut manager = Self {
+            api,
+            saved_settings,
+            unrestored: Vec::new(),
             monitors_disabled: false,
+            ddc_powered_off: Vec::new(),
+            overlays: None,
+            saved_windows: window_layout::load_from_disk(&data_dir),
+            backend: DisplayBackend::default(),
+            persist_changes: true,
+            saved_topology: Self::load_saved_topology_from_disk(&data_dir),
+            baseline_captured_at: None,
+            manually_disabled: Vec::new(),
+            managed_monitors: None,
+            excluded_monitors: Vec::new(),
+            rotated: Vec::new(),
+            scale_warned: false,
+            scale_warning: None,
+            unverified: Vec::new(),
+            restore_attempts: 0,
+            data_dir,
+        };
+        manager.unrestored = manager.detached_saved_keys();
+        manager.monitors_disabled = !manager.unrestored.is_empty();
+        manager
+    }
+
+    /// Keys of snapshot entries whose monitor is currently detached.
+    fn detached_saved_keys(&self) -> Vec<String> {
+        if self.saved_settings.is_empty() {
+            return Vec::new();
         }
+        let monitors = self.get_all_monitors();
+        self.saved_settings
+            .iter()
+            .filter(|(key, saved)| {
+                let device_name = resolve_device_name(key, saved, &monitors);
+                monitors.iter().any(|m| !m.is_active && m.device_name == device_name)
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
     }
 
+    /// Whether some monitor is currently changed for a target or by hand.
     pub fn are_monitors_disabled(&self) -> bool {
         self.monitors_disabled
     }
 
+    /// Whether anything still needs restoring before exit, counting monitors
+    /// a restore failed on, which `are_monitors_disabled` no longer reports.
+    pub fn has_pending_restore(&self) -> bool {
+        self.monitors_disabled || !self.unrestored.is_empty()
+    }
+
+    /// Keys of snapshot entries whose monitor isn't connected any more, for
+    /// instance after the displays were re-enumerated on resume.
+    pub fn missing_saved_monitors(&self) -> Vec<String> {
+        let monitors = self.get_all_monitors();
+        self.saved_settings
+            .iter()
+            .filter(|(key, saved)| !is_saved_monitor_present(key, saved, &monitors))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// When `save_current_settings` last took a snapshot this session.
+    pub fn baseline_captured_at(&self) -> Option<DateTime<Local>> {
+        self.baseline_captured_at
+    }
+
+    /// Selects the API used to detach monitors (`Config::display_backend`).
+    pub fn set_backend(&mut self, backend: DisplayBackend) {
+        self.backend = backend;
+    }
+
+    /// See `Config::persist_changes`.
+    pub fn set_persist_changes(&mut self, persist: bool) {
+        self.persist_changes = persist;
+    }
+
+    /// Limits the secondary-monitor passes to these monitor keys; see
+    /// `Config::managed_monitors`.
+    pub fn set_managed_monitors(&mut self, managed: Option<Vec<String>>) {
+        self.managed_monitors = managed;
+    }
+
+    /// Monitors that are never changed; see `Config::excluded_monitors`.
+    pub fn set_excluded_monitors(&mut self, excluded: Vec<String>) {
+        self.excluded_monitors = excluded;
+    }
+
+    /// Whether this monitor must be left alone whatever runs.
+    pub fn is_excluded(&self, monitor: &MonitorInfo) -> bool {
+        self.excluded_monitors.iter().any(|id| monitor.matches_id(id))
+    }
+
+    /// `device_names` without the excluded monitors.
+    pub fn without_excluded(&self, mut device_names: Vec<String>) -> Vec<String> {
+        let monitors = self.get_all_monitors();
+        device_names.retain(|device_name| {
+            !monitors
+                .iter()
+                .any(|m| m.device_name.eq_ignore_ascii_case(device_name) && self.is_excluded(m))
+        });
+        device_names
+    }
+
+    /// Whether the secondary-monitor passes may turn this monitor off.
+    fn is_managed(&self, monitor: &MonitorInfo) -> bool {
+        self.managed_monitors
+            .as_ref()
+            .is_none_or(|keys| keys.iter().any(|key| monitor.matches_id(key)))
+            && !self.rotated.iter().any(|key| monitor.matches_id(key))
+            && !self.is_excluded(monitor)
+    }
+
+    /// Records the current mode of every active monitor as the baseline to
+    /// restore. Does nothing while monitors are disabled.
     pub fn save_current_settings(&mut self) {
         if self.monitors_disabled {
             return;
         }
-        self.saved_settings.clear();
-        let monitors = self.get_all_monitors();
-        for monitor in monitors {
-            if monitor.is_active {
+        let monitors = self.get_all_monitors_detailed();
+        // An unrestored entry whose monitor is still detached is the only
+        // record of how to bring it back, so it survives a re-save. Monitors
+        // turned off outside the app are none of its business.
+        let unrestored = &self.unrestored;
+        self.saved_settings.retain(|key, saved| {
+            let device_name = resolve_device_name(key, saved, &monitors);
+            unrestored.contains(key) && monitors.iter().any(|m| !m.is_active && m.device_name == device_name)
+        });
+        self.unrestored.retain(|key| self.saved_settings.contains_key(key));
+        self.unverified.clear();
+        for monitor in &monitors {
+            // Excluded monitors are never changed, so there's nothing to restore.
+            if monitor.is_active && monitor.is_physical() && !self.is_excluded(monitor) {
                 if let Some(settings) = self.get_monitor_settings(&monitor.device_name) {
-                    self.saved_settings.insert(monitor.device_name.clone(), settings);
+                    self.saved_settings.insert(
+                        monitor.key().to_string(),
+                        SavedMonitor {
+                            device_name: monitor.device_name.clone(),
+                            is_primary: monitor.is_primary,
+                            mode: DisplayMode::from_devmode(&settings),
+                            gamma: self.api.capture_gamma(&monitor.device_name),
+                            color_profile: monitor.color_profile.clone(),
+                            scale_percent: monitor.scale_percent,
+                            hdr_enabled: monitor.hdr_supported.then_some(monitor.hdr_enabled),
+                        },
+                    );
                 }
             }
         }
+        self.save_settings_to_disk();
+        if self.backend == DisplayBackend::Ccd {
+            match self.api.query_topology() {
+                Ok(topology) => {
+                    self.saved_topology = Some(topology);
+                    self.save_topology_to_disk();
+                }
+                Err(e) => log::warn!("Could not query the display topology: {}", e.message()),
+            }
+        }
+        self.saved_windows = self.api.capture_windows(&monitors);
+        window_layout::save_to_disk(&self.data_dir, &self.saved_windows);
+        self.baseline_captured_at = Some(Local::now());
     }
 
-    pub fn get_all_monitors(&self) -> Vec<MonitorInfo> {
-        let mut monitors = Vec::new();
-        let mut i = 0u32;
-
-        loop {
-            let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
-            display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as u32;
-
-            unsafe {
-                if EnumDisplayDevicesW(PCWSTR::null(), i, &mut display_device, 0).as_bool() {
-                    let is_active = (display_device.StateFlags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP) != 0;
-                    let is_primary = (display_device.StateFlags & DISPLAY_DEVICE_PRIMARY_DEVICE) != 0;
-
-                    let device_name = String::from_utf16_lossy(
-                        &display_device.DeviceName[..display_device.DeviceName.iter()
-                            .position(|&c| c == 0)
-                            .unwrap_or(display_device.DeviceName.len())]
-                    );
+    /// Where the snapshot is kept between runs, in the data directory `dir`.
+    pub fn saved_settings_path(dir: &Path) -> PathBuf {
+        dir.join(SAVED_MONITORS_FILE)
+    }
 
-                    let description = String::from_utf16_lossy(
-                        &display_device.DeviceString[..display_device.DeviceString.iter()
-                            .position(|&c| c == 0)
-                            .unwrap_or(display_device.DeviceString.len())]
-                    );
+    /// Persists the snapshot so monitors can be restored after a crash.
+    fn save_settings_to_disk(&self) {
+        let path = Self::saved_settings_path(&self.data_dir);
+        let result = serde_json::to_string_pretty(&self.saved_settings)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                fs::write(&path, content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Could not write {}: {}", path.display(), e);
+        }
+    }
 
-                    monitors.push(MonitorInfo {
-                        device_name,
-                        description,
-                        is_primary,
-                        is_active,
-                    });
+    /// Loads the snapshot a previous run left in `dir`, if any.
+    pub fn load_saved_settings_from_disk(dir: &Path) -> SavedSettings {
+        let path = Self::saved_settings_path(dir);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return HashMap::new();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+            HashMap::new()
+        })
+    }
 
-                    i += 1;
-                } else {
-                    break;
+    fn remove_saved_settings_file(&self) {
+        let path = Self::saved_settings_path(&self.data_dir);
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Could not remove {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn saved_topology_path(dir: &Path) -> PathBuf {
+        dir.join(SAVED_TOPOLOGY_FILE)
+    }
+
+    fn save_topology_to_disk(&self) {
+        let path = Self::saved_topology_path(&self.data_dir);
+        let result = serde_json::to_string_pretty(&self.saved_topology)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
                 }
+                fs::write(&path, content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Could not write {}: {}", path.display(), e);
+        }
+    }
+
+    fn load_saved_topology_from_disk(dir: &Path) -> Option<Topology> {
+        let path = Self::saved_topology_path(dir);
+        let content = fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+            None
+        })
+    }
+
+    fn remove_saved_topology_file(&self) {
+        let path = Self::saved_topology_path(&self.data_dir);
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Could not remove {}: {}", path.display(), e);
             }
         }
+    }
+
+    /// Every display device, including ones that are detached. Only the
+    /// cheap display calls are made: the color profile, scaling and HDR
+    /// fields are left empty.
+    pub fn get_all_monitors(&self) -> Vec<MonitorInfo> {
+        self.api
+            .enumerate_devices()
+            .into_iter()
+            .map(|device| {
+                let is_active = (device.state_flags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP) != 0;
+                let is_primary = (device.state_flags & DISPLAY_DEVICE_PRIMARY_DEVICE) != 0;
+                let mode = if is_active {
+                    self.get_monitor_settings(&device.device_name)
+                        .map(|dev_mode| DisplayMode::from_devmode(&dev_mode))
+                } else {
+                    None
+                };
+                MonitorInfo {
+                    device_name: device.device_name,
+                    description: device.description,
+                    stable_id: device.stable_id,
+                    monitor_name: device.monitor_name,
+                    is_primary,
+                    is_active,
+                    kind: DeviceKind::from_state_flags(device.state_flags),
+                    state_flags: device.state_flags,
+                    width: mode.map(|m| m.width),
+                    height: mode.map(|m| m.height),
+                    position_x: mode.map(|m| m.position_x),
+                    position_y: mode.map(|m| m.position_y),
+                    refresh_hz: mode.map(|m| m.frequency),
+                    color_profile: None,
+                    scale_percent: None,
+                    hdr_supported: false,
+                    hdr_enabled: false,
+                }
+            })
+            .collect()
+    }
 
+    /// Like `get_all_monitors`, with the color profile, scaling and HDR
+    /// state of the active monitors filled in, for saving and for showing.
+    pub fn get_all_monitors_detailed(&self) -> Vec<MonitorInfo> {
+        let mut monitors = self.get_all_monitors();
+        self.fill_extras(&mut monitors);
         monitors
     }
 
+    fn fill_extras(&self, monitors: &mut [MonitorInfo]) {
+        let active: Vec<String> = monitors.iter().filter(|m| m.is_active).map(|m| m.device_name.clone()).collect();
+        let mut extras = self.api.device_extras(&active).into_iter();
+        for monitor in monitors.iter_mut().filter(|m| m.is_active) {
+            let Some(extras) = extras.next() else {
+                break;
+            };
+            monitor.color_profile = extras.color_profile;
+            monitor.scale_percent = extras.scale_percent;
+            monitor.hdr_supported = extras.hdr_supported;
+            monitor.hdr_enabled = extras.hdr_enabled;
+        }
+    }
+
     fn get_monitor_settings(&self, device_name: &str) -> Option<DEVMODEW> {
+        self.api.get_settings(device_name, ENUM_CURRENT_SETTINGS)
+    }
+
+    /// Every mode the driver reports for the device.
+    pub fn available_modes(&self, device_name: &str) -> Vec<DisplayMode> {
+        let mut modes = Vec::new();
+        let mut index = 0u32;
+        while let Some(dev_mode) = self.api.get_settings(device_name, ENUM_DISPLAY_SETTINGS_MODE(index)) {
+            let mode = DisplayMode::from_devmode(&dev_mode);
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+            index += 1;
+        }
+        modes
+    }
+
+    /// Switches an active monitor to `hz`, keeping its resolution and color
+    /// depth. Rates the driver doesn't list for that resolution are rejected
+    /// rather than applied.
+    pub fn set_refresh_rate(&mut self, device_name: &str, hz: u32) -> Result<(), DisplayChangeError> {
+        let (_, result) = self.set_refresh_rates(&[device_name.to_string()], hz).remove(0);
+        result
+    }
+
+    /// Applies `hz` to each device in one topology change.
+    pub fn set_refresh_rates(&mut self, device_names: &[String], hz: u32) -> Vec<DeviceResult> {
+        let planned = device_names
+            .iter()
+            .map(|device_name| (device_name.clone(), self.plan_refresh_rate(device_name, hz)))
+            .collect();
+        let results = self.apply_planned(planned);
+        if results.iter().any(|(_, r)| r.is_ok()) {
+            self.monitors_disabled = true;
+        }
+        results
+    }
+
+    fn plan_refresh_rate(&self, device_name: &str, hz: u32) -> Result<Option<DisplayChange>, DisplayChangeError> {
+        let current = self
+            .get_monitor_settings(device_name)
+            .ok_or(DisplayChangeError::NotActive)?;
+        let current_mode = DisplayMode::from_devmode(&current);
+        if current_mode.frequency == hz {
+            return Ok(None);
+        }
+
+        let mut available: Vec<u32> = self
+            .available_modes(device_name)
+            .into_iter()
+            .filter(|m| {
+                m.width == current_mode.width
+                    && m.height == current_mode.height
+                    && m.bits_per_pel == current_mode.bits_per_pel
+            })
+            .map(|m| m.frequency)
+            .collect();
+        available.sort_unstable_by(|a, b| b.cmp(a));
+        available.dedup();
+        if !available.contains(&hz) {
+            return Err(DisplayChangeError::UnsupportedRefreshRate { requested: hz, available });
+        }
+
         let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
         dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_DISPLAYFREQUENCY;
+        dev_mode.dmDisplayFrequency = hz;
+        Ok(Some(DisplayChange {
+            device_name: device_name.to_string(),
+            dev_mode,
+            flags: CDS_TYPE(0),
+        }))
+    }
 
-        let name_wide = Self::device_name_wide(device_name);
+    /// Rotates an active monitor, swapping its width and height when it turns
+    /// between landscape and portrait.
+    pub fn set_orientation(&mut self, device_name: &str, orientation: Orientation) -> Result<(), DisplayChangeError> {
+        let planned = vec![(device_name.to_string(), self.plan_orientation(device_name, orientation))];
+        let (_, result) = self.apply_planned(planned).remove(0);
+        if result.is_ok() {
+            self.monitors_disabled = true;
+        }
+        result
+    }
 
-        unsafe {
-            if EnumDisplaySettingsW(
-                PCWSTR(name_wide.as_ptr()),
-                ENUM_CURRENT_SETTINGS,
-                &mut dev_mode,
-            ).as_bool() {
-                Some(dev_mode)
-            } else {
-                None
+    /// Applies a profile's orientation rules in one topology change. Rotated
+    /// monitors are left out of the secondary-monitor passes until restored.
+    pub fn apply_orientation_rules(&mut self, rules: &[OrientationRule]) -> Vec<DeviceResult> {
+        let monitors = self.get_all_monitors();
+        let planned = rules
+            .iter()
+            .map(|rule| match monitors.iter().find(|m| m.is_active && m.matches_id(&rule.monitor)) {
+                Some(monitor) => {
+                    let plan = self.plan_orientation(&monitor.device_name, rule.orientation);
+                    (monitor.device_name.clone(), plan)
+                }
+                None => (rule.monitor.clone(), Err(DisplayChangeError::NotActive)),
+            })
+            .collect();
+        let results = self.apply_planned(planned);
+        for (device_name, result) in &results {
+            let Some(monitor) = monitors.iter().find(|m| &m.device_name == device_name) else {
+                continue;
+            };
+            if result.is_ok() && !self.rotated.iter().any(|key| key == monitor.key()) {
+                self.rotated.push(monitor.key().to_string());
             }
         }
+        if results.iter().any(|(_, r)| r.is_ok()) {
+            self.monitors_disabled = true;
+        }
+        results
+    }
+
+    fn plan_orientation(&self, device_name: &str, orientation: Orientation) -> Result<Option<DisplayChange>, DisplayChangeError> {
+        let current = self
+            .get_monitor_settings(device_name)
+            .ok_or(DisplayChangeError::NotActive)?;
+        let current_mode = DisplayMode::from_devmode(&current);
+        let current_orientation = Orientation::from_dmdo(current_mode.orientation);
+        if current_orientation == orientation {
+            return Ok(None);
+        }
+
+        let (width, height) = if current_orientation.is_portrait() == orientation.is_portrait() {
+            (current_mode.width, current_mode.height)
+        } else {
+            (current_mode.height, current_mode.width)
+        };
+        let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+        dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYORIENTATION;
+        dev_mode.dmPelsWidth = width;
+        dev_mode.dmPelsHeight = height;
+        dev_mode.Anonymous1.Anonymous2.dmDisplayOrientation = DEVMODE_DISPLAY_ORIENTATION(orientation.dmdo());
+        Ok(Some(DisplayChange {
+            device_name: device_name.to_string(),
+            dev_mode,
+            flags: CDS_TYPE(0),
+        }))
+    }
+
+    /// Distinct resolutions the device supports, largest first.
+    pub fn available_resolutions(&self, device_name: &str) -> Vec<(u32, u32)> {
+        let mut resolutions: Vec<(u32, u32)> = self
+            .available_modes(device_name)
+            .into_iter()
+            .map(|m| (m.width, m.height))
+            .collect();
+        resolutions.sort_unstable_by_key(|&(width, height)| std::cmp::Reverse((width * height, width)));
+        resolutions.dedup();
+        resolutions
     }
 
-    fn device_name_wide(device_name: &str) -> Vec<u16> {
-        device_name.encode_utf16().chain(Some(0)).collect()
+    /// Switches an active monitor to `width`x`height`. Monitors already at or
+    /// below that size are left alone.
+    pub fn set_resolution(&mut self, device_name: &str, width: u32, height: u32) -> Result<(), DisplayChangeError> {
+        let (_, result) = self.set_resolutions(&[device_name.to_string()], width, height).remove(0);
+        result
     }
 
-    fn apply_staged_changes() {
-        unsafe {
-            let _ = ChangeDisplaySettingsExW(PCWSTR::null(), None, None, CDS_TYPE(0), None);
+    /// Applies `width`x`height` to each device in one topology change.
+    pub fn set_resolutions(&mut self, device_names: &[String], width: u32, height: u32) -> Vec<DeviceResult> {
+        let planned = device_names
+            .iter()
+            .map(|device_name| (device_name.clone(), self.plan_resolution(device_name, width, height)))
+            .collect();
+        let results = self.apply_planned(planned);
+        if results.iter().any(|(_, r)| r.is_ok()) {
+            self.monitors_disabled = true;
         }
+        results
     }
 
-    pub fn disable_secondary_monitors(&mut self) -> usize {
+    fn plan_resolution(&self, device_name: &str, width: u32, height: u32) -> Result<Option<DisplayChange>, DisplayChangeError> {
+        let current = self
+            .get_monitor_settings(device_name)
+            .ok_or(DisplayChangeError::NotActive)?;
+        let current_mode = DisplayMode::from_devmode(&current);
+        if current_mode.width <= width && current_mode.height <= height {
+            return Err(DisplayChangeError::AlreadyAtOrBelowResolution { width, height });
+        }
+
+        // Prefer keeping the current refresh rate, otherwise take the fastest one.
+        let candidates: Vec<DisplayMode> = self
+            .available_modes(device_name)
+            .into_iter()
+            .filter(|m| m.width == width && m.height == height && m.bits_per_pel == current_mode.bits_per_pel)
+            .collect();
+        let target = candidates
+            .iter()
+            .find(|m| m.frequency == current_mode.frequency)
+            .or_else(|| candidates.iter().max_by_key(|m| m.frequency))
+            .ok_or(DisplayChangeError::UnsupportedResolution { width, height })?;
+
+        let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+        dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY;
+        dev_mode.dmPelsWidth = target.width;
+        dev_mode.dmPelsHeight = target.height;
+        dev_mode.dmDisplayFrequency = target.frequency;
+        Ok(Some(DisplayChange {
+            device_name: device_name.to_string(),
+            dev_mode,
+            flags: CDS_TYPE(0),
+        }))
+    }
+
+    /// Active monitors other than the one that stays untouched: `keep` if it
+    /// is connected, otherwise the primary.
+    pub fn secondary_device_names(&self, keep: Option<&str>) -> Vec<String> {
         let monitors = self.get_all_monitors();
-        let stage_flags = CDS_TYPE(CDS_UPDATEREGISTRY.0 | CDS_NORESET.0);
-        let mut count = 0;
+        let kept = keep.and_then(|keep| monitors.iter().find(|m| m.is_active && m.is_physical() && m.matches_id(keep)));
+        monitors
+            .iter()
+            .filter(|m| m.is_active && m.is_physical() && self.is_managed(m))
+            .filter(|m| match kept {
+                Some(kept) => m.device_name != kept.device_name,
+                None => !m.is_primary,
+            })
+            .map(|m| m.device_name.clone())
+            .collect()
+    }
 
-        for monitor in &monitors {
-            if monitor.is_primary || !monitor.is_active {
+    /// Stages every change with `CDS_UPDATEREGISTRY | CDS_NORESET` and commits
+    /// them with a single `ChangeDisplaySettingsExW(NULL, ...)`, so Windows
+    /// re-lays out the desktop once. Either all changes are committed or none:
+    /// if one can't be staged, the registry entries already written for the
+    /// others are put back before returning.
+    pub fn apply_batch(&self, changes: &[DisplayChange]) -> Result<(), BatchError> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if !self.persist_changes {
+            return self.apply_transient(changes);
+        }
+        let mut staged = Vec::with_capacity(changes.len());
+        for change in changes {
+            let original = self.get_registry_settings(&change.device_name);
+            if let Err(error) = self.stage_change(&change.device_name, &change.dev_mode, change.flags) {
+                self.roll_back(&staged);
+                return Err(BatchError::Stage {
+                    device_name: change.device_name.clone(),
+                    error,
+                });
+            }
+            staged.push((change.device_name.as_str(), original));
+        }
+        self.api.apply_settings(None, None, CDS_TYPE(0)).map_err(|error| {
+            self.roll_back(&staged);
+            BatchError::Commit(error)
+        })
+    }
+
+    /// Applies each change on its own without touching the registry. Staging
+    /// needs the registry, so there is no single commit here; if a change
+    /// fails, the ones already applied are reverted to the mode they had.
+    fn apply_transient(&self, changes: &[DisplayChange]) -> Result<(), BatchError> {
+        let mut applied: Vec<(&str, Option<DEVMODEW>)> = Vec::with_capacity(changes.len());
+        for change in changes {
+            let original = self.get_monitor_settings(&change.device_name);
+            if let Err(error) = self.change_now(&change.device_name, &change.dev_mode, change.flags) {
+                for (device_name, original) in applied.iter().rev() {
+                    // A device that had no current mode was detached.
+                    let mode = original.unwrap_or_else(detach_mode);
+                    if self.change_now(device_name, &mode, CDS_TYPE(0)).is_err() {
+                        log::warn!("Could not revert the change for {}", device_name);
+                    }
+                }
+                return Err(BatchError::Stage {
+                    device_name: change.device_name.clone(),
+                    error,
+                });
+            }
+            applied.push((change.device_name.as_str(), original));
+        }
+        Ok(())
+    }
+
+    /// Applies the planned changes as one batch and reports the outcome per
+    /// device, in the order planned.
+    fn apply_planned(&self, planned: Vec<PlannedChange>) -> Vec<DeviceResult> {
+        let changes: Vec<DisplayChange> = planned
+            .iter()
+            .filter_map(|(_, plan)| plan.as_ref().ok().and_then(Clone::clone))
+            .collect();
+        let batch_results = match self.apply_batch(&changes) {
+            Ok(()) => Vec::new(),
+            Err(e) => e.device_results(&changes),
+        };
+        planned
+            .into_iter()
+            .map(|(device_name, plan)| {
+                let result = match plan {
+                    Err(e) => Err(e),
+                    Ok(None) => Ok(()),
+                    Ok(Some(change)) => batch_results
+                        .iter()
+                        .find(|(name, _)| *name == change.device_name)
+                        .map_or(Ok(()), |(_, result)| result.clone()),
+                };
+                (device_name, result)
+            })
+            .collect()
+    }
+
+    /// What the registry holds for the device, i.e. what a commit would apply.
+    fn get_registry_settings(&self, device_name: &str) -> Option<DEVMODEW> {
+        self.api.get_settings(device_name, ENUM_REGISTRY_SETTINGS)
+    }
+
+    /// Rewrites the registry entries changed by a batch that won't be committed.
+    fn roll_back(&self, staged: &[(&str, Option<DEVMODEW>)]) {
+        for (device_name, original) in staged {
+            let restored = original.is_some_and(|mode| self.stage_change(device_name, &mode, CDS_TYPE(0)).is_ok());
+            if !restored {
+                log::warn!("Could not roll back the staged change for {}", device_name);
+            }
+        }
+    }
+
+    /// Writes the change to the registry (for all users) without applying it.
+    fn stage_change(&self, device_name: &str, dev_mode: &DEVMODEW, extra_flags: CDS_TYPE) -> Result<(), DisplayChangeError> {
+        let flags = CDS_TYPE(CDS_UPDATEREGISTRY.0 | CDS_GLOBAL.0 | CDS_NORESET.0 | extra_flags.0);
+        self.api.apply_settings(Some(device_name), Some(dev_mode), flags)
+    }
+
+    /// Applies the change immediately, for this session only.
+    fn change_now(&self, device_name: &str, dev_mode: &DEVMODEW, flags: CDS_TYPE) -> Result<(), DisplayChangeError> {
+        self.api.apply_settings(Some(device_name), Some(dev_mode), flags)
+    }
+
+    /// Makes `device_name` the primary monitor. The primary always sits at
+    /// (0, 0), so every active monitor is shifted by the new primary's
+    /// current offset to keep the arrangement intact.
+    pub fn set_primary(&mut self, device_name: &str) -> Result<(), DisplayChangeError> {
+        let monitors = self.get_all_monitors();
+        let Some(target) = monitors.iter().find(|m| m.device_name.eq_ignore_ascii_case(device_name)) else {
+            return Err(DisplayChangeError::UnknownDevice);
+        };
+        if !target.is_physical() {
+            return Err(DisplayChangeError::NotPhysical);
+        }
+        if !target.is_active {
+            return Err(DisplayChangeError::NotActive);
+        }
+        if target.is_primary {
+            return Ok(());
+        }
+        let (offset_x, offset_y) = (target.position_x.unwrap_or(0), target.position_y.unwrap_or(0));
+
+        let changes: Vec<DisplayChange> = monitors
+            .iter()
+            .filter(|m| m.is_active && m.is_physical())
+            .map(|monitor| {
+                let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+                dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+                dev_mode.dmFields = DM_POSITION;
+                dev_mode.Anonymous1.Anonymous2.dmPosition.x = monitor.position_x.unwrap_or(0) - offset_x;
+                dev_mode.Anonymous1.Anonymous2.dmPosition.y = monitor.position_y.unwrap_or(0) - offset_y;
+                let flags = if monitor.device_name == target.device_name {
+                    CDS_SET_PRIMARY
+                } else {
+                    CDS_TYPE(0)
+                };
+                DisplayChange {
+                    device_name: monitor.device_name.clone(),
+                    dev_mode,
+                    flags,
+                }
+            })
+            .collect();
+
+        self.apply_batch(&changes).map_err(|e| match e {
+            BatchError::Stage { error, .. } | BatchError::Commit(error) => error,
+        })
+    }
+
+    /// Makes `id` (a device name or stable ID) primary for the session;
+    /// `restore_all_monitors` puts the original primary and positions back.
+    pub fn switch_primary(&mut self, id: &str) -> DeviceResult {
+        let monitors = self.get_all_monitors();
+        let Some(monitor) = monitors.iter().find(|m| m.matches_id(id)) else {
+            return (id.to_string(), Err(DisplayChangeError::UnknownDevice));
+        };
+        let result = self.set_primary(&monitor.device_name);
+        if result.is_ok() && !monitor.is_primary {
+            self.monitors_disabled = true;
+        }
+        (monitor.device_name.clone(), result)
+    }
+
+    /// The monitor that stays on: `keep` (a device name or stable ID) if it's
+    /// connected, otherwise the primary.
+    pub fn kept_monitor(&self, keep: Option<&str>) -> Option<MonitorInfo> {
+        let mut monitors = self.get_all_monitors();
+        let idx = keep
+            .and_then(|keep| monitors.iter().position(|m| m.is_active && m.matches_id(keep)))
+            .or_else(|| monitors.iter().position(|m| m.is_primary))?;
+        Some(monitors.swap_remove(idx))
+    }
+
+    /// Turns on HDR on `keep` (a device name or stable ID), or on the primary
+    /// when it's `None` or not connected. `restore_all_monitors` turns it back
+    /// off if the snapshot had it off.
+    pub fn enable_hdr(&mut self, keep: Option<&str>) -> DeviceResult {
+        let Some(mut monitor) = self.kept_monitor(keep) else {
+            return (keep.unwrap_or_default().to_string(), Err(DisplayChangeError::UnknownDevice));
+        };
+        self.fill_extras(slice::from_mut(&mut monitor));
+        let result = if !monitor.hdr_supported {
+            Err(DisplayChangeError::NoHdr)
+        } else if monitor.hdr_enabled {
+            Ok(())
+        } else {
+            self.api.set_advanced_color(&monitor.device_name, true).map_err(DisplayChangeError::Hdr)
+        };
+        if result.is_ok() && !monitor.hdr_enabled {
+            self.monitors_disabled = true;
+        }
+        (monitor.device_name.clone(), result)
+    }
+
+    /// Disables every active monitor except `keep` (a device name or stable
+    /// ID), making it primary first if needed. Falls back to keeping the
+    /// current primary when `keep` isn't connected, so at least one monitor
+    /// always stays on.
+    pub fn disable_all_except(&mut self, keep: &str) -> Vec<DeviceResult> {
+        let monitors = self.get_all_monitors();
+        let kept = monitors.iter().find(|m| m.is_active && m.is_physical() && m.matches_id(keep));
+        match kept {
+            Some(kept) if !kept.is_primary => {
+                if let Err(e) = self.set_primary(&kept.device_name) {
+                    log::warn!("Could not make {} primary ({}); keeping the current primary", kept.device_name, e);
+                }
+            }
+            Some(_) => {}
+            None => log::warn!("Monitor {} to keep enabled is not connected; keeping the primary", keep),
+        }
+        self.disable_secondary_monitors()
+    }
+
+    /// Detaches every active monitor except the primary.
+    pub fn disable_secondary_monitors(&mut self) -> Vec<DeviceResult> {
+        let secondaries: Vec<String> = self
+            .get_all_monitors()
+            .into_iter()
+            .filter(|m| m.is_active && !m.is_primary && m.is_physical() && self.is_managed(m))
+            .map(|m| m.device_name)
+            .collect();
+        self.disable_monitors(&secondaries)
+    }
+
+    /// Detaches each named monitor and reports the outcome per device.
+    pub fn disable_monitors(&mut self, device_names: &[String]) -> Vec<DeviceResult> {
+        let monitors = self.get_all_monitors();
+        let planned: Vec<PlannedChange> = device_names
+            .iter()
+            .map(|device_name| {
+                let Some(monitor) = monitors
+                    .iter()
+                    .find(|m| m.device_name.eq_ignore_ascii_case(device_name))
+                else {
+                    return (device_name.clone(), Err(DisplayChangeError::UnknownDevice));
+                };
+                let plan = if !monitor.is_physical() {
+                    Err(DisplayChangeError::NotPhysical)
+                } else if monitor.is_primary {
+                    Err(DisplayChangeError::PrimaryMonitor)
+                } else if self.is_excluded(monitor) {
+                    Err(DisplayChangeError::Excluded)
+                } else if !monitor.is_active {
+                    Err(DisplayChangeError::NotActive)
+                } else {
+                    Ok(Some(DisplayChange {
+                        device_name: monitor.device_name.clone(),
+                        dev_mode: detach_mode(),
+                        flags: CDS_TYPE(0),
+                    }))
+                };
+                (monitor.device_name.clone(), plan)
+            })
+            .collect();
+
+        let results = if self.backend == DisplayBackend::Ccd {
+            self.detach_with_ccd(planned)
+        } else {
+            self.apply_planned(planned)
+        };
+        if results.iter().any(|(_, r)| r.is_ok()) {
+            self.monitors_disabled = true;
+        }
+        results
+    }
+
+    /// Drops every planned device from the topology in one `SetDisplayConfig` call.
+    fn detach_with_ccd(&self, planned: Vec<PlannedChange>) -> Vec<DeviceResult> {
+        let detaching: Vec<String> = planned
+            .iter()
+            .filter(|(_, plan)| plan.is_ok())
+            .map(|(device_name, _)| device_name.clone())
+            .collect();
+        let outcome = if detaching.is_empty() {
+            Ok(())
+        } else {
+            self.api.detach_paths(&detaching, self.persist_changes).map_err(DisplayChangeError::Ccd)
+        };
+        planned
+            .into_iter()
+            .map(|(device_name, plan)| (device_name, plan.and(outcome.clone())))
+            .collect()
+    }
+
+    /// Powers each named monitor off over DDC/CI. Monitors that don't answer
+    /// DDC/CI are detached instead.
+    pub fn power_off_monitors(&mut self, device_names: &[String]) -> Vec<DeviceResult> {
+        let monitors = self.get_all_monitors();
+        let mut results = Vec::with_capacity(device_names.len());
+        let mut fallback = Vec::new();
+
+        for device_name in device_names {
+            let Some(monitor) = monitors
+                .iter()
+                .find(|m| m.device_name.eq_ignore_ascii_case(device_name))
+            else {
+                results.push((device_name.clone(), Err(DisplayChangeError::UnknownDevice)));
                 continue;
+            };
+            if !monitor.is_physical() {
+                results.push((monitor.device_name.clone(), Err(DisplayChangeError::NotPhysical)));
+            } else if !monitor.is_active {
+                results.push((monitor.device_name.clone(), Err(DisplayChangeError::NotActive)));
+            } else {
+                match self.api.power_off(&monitor.device_name) {
+                    Ok(()) => {
+                        self.ddc_powered_off.push(monitor.device_name.clone());
+                        self.monitors_disabled = true;
+                        results.push((monitor.device_name.clone(), Ok(())));
+                    }
+                    Err(e) => {
+                        log::warn!("Could not power off {} over DDC/CI ({}); detaching it instead", monitor.device_name, e);
+                        fallback.push(monitor.device_name.clone());
+                    }
+                }
             }
+        }
+
+        if !fallback.is_empty() {
+            results.extend(self.disable_monitors(&fallback));
+        }
+        results
+    }
 
-            let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
-            dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
-            dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_POSITION;
-            dev_mode.dmPelsWidth = 0;
-            dev_mode.dmPelsHeight = 0;
-
-            let name_wide = Self::device_name_wide(&monitor.device_name);
-
-            unsafe {
-                let result = ChangeDisplaySettingsExW(
-                    PCWSTR(name_wide.as_ptr()),
-                    Some(&dev_mode),
-                    None,
-                    stage_flags,
-                    None,
-                );
-                if result == DISP_CHANGE_SUCCESSFUL {
-                    count += 1;
+    /// Covers each named monitor with a black window, leaving the display
+    /// topology alone.
+    pub fn blank_monitors(&mut self, device_names: &[String]) -> Vec<DeviceResult> {
+        let monitors = self.get_all_monitors();
+        let mut results = Vec::with_capacity(device_names.len());
+        let mut covered = Vec::new();
+        let mut rects = Vec::new();
+
+        for device_name in device_names {
+            match monitors.iter().find(|m| m.device_name.eq_ignore_ascii_case(device_name)) {
+                None => results.push((device_name.clone(), Err(DisplayChangeError::UnknownDevice))),
+                Some(monitor) if !monitor.is_physical() => {
+                    results.push((monitor.device_name.clone(), Err(DisplayChangeError::NotPhysical)))
                 }
+                Some(monitor) => match monitor.rect() {
+                    Some(rect) if monitor.is_active => {
+                        covered.push(monitor.device_name.clone());
+                        rects.push(rect);
+                    }
+                    _ => results.push((monitor.device_name.clone(), Err(DisplayChangeError::NotActive))),
+                },
+            }
+        }
+
+        // Replacing the overlays drops (and closes) any left from before.
+        let (overlays, overlay_results) = self.api.show_overlays(rects);
+        self.overlays = Some(overlays);
+        for (device_name, result) in covered.into_iter().zip(overlay_results) {
+            let result = result.map_err(DisplayChangeError::Overlay);
+            if result.is_ok() {
+                self.monitors_disabled = true;
             }
+            results.push((device_name, result));
+        }
+        results
+    }
+
+    fn power_on_monitors(&mut self) -> Vec<DeviceResult> {
+        self.ddc_powered_off
+            .drain(..)
+            .map(|device_name| {
+                let result = self.api.power_on(&device_name).map_err(DisplayChangeError::Ddc);
+                (device_name, result)
+            })
+            .collect()
+    }
+
+    /// Detaches one monitor at the user's request. It stays off through
+    /// `restore_session_monitors` until the user turns it back on.
+    pub fn disable_monitor_manually(&mut self, id: &str) -> DeviceResult {
+        let monitors = self.get_all_monitors();
+        let Some(monitor) = monitors.iter().find(|m| m.matches_id(id)) else {
+            return (id.to_string(), Err(DisplayChangeError::UnknownDevice));
+        };
+        self.save_current_settings();
+        let result = self.disable_monitors(std::slice::from_ref(&monitor.device_name)).remove(0);
+        if result.1.is_ok() && !self.manually_disabled.iter().any(|k| k == monitor.key()) {
+            self.manually_disabled.push(monitor.key().to_string());
+        }
+        result
+    }
+
+    /// Reattaches one detached monitor with its saved mode, or with its
+    /// largest mode to the right of the desktop if nothing was saved for it.
+    pub fn enable_monitor_manually(&mut self, id: &str) -> DeviceResult {
+        let monitors = self.get_all_monitors();
+        let Some(monitor) = monitors.iter().find(|m| m.matches_id(id)) else {
+            return (id.to_string(), Err(DisplayChangeError::UnknownDevice));
+        };
+        if monitor.is_active {
+            return (monitor.device_name.clone(), Ok(()));
+        }
+        let saved_key = self
+            .saved_settings
+            .iter()
+            .find(|(key, saved)| resolve_device_name(key, saved, &monitors) == monitor.device_name)
+            .map(|(key, _)| key.clone());
+        let mode = match saved_key.as_ref().and_then(|key| self.saved_settings.get(key)) {
+            Some(saved) => saved.mode,
+            None => {
+                let Some(mut mode) = self
+                    .available_modes(&monitor.device_name)
+                    .into_iter()
+                    .max_by_key(|m| (m.width * m.height, m.frequency))
+                else {
+                    return (monitor.device_name.clone(), Err(DisplayChangeError::BadMode));
+                };
+                mode.position_x = monitors.iter().filter_map(|m| m.rect()).map(|r| r.right).max().unwrap_or(0);
+                mode.position_y = 0;
+                mode
+            }
+        };
+
+        let change = DisplayChange {
+            device_name: monitor.device_name.clone(),
+            dev_mode: mode.to_devmode(),
+            flags: CDS_TYPE(0),
+        };
+        if let Err(e) = self.apply_batch(std::slice::from_ref(&change)) {
+            return e.device_results(&[change]).remove(0);
         }
 
-        if count > 0 {
-            Self::apply_staged_changes();
+        self.manually_disabled.retain(|key| !monitor.matches_id(key));
+        if let Some(key) = &saved_key {
+            self.restore_extras(std::slice::from_ref(key));
+        }
+        if let Some(key) = saved_key {
+            self.saved_settings.remove(&key);
+            self.unrestored.retain(|k| *k != key);
+        }
+        if self.detached_saved_keys().is_empty() && self.ddc_powered_off.is_empty() && self.overlays.is_none() {
+            self.monitors_disabled = false;
+        }
+        if self.saved_settings.is_empty() {
+            self.remove_saved_settings_file();
+        } else {
+            self.save_settings_to_disk();
+        }
+        (monitor.device_name.clone(), Ok(()))
+    }
+
+    /// Reattaches monitors detached for a target that `managed_monitors` no
+    /// longer covers, so unticking one in settings takes effect right away.
+    /// Monitors the user turned off by hand stay off.
+    pub fn release_unmanaged_monitors(&mut self) -> Vec<DeviceResult> {
+        if !self.monitors_disabled {
+            return Vec::new();
+        }
+        let monitors = self.get_all_monitors();
+        let released: Vec<String> = monitors
+            .iter()
+            .filter(|m| !m.is_active && m.is_physical() && !self.is_managed(m))
+            .filter(|m| !self.manually_disabled.iter().any(|key| m.matches_id(key)))
+            .filter(|m| {
+                self.saved_settings
+                    .iter()
+                    .any(|(key, saved)| resolve_device_name(key, saved, &monitors) == m.device_name)
+            })
+            .map(|m| m.key().to_string())
+            .collect();
+        released.iter().map(|key| self.enable_monitor_manually(key)).collect()
+    }
+
+    /// Like `restore_all_monitors`, but monitors the user turned off by hand
+    /// stay off unless `include_manual` is set.
+    pub fn restore_session_monitors(&mut self, include_manual: bool) -> Vec<DeviceResult> {
+        if include_manual || self.manually_disabled.is_empty() {
+            return self.restore_all_monitors();
+        }
+        let manual = mem::take(&mut self.manually_disabled);
+        let held: Vec<(String, SavedMonitor)> = manual
+            .iter()
+            .filter_map(|key| self.saved_settings.remove_entry(key))
+            .collect();
+        let results = self.restore_all_monitors();
+        if !held.is_empty() {
+            self.unrestored.extend(held.iter().map(|(key, _)| key.clone()));
+            self.saved_settings.extend(held);
             self.monitors_disabled = true;
+            self.save_settings_to_disk();
+        }
+        self.manually_disabled = manual;
+        results
+    }
+
+    /// Removes any overlays and wakes monitors powered off over DDC/CI, then
+    /// reapplies every saved mode and reports the outcome per device, using
+    /// each monitor's current device name. This includes monitors turned off
+    /// by hand.
+    pub fn restore_all_monitors(&mut self) -> Vec<DeviceResult> {
+        self.manually_disabled.clear();
+        self.rotated.clear();
+        self.overlays = None;
+        self.restore_attempts = 1;
+        let mut results = self.power_on_monitors();
+        let mut restored = None;
+        if let Some(topology) = self.saved_topology.take() {
+            self.remove_saved_topology_file();
+            match self.api.apply_topology(&topology, self.persist_changes) {
+                Ok(()) => restored = Some(self.verify_topology(&mut results)),
+                // Adapter IDs in a saved topology don't survive a reboot, so a
+                // snapshot from before a crash can be stale; the per-monitor
+                // modes below still work.
+                Err(e) => log::warn!("Could not reapply the saved topology ({}); restoring mode by mode", e.message()),
+            }
         }
+        let restored = match restored {
+            Some(restored) => restored,
+            None => self.restore_saved_modes(&mut results),
+        };
+        self.restore_extras(&restored);
+        self.monitors_disabled = false;
+        self.settle_snapshot(&restored);
+        self.restore_window_layout();
+        results
+    }
+
+    /// Reapplies the saved modes of monitors whose last restore didn't take
+    /// and reports the outcome per device. Call it once `restore_retry_delay`
+    /// has passed; the rest of the snapshot is left as it is.
+    pub fn retry_restore(&mut self) -> Vec<DeviceResult> {
+        let retry = mem::take(&mut self.unverified);
+        let others: Vec<String> = self.saved_settings.keys().filter(|key| !retry.contains(key)).cloned().collect();
+        let held: Vec<(String, SavedMonitor)> =
+            others.iter().filter_map(|key| self.saved_settings.remove_entry(key)).collect();
+        self.restore_attempts += 1;
+        let mut results = Vec::new();
+        let restored = self.restore_saved_modes(&mut results);
+        self.restore_extras(&restored);
+        self.saved_settings.extend(held);
+        self.settle_snapshot(&restored);
+        results
+    }
 
-        count
+    /// How long to wait before `retry_restore`, if the last restore left
+    /// monitors that didn't come back and attempts are left. The caller does
+    /// the waiting, so the manager's thread stays free meanwhile.
+    pub fn restore_retry_delay(&self) -> Option<Duration> {
+        if self.unverified.is_empty() || self.restore_attempts >= RESTORE_ATTEMPTS {
+            return None;
+        }
+        Some(RESTORE_RETRY_DELAY * 2u32.pow(self.restore_attempts - 1))
     }
 
-    pub fn restore_all_monitors(&mut self) -> Vec<String> {
-        let mut restored = Vec::new();
+    /// `restore_all_monitors`, then `retry_restore` after each
+    /// `restore_retry_delay`, sleeping on this thread in between. For callers
+    /// that own the manager; behind a `ManagerHandle` use
+    /// `ManagerHandle::finish_restore` instead.
+    pub fn restore_all_monitors_with_retries(&mut self) -> Vec<DeviceResult> {
+        let mut results = self.restore_all_monitors();
+        while let Some(delay) = self.restore_retry_delay() {
+            thread::sleep(delay);
+            let retried = self.retry_restore();
+            merge_results(&mut results, retried);
+        }
+        results
+    }
+
+    /// Drops the `restored` entries from the snapshot. Whatever failed stays
+    /// in it (and on disk) for another attempt.
+    fn settle_snapshot(&mut self, restored: &[String]) {
+        self.saved_settings.retain(|key, _| !restored.contains(key));
+        self.unrestored = self.saved_settings.keys().cloned().collect();
         if self.saved_settings.is_empty() {
-            self.monitors_disabled = false;
-            return restored;
+            self.remove_saved_settings_file();
+        } else {
+            self.save_settings_to_disk();
         }
+    }
 
-        let stage_flags = CDS_TYPE(CDS_UPDATEREGISTRY.0 | CDS_NORESET.0);
+    /// Checks that the monitors of the snapshot came back as saved once the
+    /// saved topology is reapplied, and returns the keys that did.
+    fn verify_topology(&mut self, results: &mut Vec<DeviceResult>) -> Vec<String> {
+        let monitors = self.get_all_monitors();
+        let mut pending = Vec::new();
+        for (key, saved) in &self.saved_settings {
+            let device_name = resolve_device_name(key, saved, &monitors);
+            if !is_saved_monitor_present(key, saved, &monitors) {
+                results.push((device_name, Err(DisplayChangeError::UnknownDevice)));
+                continue;
+            }
+            let flags = if saved.is_primary { CDS_SET_PRIMARY } else { CDS_TYPE(0) };
+            pending.push((key.clone(), DisplayChange { device_name, dev_mode: saved.mode.to_devmode(), flags }));
+        }
+        self.verify_restored(pending, results)
+    }
+
+    /// Reapplies the snapshot's modes in one batch and returns the keys of
+    /// the entries that were verified to be back.
+    fn restore_saved_modes(&mut self, results: &mut Vec<DeviceResult>) -> Vec<String> {
+        self.unverified.clear();
+        if self.saved_settings.is_empty() {
+            return Vec::new();
+        }
+        let monitors = self.get_all_monitors();
 
-        for (device_name, settings) in &self.saved_settings {
-            let name_wide = Self::device_name_wide(device_name);
+        // The saved positions are relative to the original primary. If that
+        // monitor was unplugged mid-session, keep the current primary where
+        // it is and shift the saved layout around it instead.
+        let primary_missing = self
+            .saved_settings
+            .iter()
+            .any(|(key, saved)| saved.is_primary && !is_saved_monitor_present(key, saved, &monitors));
+        let (offset_x, offset_y) = if primary_missing {
+            monitors
+                .iter()
+                .find(|m| m.is_primary)
+                .and_then(|primary| self.saved_settings.get(primary.key()))
+                .map(|saved| (saved.mode.position_x, saved.mode.position_y))
+                .unwrap_or((0, 0))
+        } else {
+            (0, 0)
+        };
 
-            unsafe {
-                let result = ChangeDisplaySettingsExW(
-                    PCWSTR(name_wide.as_ptr()),
-                    Some(settings),
-                    None,
-                    stage_flags,
-                    None,
-                );
+        let mut changes = Vec::with_capacity(self.saved_settings.len());
+        let mut keys = Vec::with_capacity(self.saved_settings.len());
+        for (key, saved) in &self.saved_settings {
+            let device_name = resolve_device_name(key, saved, &monitors);
+            if !is_saved_monitor_present(key, saved, &monitors) {
+                results.push((device_name, Err(DisplayChangeError::UnknownDevice)));
+                continue;
+            }
+            let mut mode = saved.mode;
+            mode.position_x -= offset_x;
+            mode.position_y -= offset_y;
+            let is_new_primary = if primary_missing {
+                monitors.iter().any(|m| m.is_primary && m.device_name == device_name)
+            } else {
+                saved.is_primary
+            };
+            let flags = if is_new_primary { CDS_SET_PRIMARY } else { CDS_TYPE(0) };
+            keys.push(key.clone());
+            changes.push(DisplayChange {
+                device_name,
+                dev_mode: mode.to_devmode(),
+                flags,
+            });
+        }
+        // HashMap order is arbitrary; placing monitors in the wrong order can
+        // leave one pushed away from where the snapshot had it.
+        let mut ordered: Vec<(String, DisplayChange)> = keys.into_iter().zip(changes).collect();
+        ordered.sort_by_key(|(_, change)| restore_order(change));
+        let (mut keys, mut changes): (Vec<_>, Vec<_>) = ordered.into_iter().unzip();
 
-                if result == DISP_CHANGE_SUCCESSFUL {
-                    restored.push(device_name.clone());
+        // A saved mode the driver no longer accepts shouldn't keep every other
+        // monitor from coming back, so drop it and commit the rest.
+        let mut committed = Vec::new();
+        while !changes.is_empty() {
+            match self.apply_batch(&changes) {
+                Ok(()) => committed.extend(keys.drain(..).zip(changes.drain(..))),
+                Err(BatchError::Stage { device_name, error }) => {
+                    let idx = changes.iter().position(|c| c.device_name == device_name).unwrap_or(0);
+                    let change = changes.remove(idx);
+                    keys.remove(idx);
+                    results.push((change.device_name, Err(error)));
+                }
+                Err(e @ BatchError::Commit(_)) => {
+                    results.extend(e.device_results(&changes));
+                    changes.clear();
                 }
             }
         }
+        self.verify_restored(committed, results)
+    }
 
-        Self::apply_staged_changes();
-        self.monitors_disabled = false;
-        restored
+    /// Names of the layouts saved in the data directory `dir`, sorted.
+    pub fn layout_names(dir: &Path) -> Vec<String> {
+        Self::load_layouts(dir).into_keys().collect()
+    }
+
+    /// Saves the mode, position, orientation and primary flag of every active
+    /// monitor as the layout `name`, replacing any layout of that name, and
+    /// returns how many monitors it holds.
+    pub fn capture_snapshot(&self, name: &str) -> Result<usize, LayoutError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LayoutError::EmptyName);
+        }
+        let monitors: Vec<LayoutMonitor> = self
+            .get_all_monitors()
+            .iter()
+            .filter(|m| m.is_active && m.is_physical())
+            .filter_map(|m| {
+                let settings = self.get_monitor_settings(&m.device_name)?;
+                Some(LayoutMonitor {
+                    key: m.key().to_string(),
+                    device_name: m.device_name.clone(),
+                    is_primary: m.is_primary,
+                    mode: DisplayMode::from_devmode(&settings),
+                })
+            })
+            .collect();
+        if monitors.is_empty() {
+            return Err(LayoutError::NoActiveMonitors);
+        }
+        let count = monitors.len();
+        let mut layouts = Self::load_layouts(&self.data_dir);
+        layouts.insert(name.to_string(), monitors);
+        Self::save_layouts(&self.data_dir, &layouts)?;
+        log::info!("Saved layout \"{}\" with {} monitor(s)", name, count);
+        Ok(count)
+    }
+
+    /// Puts the monitors back the way the layout `name` had them, in one
+    /// batch. Active monitors the layout doesn't include are turned off,
+    /// except excluded ones. A monitor in the layout that isn't connected is
+    /// reported as an unknown device and the rest is applied without it.
+    pub fn apply_snapshot(&mut self, name: &str) -> Result<Vec<DeviceResult>, LayoutError> {
+        if self.monitors_disabled {
+            return Err(LayoutError::MonitorsChanged);
+        }
+        let name = name.trim();
+        let layout = Self::load_layouts(&self.data_dir)
+            .remove(name)
+            .ok_or_else(|| LayoutError::NotFound(name.to_string()))?;
+        let monitors = self.get_all_monitors();
+
+        let mut results = Vec::new();
+        let mut changes = Vec::with_capacity(layout.len());
+        for entry in &layout {
+            match monitors.iter().find(|m| m.is_physical() && m.matches_id(&entry.key)) {
+                None => results.push((entry.device_name.clone(), Err(DisplayChangeError::UnknownDevice))),
+                Some(m) if self.is_excluded(m) => {
+                    results.push((m.device_name.clone(), Err(DisplayChangeError::Excluded)));
+                }
+                Some(m) => changes.push(DisplayChange {
+                    device_name: m.device_name.clone(),
+                    dev_mode: entry.mode.to_devmode(),
+                    flags: if entry.is_primary { CDS_SET_PRIMARY } else { CDS_TYPE(0) },
+                }),
+            }
+        }
+        // None of the layout's monitors is here; turning the others off would
+        // leave nothing on screen.
+        if changes.is_empty() {
+            return Ok(results);
+        }
+        changes.sort_by_key(restore_order);
+
+        // The current primary can only go if another monitor takes over.
+        let new_primary = changes.iter().any(|c| c.flags == CDS_SET_PRIMARY);
+        for monitor in &monitors {
+            let in_layout = changes.iter().any(|c| c.device_name == monitor.device_name);
+            if in_layout || !monitor.is_active || !monitor.is_physical() || self.is_excluded(monitor) {
+                continue;
+            }
+            if monitor.is_primary && !new_primary {
+                continue;
+            }
+            changes.push(DisplayChange {
+                device_name: monitor.device_name.clone(),
+                dev_mode: detach_mode(),
+                flags: CDS_TYPE(0),
+            });
+        }
+
+        match self.apply_batch(&changes) {
+            Ok(()) => results.extend(changes.into_iter().map(|c| (c.device_name, Ok(())))),
+            Err(e) => results.extend(e.device_results(&changes)),
+        }
+        Ok(results)
+    }
+
+    fn layouts_path(dir: &Path) -> PathBuf {
+        dir.join(LAYOUTS_FILE)
+    }
+
+    fn load_layouts(dir: &Path) -> Layouts {
+        let path = Self::layouts_path(dir);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Layouts::new();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+            Layouts::new()
+        })
+    }
+
+    fn save_layouts(dir: &Path, layouts: &Layouts) -> Result<(), LayoutError> {
+        let path = Self::layouts_path(dir);
+        let content = serde_json::to_string_pretty(layouts).map_err(|e| LayoutError::Io(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| LayoutError::Io(e.to_string()))?;
+        }
+        fs::write(&path, content).map_err(|e| LayoutError::Io(format!("could not write {}: {}", path.display(), e)))
+    }
+
+    /// Confirms each committed change left its monitor active at the expected
+    /// resolution and position and returns the keys that check out. Windows
+    /// sometimes reports success while the monitor stays dark, typically right
+    /// after the GPU driver reloads when a fullscreen game exits; those keys
+    /// are kept in `unverified` for `retry_restore`.
+    fn verify_restored(
+        &mut self,
+        pending: Vec<(String, DisplayChange)>,
+        results: &mut Vec<DeviceResult>,
+    ) -> Vec<String> {
+        let monitors = self.get_all_monitors();
+        let mut verified = Vec::new();
+        self.unverified.clear();
+        for (key, change) in pending {
+            let mode = DisplayMode::from_devmode(&change.dev_mode);
+            let took_effect = monitors.iter().any(|m| {
+                m.device_name == change.device_name
+                    && m.is_active
+                    && m.width == Some(mode.width)
+                    && m.height == Some(mode.height)
+                    && m.position_x == Some(mode.position_x)
+                    && m.position_y == Some(mode.position_y)
+            });
+            if took_effect {
+                verified.push(key);
+                results.push((change.device_name, Ok(())));
+            } else {
+                self.unverified.push(key);
+                let error = DisplayChangeError::NotVerified { attempts: self.restore_attempts };
+                results.push((change.device_name, Err(error)));
+            }
+        }
+        if !self.unverified.is_empty() {
+            log::warn!("{} monitor(s) not back after restore attempt {}", self.unverified.len(), self.restore_attempts);
+        }
+        verified
+    }
+
+    /// Puts back the scaling, color profile and gamma ramp of the restored
+    /// snapshot entries under `keys`. A scale that can't be set is reported
+    /// once per run through `take_scale_warning`.
+    fn restore_extras(&mut self, keys: &[String]) {
+        let monitors = self.get_all_monitors();
+        for key in keys {
+            let Some(saved) = self.saved_settings.get(key) else {
+                continue;
+            };
+            let device_name = resolve_device_name(key, saved, &monitors);
+            if let Err(e) = saved.restore_extras(self.api.as_ref(), &device_name) {
+                log::warn!("Could not restore the scaling of {}: {}", device_name, e.message());
+                if !self.scale_warned {
+                    self.scale_warned = true;
+                    self.scale_warning = Some(format!(
+                        "Windows didn't accept the saved scaling for {} ({}). Set it in Display settings if it looks wrong.",
+                        device_name,
+                        e.message()
+                    ));
+                }
+            }
+        }
+    }
+
+    /// A scaling failure the user hasn't been told about yet, if any.
+    pub fn take_scale_warning(&mut self) -> Option<String> {
+        self.scale_warning.take()
+    }
+
+    fn restore_window_layout(&mut self) {
+        let monitors = self.get_all_monitors();
+        self.api.restore_windows(&self.saved_windows, &monitors);
+        self.saved_windows.retain(|window| !window_layout::is_on_active_monitor(window, &monitors));
+        if self.saved_windows.is_empty() {
+            window_layout::remove_file(&self.data_dir);
+        } else {
+            window_layout::save_to_disk(&self.data_dir, &self.saved_windows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_api::mock::MockDisplayApi;
+
+    /// A primary with one secondary on either side of it.
+    fn three_monitors() -> MockDisplayApi {
+        let api = MockDisplayApi::default();
+        api.add_monitor(r"\\.\DISPLAY1", true, (0, 0));
+        api.add_monitor(r"\\.\DISPLAY2", false, (1920, 0));
+        api.add_monitor(r"\\.\DISPLAY3", false, (-1920, 0));
+        api
+    }
+
+    /// A manager over `api` keeping its files in `dir`.
+    fn manager(dir: &Path, api: &MockDisplayApi, saved_settings: SavedSettings) -> MonitorManager {
+        MonitorManager::with_api(dir.to_path_buf(), saved_settings, Box::new(api.clone()))
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn save_and_restore_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        assert_eq!(manager.saved_settings.len(), 3);
+
+        let results = manager.disable_secondary_monitors();
+        assert!(results.iter().all(|(_, result)| result.is_ok()), "{:?}", results);
+        assert!(manager.are_monitors_disabled());
+        assert!(!api.is_active(r"\\.\DISPLAY2") && !api.is_active(r"\\.\DISPLAY3"));
+
+        let results = manager.restore_all_monitors();
+        assert!(results.iter().all(|(_, result)| result.is_ok()), "{:?}", results);
+        assert!(!manager.has_pending_restore());
+        assert!(manager.saved_settings.is_empty());
+        assert!(api.is_primary(r"\\.\DISPLAY1"));
+        assert_eq!((api.mode(r"\\.\DISPLAY2").position_x, api.mode(r"\\.\DISPLAY3").position_x), (1920, -1920));
+        assert!(api.is_active(r"\\.\DISPLAY2") && api.is_active(r"\\.\DISPLAY3"));
+    }
+
+    #[test]
+    fn disable_skips_the_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+
+        let results = manager.disable_monitors(&names(&[r"\\.\DISPLAY1", r"\\.\DISPLAY2"]));
+        assert_eq!(results[0], (r"\\.\DISPLAY1".to_string(), Err(DisplayChangeError::PrimaryMonitor)));
+        assert_eq!(results[1], (r"\\.\DISPLAY2".to_string(), Ok(())));
+        assert!(api.is_active(r"\\.\DISPLAY1") && api.is_primary(r"\\.\DISPLAY1"));
+        assert!(!api.is_active(r"\\.\DISPLAY2"));
+    }
+
+    #[test]
+    fn restores_a_device_detached_by_a_previous_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut first = manager(dir.path(), &api, SavedSettings::new());
+        first.save_current_settings();
+        first.disable_monitors(&names(&[r"\\.\DISPLAY2"]));
+        // The first run dies here; the next one only has the snapshot on disk.
+        drop(first);
+
+        let saved_settings = MonitorManager::load_saved_settings_from_disk(dir.path());
+        let mut manager = manager(dir.path(), &api, saved_settings);
+        assert!(manager.are_monitors_disabled());
+        let results = manager.restore_all_monitors();
+        assert!(results.iter().all(|(_, result)| result.is_ok()), "{:?}", results);
+        assert!(api.is_active(r"\\.\DISPLAY2"));
+        assert_eq!(api.mode(r"\\.\DISPLAY2").position_x, 1920);
+        assert!(!MonitorManager::saved_settings_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn restore_all_reports_partial_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+        api.fail_changes(r"\\.\DISPLAY3");
+
+        let results = manager.restore_all_monitors();
+        let failed: Vec<&DeviceResult> = results.iter().filter(|(_, result)| result.is_err()).collect();
+        assert_eq!(failed, [&(r"\\.\DISPLAY3".to_string(), Err(DisplayChangeError::Failed))]);
+        assert!(api.is_active(r"\\.\DISPLAY2"));
+        assert!(!api.is_active(r"\\.\DISPLAY3"));
+        // The failed monitor stays in the snapshot for another attempt.
+        let remaining: Vec<&String> = manager.saved_settings.values().map(|saved| &saved.device_name).collect();
+        assert_eq!(remaining, [r"\\.\DISPLAY3"]);
+        assert!(MonitorManager::saved_settings_path(dir.path()).exists());
+        // The strategy is over, but quitting still has something to restore.
+        assert!(!manager.are_monitors_disabled());
+        assert!(manager.has_pending_restore());
+    }
+
+    #[test]
+    fn restore_that_did_not_take_is_left_for_the_caller_to_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+        api.ignore_next_change(r"\\.\DISPLAY2");
+
+        let results = manager.restore_all_monitors();
+        assert!(results.contains(&(r"\\.\DISPLAY2".to_string(), Err(DisplayChangeError::NotVerified { attempts: 1 }))));
+        assert!(results.contains(&(r"\\.\DISPLAY3".to_string(), Ok(()))));
+        assert!(!api.is_active(r"\\.\DISPLAY2"));
+        assert!(manager.has_pending_restore());
+        assert_eq!(manager.restore_retry_delay(), Some(RESTORE_RETRY_DELAY));
+
+        assert_eq!(manager.retry_restore(), [(r"\\.\DISPLAY2".to_string(), Ok(()))]);
+        assert!(api.is_active(r"\\.\DISPLAY2"));
+        assert_eq!(manager.restore_retry_delay(), None);
+        assert!(!manager.has_pending_restore());
+        assert!(!MonitorManager::saved_settings_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn retries_back_off_and_stop_after_the_last_attempt() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+        for _ in 0..RESTORE_ATTEMPTS {
+            api.ignore_next_change(r"\\.\DISPLAY2");
+        }
+
+        let mut results = manager.restore_all_monitors();
+        let mut delays = Vec::new();
+        while let Some(delay) = manager.restore_retry_delay() {
+            delays.push(delay);
+            merge_results(&mut results, manager.retry_restore());
+        }
+        assert_eq!(delays, [RESTORE_RETRY_DELAY, RESTORE_RETRY_DELAY * 2]);
+        let failed = (r"\\.\DISPLAY2".to_string(), Err(DisplayChangeError::NotVerified { attempts: RESTORE_ATTEMPTS }));
+        assert!(results.contains(&failed), "{:?}", results);
+        assert_eq!(results.len(), 3);
+        // Still owed a restore, on disk for the next start.
+        assert!(manager.has_pending_restore());
+        assert!(MonitorManager::saved_settings_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn topology_restore_checks_each_monitor() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.set_backend(DisplayBackend::Ccd);
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+        assert!(!api.is_active(r"\\.\DISPLAY2") && !api.is_active(r"\\.\DISPLAY3"));
+        api.unplug(r"\\.\DISPLAY3");
+        api.ignore_next_change(r"\\.\DISPLAY2");
+
+        let mut results = manager.restore_all_monitors();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            results,
+            [
+                (r"\\.\DISPLAY1".to_string(), Ok(())),
+                (r"\\.\DISPLAY2".to_string(), Err(DisplayChangeError::NotVerified { attempts: 1 })),
+                (r"\\.\DISPLAY3".to_string(), Err(DisplayChangeError::UnknownDevice)),
+            ]
+        );
+        assert!(manager.has_pending_restore());
+
+        // The retry goes mode by mode; the unplugged monitor waits for its return.
+        assert_eq!(manager.retry_restore(), [(r"\\.\DISPLAY2".to_string(), Ok(()))]);
+        assert_eq!(api.mode(r"\\.\DISPLAY2").position_x, 1920);
+        let remaining: Vec<&String> = manager.saved_settings.values().map(|saved| &saved.device_name).collect();
+        assert_eq!(remaining, [r"\\.\DISPLAY3"]);
+    }
+
+    #[test]
+    fn resave_keeps_only_entries_still_owed_a_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+        api.fail_changes(r"\\.\DISPLAY3");
+        manager.restore_all_monitors();
+
+        // Turned off in Windows settings, not by the app.
+        api.detach(r"\\.\DISPLAY2");
+        manager.save_current_settings();
+        let mut kept: Vec<(&String, bool)> = manager
+            .saved_settings
+            .values()
+            .map(|saved| (&saved.device_name, api.is_active(&saved.device_name)))
+            .collect();
+        kept.sort();
+        assert_eq!(kept, [(&r"\\.\DISPLAY1".to_string(), true), (&r"\\.\DISPLAY3".to_string(), false)]);
+    }
+
+    /// A change placing `device_name` at `position`, written straight into the DEVMODE.
+    fn change_at(device_name: &str, position: (i32, i32), primary: bool) -> DisplayChange {
+        let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+        dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+        dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_POSITION;
+        dev_mode.dmPelsWidth = 1920;
+        dev_mode.dmPelsHeight = 1080;
+        dev_mode.Anonymous1.Anonymous2.dmPosition.x = position.0;
+        dev_mode.Anonymous1.Anonymous2.dmPosition.y = position.1;
+        DisplayChange {
+            device_name: device_name.to_string(),
+            dev_mode,
+            flags: if primary { CDS_SET_PRIMARY } else { CDS_TYPE(0) },
+        }
+    }
+
+    fn sorted_for_restore(mut changes: Vec<DisplayChange>) -> Vec<String> {
+        changes.sort_by_key(restore_order);
+        changes.into_iter().map(|change| change.device_name).collect()
+    }
+
+    #[test]
+    fn restore_starts_with_the_primary_and_works_outward() {
+        let changes = vec![
+            change_at("far right", (3840, 0), false),
+            change_at("left", (-1920, 0), false),
+            change_at("right", (1920, 0), false),
+            change_at("above", (0, -1080), false),
+            change_at("primary", (0, 0), true),
+        ];
+        assert_eq!(sorted_for_restore(changes), ["primary", "above", "left", "right", "far right"]);
+    }
+
+    #[test]
+    fn restore_order_puts_the_primary_first_wherever_it_is() {
+        // A primary that isn't at the origin, e.g. while an offset is being applied.
+        let changes = vec![change_at("origin", (0, 0), false), change_at("primary", (-3840, 1080), true)];
+        assert_eq!(sorted_for_restore(changes), ["primary", "origin"]);
+    }
+
+    #[test]
+    fn restore_order_breaks_ties_by_position() {
+        let changes = vec![
+            change_at("below", (0, 1080), false),
+            change_at("right", (1080, 0), false),
+            change_at("above", (0, -1080), false),
+            change_at("left", (-1080, 0), false),
+        ];
+        assert_eq!(sorted_for_restore(changes), ["left", "above", "below", "right"]);
+    }
+
+    #[test]
+    fn monitors_are_keyed_by_stable_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let monitors = manager(dir.path(), &api, SavedSettings::new()).get_all_monitors();
+        let second = monitors.iter().find(|m| m.device_name == r"\\.\DISPLAY2").unwrap();
+        assert_eq!(second.key(), r"\\?\DISPLAY#MCK0002#2");
+        assert!(second.matches_id(r"\\.\display2"));
+        assert!(second.matches_id(r"\\?\display#mck0002#2"));
+        assert!(!second.matches_id(r"\\.\DISPLAY3"));
+
+        let unidentified = MonitorInfo { stable_id: None, ..second.clone() };
+        assert_eq!(unidentified.key(), r"\\.\DISPLAY2");
+    }
+
+    #[test]
+    fn scaling_is_only_read_when_asked_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        api.detach(r"\\.\DISPLAY3");
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        assert!(manager.get_all_monitors().iter().all(|m| m.scale_percent.is_none()));
+
+        let monitors = manager.get_all_monitors_detailed();
+        let scales: Vec<(&str, Option<u32>)> =
+            monitors.iter().map(|m| (m.device_name.as_str(), m.scale_percent)).collect();
+        assert_eq!(scales, [(r"\\.\DISPLAY1", Some(100)), (r"\\.\DISPLAY2", Some(100)), (r"\\.\DISPLAY3", None)]);
+        manager.save_current_settings();
+        assert_eq!(manager.saved_settings.len(), 2);
+        assert!(manager.saved_settings.values().all(|saved| saved.scale_percent == Some(100)));
+    }
+
+    #[test]
+    fn saved_entries_follow_their_monitor_after_renumbering() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        let key = r"\\?\DISPLAY#MCK0002#2";
+        let saved = manager.saved_settings[key].clone();
+        assert_eq!(saved.device_name, r"\\.\DISPLAY2");
+
+        api.swap_names(r"\\.\DISPLAY2", r"\\.\DISPLAY3");
+        let monitors = manager.get_all_monitors();
+        assert_eq!(resolve_device_name(key, &saved, &monitors), r"\\.\DISPLAY3");
+        assert!(is_saved_monitor_present(key, &saved, &monitors));
+
+        // Unplugged: the old name now belongs to another monitor, so the entry isn't for it.
+        let unplugged: Vec<MonitorInfo> = monitors.into_iter().filter(|m| m.key() != key).collect();
+        assert_eq!(resolve_device_name(key, &saved, &unplugged), r"\\.\DISPLAY2");
+        assert!(!is_saved_monitor_present(key, &saved, &unplugged));
+    }
+
+    #[test]
+    fn entries_saved_by_device_name_still_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let monitors = manager(dir.path(), &api, SavedSettings::new()).get_all_monitors();
+        // Written before stable IDs existed: keyed by device name, without one inside.
+        let saved = SavedMonitor {
+            device_name: String::new(),
+            is_primary: false,
+            mode: api.mode(r"\\.\DISPLAY2"),
+            gamma: None,
+            color_profile: None,
+            scale_percent: None,
+            hdr_enabled: None,
+        };
+        assert_eq!(resolve_device_name(r"\\.\DISPLAY2", &saved, &monitors), r"\\.\DISPLAY2");
+        assert!(is_saved_monitor_present(r"\\.\DISPLAY2", &saved, &monitors));
+        assert_eq!(resolve_device_name(r"\\.\DISPLAY9", &saved, &monitors), r"\\.\DISPLAY9");
+        assert!(!is_saved_monitor_present(r"\\.\DISPLAY9", &saved, &monitors));
+    }
+
+    #[test]
+    fn restore_after_renumbering_puts_each_monitor_back_in_its_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = three_monitors();
+        let mut manager = manager(dir.path(), &api, SavedSettings::new());
+        manager.save_current_settings();
+        manager.disable_secondary_monitors();
+
+        // Docking renumbers the two detached monitors.
+        api.swap_names(r"\\.\DISPLAY2", r"\\.\DISPLAY3");
+        let results = manager.restore_all_monitors();
+        assert!(results.iter().all(|(_, result)| result.is_ok()), "{:?}", results);
+        // The monitor that was right of the primary is DISPLAY3 now.
+        assert_eq!(api.mode(r"\\.\DISPLAY3").position_x, 1920);
+        assert_eq!(api.mode(r"\\.\DISPLAY2").position_x, -1920);
+        assert!(api.is_active(r"\\.\DISPLAY2") && api.is_active(r"\\.\DISPLAY3"));
     }
 }
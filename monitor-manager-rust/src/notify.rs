@@ -0,0 +1,64 @@
+use std::thread;
+
+use native_windows_gui as nwg;
+use windows::core::{Result, HSTRING};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+/// Toasts from an unpackaged app need an AppUserModelID that has a Start menu
+/// shortcut; PowerShell's is registered on every Windows install.
+const TOAST_APP_ID: &str = r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    /// Needs acknowledging, so it's shown in a message box even when toasts
+    /// are turned off.
+    Error,
+}
+
+/// Shows a notification without blocking the calling thread. `enabled` is the
+/// `notifications` config toggle and silences everything below `Error`.
+pub fn notify(enabled: bool, severity: Severity, title: &str, message: &str) {
+    match severity {
+        Severity::Error => {
+            let (title, message) = (title.to_string(), message.to_string());
+            thread::spawn(move || {
+                unsafe {
+                    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                }
+                nwg::error_message(&title, &message);
+            });
+        }
+        _ if !enabled => {}
+        _ => {
+            if let Err(e) = show_toast(title, message) {
+                log::warn!("Could not show notification \"{}\": {}", title, e.message());
+            }
+        }
+    }
+}
+
+fn show_toast(title: &str, message: &str) -> Result<()> {
+    // WinRT activation needs COM on this thread; a repeat call is a no-op.
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+    }
+    let xml = XmlDocument::new()?;
+    xml.LoadXml(&HSTRING::from(format!(
+        "<toast><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual></toast>",
+        escape_xml(title),
+        escape_xml(message)
+    )))?;
+    let toast = ToastNotification::CreateToastNotification(&xml)?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(TOAST_APP_ID))?.Show(&toast)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,128 @@
+use std::mem;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+const CLASS_NAME: PCWSTR = w!("MonitorManagerOverlay");
+
+/// Borderless black windows covering whole monitors. They live on their own
+/// thread with its own message loop and are destroyed when this is dropped.
+pub struct Overlays {
+    thread_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Overlays {
+    /// Covers each rect with an always-on-top black window that never takes
+    /// focus. Returns one result per rect, in order.
+    pub fn show(rects: Vec<RECT>) -> (Self, Vec<windows::core::Result<()>>) {
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let (windows, results) = create_windows(&rects);
+            let _ = tx.send((unsafe { GetCurrentThreadId() }, results));
+            run_message_loop();
+            for hwnd in windows {
+                // Windows already gone after a display change just fail here.
+                let _ = unsafe { DestroyWindow(hwnd) };
+            }
+        });
+        let (thread_id, results) = rx.recv().unwrap_or_default();
+        (Self { thread_id, thread: Some(thread) }, results)
+    }
+}
+
+impl Drop for Overlays {
+    fn drop(&mut self) {
+        let _ = unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn create_windows(rects: &[RECT]) -> (Vec<HWND>, Vec<windows::core::Result<()>>) {
+    let instance = unsafe { GetModuleHandleW(None) }.unwrap_or_default();
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(overlay_proc),
+        hInstance: instance.into(),
+        hCursor: unsafe { LoadCursorW(None, IDC_ARROW) }.unwrap_or_default(),
+        hbrBackground: HBRUSH(unsafe { GetStockObject(BLACK_BRUSH) }.0),
+        lpszClassName: CLASS_NAME,
+        ..Default::default()
+    };
+    // Fails harmlessly if an earlier overlay thread already registered it.
+    unsafe { RegisterClassW(&class) };
+
+    let mut windows = Vec::new();
+    let results = rects
+        .iter()
+        .map(|rect| {
+            let hwnd = unsafe {
+                CreateWindowExW(
+                    WS_EX_TOPMOST | WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW,
+                    CLASS_NAME,
+                    w!("Monitor Manager overlay"),
+                    WS_POPUP,
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    None,
+                    None,
+                    instance,
+                    None,
+                )
+            }?;
+            let _ = unsafe { ShowWindow(hwnd, SW_SHOWNOACTIVATE) };
+            windows.push(hwnd);
+            Ok(())
+        })
+        .collect();
+    (windows, results)
+}
+
+fn run_message_loop() {
+    let mut msg = MSG::default();
+    while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
+        unsafe {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+/// Whether the window still sits exactly on a monitor. After a monitor is
+/// unplugged or the layout shifts, its overlay no longer does.
+fn covers_a_monitor(hwnd: HWND) -> bool {
+    let mut rect = RECT::default();
+    if unsafe { GetWindowRect(hwnd, &mut rect) }.is_err() {
+        return false;
+    }
+    let hmonitor = unsafe { MonitorFromRect(&rect, MONITOR_DEFAULTTONULL) };
+    if hmonitor.is_invalid() {
+        return false;
+    }
+    let mut info = MONITORINFO {
+        cbSize: mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetMonitorInfoW(hmonitor, &mut info) }.as_bool() && info.rcMonitor == rect
+}
+
+unsafe extern "system" fn overlay_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_DISPLAYCHANGE => {
+            if !covers_a_monitor(hwnd) {
+                let _ = DestroyWindow(hwnd);
+            }
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
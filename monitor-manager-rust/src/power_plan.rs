@@ -0,0 +1,101 @@
+use windows::core::GUID;
+use windows::Win32::Foundation::{LocalFree, E_INVALIDARG, ERROR_NO_MORE_ITEMS, HLOCAL};
+use windows::Win32::System::Power::{
+    PowerEnumerate, PowerGetActiveScheme, PowerReadFriendlyName, PowerSetActiveScheme, ACCESS_SCHEME,
+};
+use windows::Win32::System::Registry::HKEY;
+
+/// The built-in Balanced plan, which every install has.
+pub const BALANCED: &str = "381b4222-f694-41f0-9685-ff5bb260df2e";
+
+/// A power plan from Control Panel's Power Options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerScheme {
+    /// Lowercase GUID without braces, as `powercfg /list` shows it; what
+    /// profiles store.
+    pub guid: String,
+    /// E.g. "Ultimate Performance".
+    pub name: String,
+}
+
+/// Every power plan on this PC, in the order Windows lists them.
+pub fn schemes() -> windows::core::Result<Vec<PowerScheme>> {
+    let mut found = Vec::new();
+    for index in 0.. {
+        let mut guid = GUID::zeroed();
+        let mut size = std::mem::size_of::<GUID>() as u32;
+        let status = unsafe {
+            PowerEnumerate(
+                HKEY::default(),
+                None,
+                None,
+                ACCESS_SCHEME,
+                index,
+                Some(&mut guid as *mut GUID as *mut u8),
+                &mut size,
+            )
+        };
+        if status == ERROR_NO_MORE_ITEMS {
+            break;
+        }
+        status.ok()?;
+        found.push(PowerScheme { guid: to_string(&guid), name: friendly_name(&guid)? });
+    }
+    Ok(found)
+}
+
+/// The GUID of the active power plan.
+pub fn active_scheme() -> windows::core::Result<String> {
+    let mut active: *mut GUID = std::ptr::null_mut();
+    unsafe {
+        PowerGetActiveScheme(HKEY::default(), &mut active).ok()?;
+        let guid = to_string(&*active);
+        let _ = LocalFree(HLOCAL(active.cast()));
+        Ok(guid)
+    }
+}
+
+/// Makes the plan `guid` the active one.
+pub fn set_active_scheme(guid: &str) -> windows::core::Result<()> {
+    let Some(guid) = parse(guid) else {
+        return Err(windows::core::Error::new(E_INVALIDARG, format!("{} is not a GUID", guid)));
+    };
+    unsafe { PowerSetActiveScheme(HKEY::default(), Some(&guid)) }.ok()
+}
+
+fn friendly_name(guid: &GUID) -> windows::core::Result<String> {
+    let mut size = 0u32;
+    unsafe { PowerReadFriendlyName(HKEY::default(), Some(guid), None, None, None, &mut size) }.ok()?;
+    let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+    unsafe {
+        PowerReadFriendlyName(
+            HKEY::default(),
+            Some(guid),
+            None,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            &mut size,
+        )
+    }
+    .ok()?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf16_lossy(&buffer[..len]))
+}
+
+fn to_string(guid: &GUID) -> String {
+    format!("{:?}", guid).to_lowercase()
+}
+
+/// Accepts the GUID with or without braces, in either case.
+fn parse(guid: &str) -> Option<GUID> {
+    let guid = guid.trim().trim_start_matches('{').trim_end_matches('}');
+    let dashes_in_place = guid.len() == 36 && [8, 13, 18, 23].iter().all(|&idx| guid.as_bytes()[idx] == b'-');
+    if !dashes_in_place {
+        return None;
+    }
+    let hex: String = guid.chars().filter(|&c| c != '-').collect();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok().map(GUID::from_u128)
+}
@@ -0,0 +1,317 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use windows::core::{Error, Result, BSTR, PCWSTR};
+use windows::Win32::Foundation::E_FAIL;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoSetProxyBlanket, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, EOAC_NONE,
+    RPC_C_AUTHN_LEVEL_CALL, RPC_C_IMP_LEVEL_IMPERSONATE,
+};
+use windows::Win32::System::Rpc::{RPC_C_AUTHN_WINNT, RPC_C_AUTHZ_NONE};
+use windows::Win32::System::Wmi::{
+    IEnumWbemClassObject, IWbemContext, IWbemLocator, WbemLocator, WBEM_FLAG_FORWARD_ONLY,
+    WBEM_FLAG_RETURN_IMMEDIATELY,
+};
+
+use crate::error::AppError;
+use crate::matcher::TargetPattern;
+use crate::Shutdown;
+
+/// How long the subscription thread blocks before checking whether it's
+/// still wanted, in milliseconds.
+const STOP_CHECK_MS: i32 = 1000;
+
+/// WMI notifications for processes starting and exiting, so a watcher only
+/// has to look at the process list when something changed.
+pub struct ProcessEvents {
+    events: Receiver<()>,
+    /// Another sender for `events`, handed out by `waker`.
+    wake: Sender<()>,
+    stop: Arc<AtomicBool>,
+    /// Set when the subscription thread gives up. `wake` keeps the channel
+    /// open, so the receiver alone can't tell.
+    ended: Arc<AtomicBool>,
+}
+
+impl ProcessEvents {
+    /// Subscribes to starts and exits of processes that could match
+    /// `patterns`. Literal targets narrow the query to their exe names; any
+    /// glob or regex makes it cover every process.
+    pub fn subscribe(patterns: &[TargetPattern]) -> std::result::Result<Self, AppError> {
+        let query = event_query(patterns);
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let wake = event_tx.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let ended = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread_ended = Arc::clone(&ended);
+        thread::spawn(move || {
+            // COM objects stay on the thread that created them.
+            let enumerator = match unsafe { open_subscription(&query) } {
+                Ok(enumerator) => {
+                    let _ = ready_tx.send(Ok(()));
+                    enumerator
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            while !thread_stop.load(Ordering::Relaxed) {
+                let mut objects = [None];
+                let mut returned = 0;
+                let hr = unsafe { enumerator.Next(STOP_CHECK_MS, &mut objects, &mut returned) };
+                if hr.is_err() {
+                    log::warn!("Process event subscription ended: {}", hr.message());
+                    break;
+                }
+                if returned > 0 && event_tx.send(()).is_err() {
+                    break;
+                }
+            }
+            thread_ended.store(true, Ordering::Relaxed);
+            // Wakes a `wait` in progress so it reports the end.
+            let _ = event_tx.send(());
+        });
+        ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(Error::from(E_FAIL)))
+            .map_err(AppError::ProcessScan)?;
+        Ok(Self { events, wake, stop, ended })
+    }
+
+    /// Blocks until a matching process starts or exits, the waker is called,
+    /// or `timeout` passes. `Disconnected` means the subscription broke and
+    /// won't report again.
+    pub fn wait(&self, timeout: Duration) -> std::result::Result<(), RecvTimeoutError> {
+        let result = self.events.recv_timeout(timeout);
+        if self.ended.load(Ordering::Relaxed) {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        result?;
+        // A burst of events needs only one look at the process list.
+        while self.events.try_recv().is_ok() {}
+        Ok(())
+    }
+
+    /// Ends a `wait` in progress, or the next one, early; for
+    /// [`Shutdown::on_request`](crate::Shutdown::on_request).
+    pub fn waker(&self) -> impl Fn() + Send + Sync + 'static {
+        let wake = self.wake.clone();
+        move || {
+            let _ = wake.send(());
+        }
+    }
+}
+
+/// How a [`TickWait::wait`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tick {
+    /// A stop was requested; the loop should wind down.
+    Stop,
+    /// The process list may have changed and has to be read again.
+    Poll,
+    /// No process event arrived, so the last poll still holds.
+    Unchanged,
+}
+
+/// Sleeps between the monitor loop's ticks: on process events while
+/// subscribed, otherwise on `shutdown` alone. A stop request ends the wait
+/// either way.
+pub struct TickWait {
+    shutdown: Arc<Shutdown>,
+    events: Option<ProcessEvents>,
+}
+
+impl TickWait {
+    pub fn new(shutdown: Arc<Shutdown>) -> Self {
+        Self { shutdown, events: None }
+    }
+
+    /// Waits on `events` from now on, or just polls with `None`.
+    pub fn set_events(&mut self, events: Option<ProcessEvents>) {
+        if let Some(events) = &events {
+            // `ProcessEvents::wait` doesn't sleep on `shutdown`, so quitting has to wake it.
+            self.shutdown.on_request(events.waker());
+        }
+        self.events = events;
+    }
+
+    /// Whether the wait is on process events rather than a plain timeout.
+    pub fn has_events(&self) -> bool {
+        self.events.is_some()
+    }
+
+    /// Blocks for up to `timeout`. A subscription that broke is dropped,
+    /// and the loop polls from then on.
+    pub fn wait(&mut self, timeout: Duration) -> Tick {
+        let tick = match self.events.as_ref().map(|events| events.wait(timeout)) {
+            Some(Ok(())) => Tick::Poll,
+            Some(Err(RecvTimeoutError::Timeout)) => Tick::Unchanged,
+            Some(Err(RecvTimeoutError::Disconnected)) => {
+                log::warn!("Process events stopped arriving, polling instead");
+                self.events = None;
+                Tick::Poll
+            }
+            None => {
+                self.shutdown.wait(timeout);
+                Tick::Poll
+            }
+        };
+        if self.shutdown.is_requested() {
+            Tick::Stop
+        } else {
+            tick
+        }
+    }
+}
+
+impl Drop for ProcessEvents {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn event_query(patterns: &[TargetPattern]) -> String {
+    let mut query = "SELECT * FROM __InstanceOperationEvent WITHIN 0.5 \
+        WHERE (__CLASS = '__InstanceCreationEvent' OR __CLASS = '__InstanceDeletionEvent') \
+        AND TargetInstance ISA 'Win32_Process'"
+        .to_string();
+    let names: Option<Vec<String>> = patterns
+        .iter()
+        .map(|pattern| match pattern {
+            TargetPattern::Literal(target) => Some(Path::new(target).file_name()?.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    if let Some(names) = names.filter(|names| !names.is_empty()) {
+        let conditions: Vec<String> = names
+            .iter()
+            .map(|name| format!("TargetInstance.Name = '{}'", name.replace('\\', "\\\\").replace('\'', "\\'")))
+            .collect();
+        query.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+    }
+    query
+}
+
+unsafe fn open_subscription(query: &str) -> Result<IEnumWbemClassObject> {
+    let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    let locator: IWbemLocator = CoCreateInstance(&WbemLocator, None, CLSCTX_INPROC_SERVER)?;
+    let services = locator.ConnectServer(
+        &BSTR::from(r"ROOT\CIMV2"),
+        &BSTR::new(),
+        &BSTR::new(),
+        &BSTR::new(),
+        0,
+        &BSTR::new(),
+        None::<&IWbemContext>,
+    )?;
+    CoSetProxyBlanket(
+        &services,
+        RPC_C_AUTHN_WINNT,
+        RPC_C_AUTHZ_NONE,
+        PCWSTR::null(),
+        RPC_C_AUTHN_LEVEL_CALL,
+        RPC_C_IMP_LEVEL_IMPERSONATE,
+        None,
+        EOAC_NONE,
+    )?;
+    services.ExecNotificationQuery(
+        &BSTR::from("WQL"),
+        &BSTR::from(query),
+        WBEM_FLAG_FORWARD_ONLY | WBEM_FLAG_RETURN_IMMEDIATELY,
+        None::<&IWbemContext>,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    /// A subscription that never reports anything, without going to WMI.
+    fn quiet() -> ProcessEvents {
+        let (wake, events) = mpsc::channel();
+        ProcessEvents {
+            events,
+            wake,
+            stop: Arc::new(AtomicBool::new(false)),
+            ended: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Requests a stop while `ticks` waits on another thread and returns
+    /// how that wait ended.
+    fn stop_while_waiting(shutdown: &Shutdown, mut ticks: TickWait) -> Tick {
+        let waiting = thread::spawn(move || ticks.wait(POLL_INTERVAL));
+        thread::sleep(Duration::from_millis(100));
+
+        let requested = Instant::now();
+        shutdown.request();
+        let tick = waiting.join().unwrap();
+        assert!(requested.elapsed() < POLL_INTERVAL, "took {:?}", requested.elapsed());
+        tick
+    }
+
+    #[test]
+    fn shutdown_ends_a_polling_wait() {
+        let shutdown = Arc::new(Shutdown::default());
+        let ticks = TickWait::new(Arc::clone(&shutdown));
+        assert_eq!(stop_while_waiting(&shutdown, ticks), Tick::Stop);
+    }
+
+    #[test]
+    fn shutdown_ends_a_wait_on_process_events() {
+        let shutdown = Arc::new(Shutdown::default());
+        let mut ticks = TickWait::new(Arc::clone(&shutdown));
+        ticks.set_events(Some(quiet()));
+        assert_eq!(stop_while_waiting(&shutdown, ticks), Tick::Stop);
+    }
+
+    #[test]
+    fn quiet_events_keep_the_last_poll() {
+        let mut ticks = TickWait::new(Arc::new(Shutdown::default()));
+        assert_eq!(ticks.wait(Duration::ZERO), Tick::Poll);
+        let events = quiet();
+        let wake = events.waker();
+        ticks.set_events(Some(events));
+        assert_eq!(ticks.wait(Duration::ZERO), Tick::Unchanged);
+        wake();
+        assert_eq!(ticks.wait(Duration::ZERO), Tick::Poll);
+    }
+
+    #[test]
+    fn broken_subscription_falls_back_to_polling() {
+        let mut ticks = TickWait::new(Arc::new(Shutdown::default()));
+        let events = quiet();
+        events.ended.store(true, Ordering::Relaxed);
+        ticks.set_events(Some(events));
+        assert_eq!(ticks.wait(Duration::ZERO), Tick::Poll);
+        assert!(!ticks.has_events());
+    }
+
+    #[test]
+    fn waker_registered_after_the_request_fires_at_once() {
+        let shutdown = Shutdown::default();
+        shutdown.request();
+        let events = quiet();
+        shutdown.on_request(events.waker());
+        assert_eq!(events.wait(Duration::ZERO), Ok(()));
+    }
+
+    #[test]
+    fn ended_subscription_is_disconnected_despite_the_waker() {
+        let events = quiet();
+        events.ended.store(true, Ordering::Relaxed);
+        (events.waker())();
+        assert_eq!(events.wait(POLL_INTERVAL), Err(RecvTimeoutError::Disconnected));
+    }
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use native_windows_derive::NwgUi;
+use native_windows_gui as nwg;
+use nwg::NativeUi;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+#[derive(Default, NwgUi)]
+pub struct ProcessPicker {
+    #[nwg_control(size: (640, 420), position: (350, 350), title: "Pick a Running Process", flags: "WINDOW|VISIBLE", icon: Some(&data.window_icon))]
+    #[nwg_events( OnWindowClose: [ProcessPicker::cancel] )]
+    window: nwg::Window,
+
+    #[nwg_resource(source_bin: Some(include_bytes!("../icon.ico")))]
+    window_icon: nwg::Icon,
+
+    #[nwg_resource(family: "Segoe UI", size: 13)]
+    ui_font: nwg::Font,
+
+    #[nwg_layout(parent: window, spacing: 4, margin: [10, 10, 10, 10])]
+    layout: nwg::GridLayout,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 0, col: 0, col_span: 4, row_span: 8)]
+    #[nwg_events( OnListBoxDoubleClick: [ProcessPicker::ok] )]
+    process_list: nwg::ListBox<String>,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 0, col_span: 2)]
+    footer_spacer: nwg::Label,
+
+    #[nwg_control(text: "OK", font: Some(&data.ui_font), size: (110, 30))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 2)]
+    #[nwg_events( OnButtonClick: [ProcessPicker::ok] )]
+    ok_button: nwg::Button,
+
+    #[nwg_control(text: "Cancel", font: Some(&data.ui_font), size: (110, 30))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 3)]
+    #[nwg_events( OnButtonClick: [ProcessPicker::cancel] )]
+    cancel_button: nwg::Button,
+
+    /// Exe path for each `process_list` entry.
+    paths: RefCell<Vec<PathBuf>>,
+    picked: RefCell<Option<PathBuf>>,
+}
+
+impl ProcessPicker {
+    fn ok(&self) {
+        let Some(idx) = self.process_list.selection() else {
+            return;
+        };
+        *self.picked.borrow_mut() = self.paths.borrow().get(idx).cloned();
+        nwg::stop_thread_dispatch();
+    }
+
+    // Ends only this dialog's nested event loop; the window goes away when
+    // `pick` drops it.
+    fn cancel(&self) {
+        nwg::stop_thread_dispatch();
+    }
+}
+
+/// Running processes as (name, exe path), one per distinct exe, sorted by
+/// name. Processes whose exe can't be read (usually elevated or system
+/// ones) are left out.
+fn running_processes() -> Vec<(String, PathBuf)> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::new().with_exe(UpdateKind::Always),
+    );
+    let mut processes: Vec<(String, PathBuf)> = system
+        .processes()
+        .values()
+        .filter_map(|process| {
+            let exe = process.exe().filter(|exe| !exe.as_os_str().is_empty())?;
+            Some((process.name().to_string_lossy().into_owned(), exe.to_path_buf()))
+        })
+        .collect();
+    processes.sort_by_cached_key(|(name, exe)| (name.to_lowercase(), exe.to_string_lossy().to_lowercase()));
+    processes.dedup_by(|a, b| a.1.to_string_lossy().eq_ignore_ascii_case(&b.1.to_string_lossy()));
+    processes
+}
+
+/// Shows the picker on top of `parent`, which is disabled meanwhile, and
+/// returns the exe of the process the user chose.
+pub fn pick(parent: &nwg::Window) -> Option<PathBuf> {
+    let processes = running_processes();
+    let app = ProcessPicker::build_ui(Default::default()).expect("Failed to build UI");
+    app.process_list.set_collection(
+        processes
+            .iter()
+            .map(|(name, exe)| format!("{}  —  {}", name, exe.display()))
+            .collect(),
+    );
+    *app.paths.borrow_mut() = processes.into_iter().map(|(_, exe)| exe).collect();
+
+    parent.set_enabled(false);
+    nwg::dispatch_thread_events();
+    app.window.set_visible(false);
+    parent.set_enabled(true);
+    parent.set_focus();
+
+    let picked = app.picked.borrow_mut().take();
+    picked
+}
@@ -0,0 +1,184 @@
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SERVICE_EXISTS};
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows::Win32::System::Threading::GetCurrentProcessId;
+use windows_service::service::{
+    PowerEventParam, ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType, SessionChangeReason,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::{lock, single_instance, AppState, Running};
+
+const SERVICE_NAME: &str = "MonitorManager";
+const DISPLAY_NAME: &str = "Monitor Manager";
+/// Passed by the service control manager so `main` knows to hand over.
+pub const SERVICE_FLAG: &str = "--service";
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hands the process over to the service control manager. Returns once the
+/// service has stopped.
+pub fn run() -> Result<(), String> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(describe)
+}
+
+/// Registers a per-user service: Windows starts a copy of it in each user's
+/// session at sign-in, since display changes only apply to the session
+/// they're made from. Needs an elevated prompt.
+pub fn install() -> Result<String, String> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .map_err(describe)?;
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: DISPLAY_NAME.into(),
+        service_type: ServiceType::USER_OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().map_err(|e| e.to_string())?,
+        launch_arguments: vec![SERVICE_FLAG.into()],
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG).map_err(describe)?;
+    service
+        .set_description("Disables secondary monitors while chosen programs run.")
+        .map_err(describe)?;
+    Ok(format!("Installed the {} service; it starts at the next sign-in", SERVICE_NAME))
+}
+
+/// Stops and removes the service. Copies already running in a user's
+/// session go away when that user signs out.
+pub fn uninstall() -> Result<String, String> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT).map_err(describe)?;
+    let service = manager
+        .open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE)
+        .map_err(describe)?;
+    if service.query_status().map_err(describe)?.current_state != ServiceState::Stopped {
+        let _ = service.stop();
+    }
+    service.delete().map_err(describe)?;
+    Ok(format!("Removed the {} service", SERVICE_NAME))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        log::error!("The service stopped: {}", e);
+    }
+}
+
+fn run_service() -> Result<(), String> {
+    // The handler is registered before the loop exists, so it finds the
+    // state here once the loop has started.
+    let state: Arc<OnceLock<Arc<Mutex<AppState>>>> = Arc::default();
+    let handler_state = Arc::clone(&state);
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| {
+        on_control(control, handler_state.get())
+    })
+    .map_err(describe)?;
+
+    let Some(_instance) = single_instance::acquire() else {
+        set_status(&status_handle, ServiceState::Stopped, ServiceExitCode::ServiceSpecific(1));
+        return Err("another instance is already running".to_string());
+    };
+    if session_id() == 0 {
+        log::warn!("Running in session 0, where display changes don't reach anyone's desktop");
+    }
+
+    let running = Running::start();
+    let _ = state.set(Arc::clone(&running.state));
+    set_status(&status_handle, ServiceState::Running, ServiceExitCode::Win32(0));
+    log::info!("Running as a service");
+
+    running.join();
+    set_status(&status_handle, ServiceState::Stopped, ServiceExitCode::Win32(0));
+    Ok(())
+}
+
+/// Runs on the service control manager's thread. Stopping only flags the
+/// loop, which restores the monitors before it returns.
+fn on_control(control: ServiceControl, state: Option<&Arc<Mutex<AppState>>>) -> ServiceControlHandlerResult {
+    match control {
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            log::info!("Service control {:?}, restoring and exiting", control);
+            if let Some(state) = state {
+                lock(state).shutdown.request();
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::SessionChange(change) => {
+            if matches!(change.reason, SessionChangeReason::ConsoleConnect | SessionChangeReason::RemoteConnect) {
+                if let Some(state) = state {
+                    lock(state).revalidate = true;
+                }
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::PowerEvent(PowerEventParam::ResumeAutomatic) => {
+            if let Some(state) = state {
+                let mut state = lock(state);
+                state.revalidate = true;
+                state.status = "Resumed from sleep - revalidating monitors".to_string();
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::PowerEvent(_) => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }
+}
+
+fn set_status(handle: &ServiceStatusHandle, current_state: ServiceState, exit_code: ServiceExitCode) {
+    let controls_accepted = if current_state == ServiceState::Running {
+        ServiceControlAccept::STOP
+            | ServiceControlAccept::SHUTDOWN
+            | ServiceControlAccept::SESSION_CHANGE
+            | ServiceControlAccept::POWER_EVENT
+    } else {
+        ServiceControlAccept::empty()
+    };
+    let status = ServiceStatus {
+        service_type: ServiceType::USER_OWN_PROCESS,
+        current_state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+    if let Err(e) = handle.set_service_status(status) {
+        log::warn!("Could not report the service status: {}", describe(e));
+    }
+}
+
+fn session_id() -> u32 {
+    let mut session_id = 0;
+    unsafe {
+        let _ = ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id);
+    }
+    session_id
+}
+
+/// The crate's own messages for Win32 failures don't say which one it was.
+fn describe(error: windows_service::Error) -> String {
+    let windows_service::Error::Winapi(error) = error else {
+        return error.to_string();
+    };
+    match error.raw_os_error().map(|code| code as u32) {
+        Some(code) if code == ERROR_ACCESS_DENIED.0 => {
+            "access denied; run this from an administrator command prompt".to_string()
+        }
+        Some(code) if code == ERROR_SERVICE_EXISTS.0 => "the service is already installed".to_string(),
+        Some(code) if code == ERROR_SERVICE_DOES_NOT_EXIST.0 => "the service isn't installed".to_string(),
+        _ => error.to_string(),
+    }
+}
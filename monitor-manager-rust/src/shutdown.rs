@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Something that has to be woken by hand when a stop is requested, because
+/// it blocks on something other than [`Shutdown::wait`].
+type Wake = Box<dyn Fn() + Send + Sync>;
+
+/// Tells the background threads to wind down. Each checks it between
+/// operations and sleeps through [`Shutdown::wait`], so a request wakes it
+/// straight away instead of after its next poll.
+#[derive(Default)]
+pub struct Shutdown {
+    requested: AtomicBool,
+    lock: Mutex<()>,
+    wake: Condvar,
+    wakers: Mutex<Vec<Wake>>,
+}
+
+impl Shutdown {
+    /// Asks every thread to stop and wakes the ones that are waiting.
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+        {
+            // Taking the lock means no waiter is between its check and its wait.
+            let _guard = lock(&self.lock);
+            self.wake.notify_all();
+        }
+        for wake in lock(&self.wakers).iter() {
+            wake();
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Sleeps for `timeout` or until a stop is requested, and returns whether
+    /// one was.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let guard = lock(&self.lock);
+        let _ = self.wake.wait_timeout_while(guard, timeout, |_| !self.is_requested());
+        self.is_requested()
+    }
+
+    /// Calls `wake` when a stop is requested, or right away if one already
+    /// was, for threads blocked somewhere else, e.g. in
+    /// [`ProcessEvents::wait`](crate::ProcessEvents::wait).
+    pub fn on_request(&self, wake: impl Fn() + Send + Sync + 'static) {
+        let mut wakers = lock(&self.wakers);
+        if self.is_requested() {
+            drop(wakers);
+            wake();
+        } else {
+            wakers.push(Box::new(wake));
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
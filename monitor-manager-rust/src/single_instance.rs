@@ -0,0 +1,33 @@
+use windows::core::w;
+use windows::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE};
+use windows::Win32::System::Threading::CreateMutexW;
+
+/// Holds the instance mutex for as long as this process runs.
+pub struct InstanceGuard(HANDLE);
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// Claims the single-instance mutex. `None` means another instance already
+/// holds it.
+pub fn acquire() -> Option<InstanceGuard> {
+    match unsafe { CreateMutexW(None, true, w!(r"Global\MonitorManagerSingleton")) } {
+        Ok(handle) if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS => {
+            unsafe {
+                let _ = CloseHandle(handle);
+            }
+            None
+        }
+        Ok(handle) => Some(InstanceGuard(handle)),
+        Err(e) => {
+            // Better two instances than none at all.
+            log::warn!("Could not create the single-instance mutex: {}", e.message());
+            Some(InstanceGuard(HANDLE::default()))
+        }
+    }
+}
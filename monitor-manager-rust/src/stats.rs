@@ -0,0 +1,184 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+const SESSIONS_FILE: &str = "sessions.jsonl";
+const IN_PROGRESS_FILE: &str = "session-in-progress.json";
+/// How often a running session's marker is touched, which bounds how far
+/// off a crashed session's end time can be.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One stretch of monitors being changed for a target, as stored in
+/// sessions.jsonl.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub target: String,
+    /// RFC 3339, local time.
+    pub start: String,
+    pub end: String,
+    pub duration_secs: u64,
+    pub monitors_disabled: usize,
+    /// Set when the app didn't see the session end and `end` is the last
+    /// time it was known to be running.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub approximate_end: bool,
+}
+
+/// Kept on disk while a session runs, so one cut short by a crash can be
+/// recorded at the next start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InProgress {
+    target: String,
+    start: String,
+    last_seen: String,
+    monitors_disabled: usize,
+}
+
+/// Session count and time played over a period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub sessions: usize,
+    pub duration: Duration,
+}
+
+/// Starts a session, unless one is already running.
+pub fn begin(target: &str, monitors_disabled: usize) {
+    if read_in_progress().is_some() {
+        return;
+    }
+    let now = Local::now().to_rfc3339();
+    write_in_progress(&InProgress {
+        target: target.to_string(),
+        start: now.clone(),
+        last_seen: now,
+        monitors_disabled,
+    });
+}
+
+/// Notes that the running session is still going.
+pub fn heartbeat() {
+    if let Some(mut session) = read_in_progress() {
+        session.last_seen = Local::now().to_rfc3339();
+        write_in_progress(&session);
+    }
+}
+
+/// Records the running session as ending now.
+pub fn end() {
+    if let Some(session) = read_in_progress() {
+        close(session, Local::now(), false);
+    }
+}
+
+/// Records a session the previous run didn't get to end, ending it when it
+/// was last seen.
+pub fn recover() {
+    let Some(session) = read_in_progress() else {
+        return;
+    };
+    let Ok(last_seen) = DateTime::parse_from_rfc3339(&session.last_seen) else {
+        log::warn!("Dropping an unreadable session marker");
+        remove_in_progress();
+        return;
+    };
+    log::info!("Closing the session for {} left open by the previous run", session.target);
+    close(session, last_seen.with_timezone(&Local), true);
+}
+
+/// Every recorded session, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<Session> {
+    let Ok(content) = fs::read_to_string(sessions_path()) else {
+        return Vec::new();
+    };
+    content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+/// Totals for today, this week (from Monday) and this month.
+pub fn summary(sessions: &[Session]) -> [(&'static str, Totals); 3] {
+    let today = Local::now().date_naive();
+    let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday().into());
+    let month_start = today.with_day(1).unwrap_or(today);
+    [
+        ("Today", totals_since(sessions, today)),
+        ("This week", totals_since(sessions, week_start)),
+        ("This month", totals_since(sessions, month_start)),
+    ]
+}
+
+/// Sessions that started on or after `since`.
+fn totals_since(sessions: &[Session], since: NaiveDate) -> Totals {
+    sessions
+        .iter()
+        .filter(|session| {
+            DateTime::parse_from_rfc3339(&session.start)
+                .is_ok_and(|start| start.with_timezone(&Local).date_naive() >= since)
+        })
+        .fold(Totals::default(), |totals, session| Totals {
+            sessions: totals.sessions + 1,
+            duration: totals.duration + Duration::from_secs(session.duration_secs),
+        })
+}
+
+fn close(session: InProgress, end: DateTime<Local>, approximate_end: bool) {
+    let duration_secs = DateTime::parse_from_rfc3339(&session.start)
+        .map(|start| (end.timestamp() - start.timestamp()).max(0) as u64)
+        .unwrap_or(0);
+    let record = Session {
+        target: session.target,
+        start: session.start,
+        end: end.to_rfc3339(),
+        duration_secs,
+        monitors_disabled: session.monitors_disabled,
+        approximate_end,
+    };
+    let path = sessions_path();
+    let result = serde_json::to_string(&record).map_err(|e| e.to_string()).and_then(|line| {
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::warn!("Could not write {}: {}", path.display(), e);
+    }
+    remove_in_progress();
+}
+
+fn sessions_path() -> PathBuf {
+    config::config_dir().join(SESSIONS_FILE)
+}
+
+fn in_progress_path() -> PathBuf {
+    config::config_dir().join(IN_PROGRESS_FILE)
+}
+
+fn read_in_progress() -> Option<InProgress> {
+    let content = fs::read_to_string(in_progress_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_in_progress(session: &InProgress) {
+    let path = in_progress_path();
+    let result = serde_json::to_string(session).map_err(|e| e.to_string()).and_then(|content| {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, content).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::warn!("Could not write {}: {}", path.display(), e);
+    }
+}
+
+fn remove_in_progress() {
+    let path = in_progress_path();
+    if let Err(e) = fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+}
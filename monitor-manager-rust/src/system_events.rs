@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use native_windows_gui as nwg;
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, WTSUnRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows::Win32::System::Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy};
+use windows::Win32::UI::WindowsAndMessaging::{
+    PBT_APMRESUMEAUTOMATIC, WM_ENDSESSION, WM_POWERBROADCAST, WM_QUERYENDSESSION, WM_SETTINGCHANGE,
+    WM_WTSSESSION_CHANGE, WTS_CONSOLE_CONNECT, WTS_REMOTE_CONNECT,
+};
+
+use crate::{lock, AppState};
+use monitor_manager::monitor;
+
+/// NWG reserves handler ids up to 0xFFFF.
+const HANDLER_ID: usize = 0x10000;
+
+/// Set when the light/dark theme may have changed, until the tray picks it up.
+static THEME_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the theme may have changed since the last call.
+pub fn take_theme_change() -> bool {
+    THEME_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+/// A hidden top-level window for the broadcasts the tray's thread messages
+/// never see. Keep it alive for as long as the message loop runs.
+pub struct SystemEvents {
+    window: nwg::Window,
+    _handler: nwg::RawEventHandler,
+}
+
+impl Drop for SystemEvents {
+    fn drop(&mut self) {
+        if let Some(hwnd) = self.window.handle.hwnd() {
+            unsafe {
+                let _ = WTSUnRegisterSessionNotification(HWND(hwnd as _));
+            }
+        }
+    }
+}
+
+/// Creates the window on the calling thread, whose message loop then
+/// dispatches to it. `None` if it couldn't be created.
+pub fn listen(state: &Arc<Mutex<AppState>>) -> Option<SystemEvents> {
+    let mut window = nwg::Window::default();
+    if let Err(e) = nwg::Window::builder()
+        .flags(nwg::WindowFlags::WINDOW)
+        .title("Monitor Manager")
+        .build(&mut window)
+    {
+        log::warn!("Could not create the system events window: {}", e);
+        return None;
+    }
+
+    let state = Arc::clone(state);
+    let handler = nwg::bind_raw_event_handler(&window.handle, HANDLER_ID, move |hwnd, msg, wparam, lparam| {
+        let hwnd = HWND(hwnd as _);
+        match msg {
+            WM_QUERYENDSESSION => {
+                // Only shown if the restore is still running when Windows
+                // lists the apps holding up the logoff.
+                unsafe {
+                    let _ = ShutdownBlockReasonCreate(hwnd, w!("Restoring monitors"));
+                }
+                Some(1)
+            }
+            WM_ENDSESSION => {
+                if wparam != 0 {
+                    restore_for_session_end(&state);
+                }
+                unsafe {
+                    let _ = ShutdownBlockReasonDestroy(hwnd);
+                }
+                Some(0)
+            }
+            WM_POWERBROADCAST if wparam == PBT_APMRESUMEAUTOMATIC as usize => {
+                log::info!("Resumed from sleep");
+                let mut state = lock(&state);
+                state.revalidate = true;
+                state.status = "Resumed from sleep - revalidating monitors".to_string();
+                Some(1)
+            }
+            WM_WTSSESSION_CHANGE if [WTS_CONSOLE_CONNECT, WTS_REMOTE_CONNECT].contains(&(wparam as u32)) => {
+                log::info!("Session connected {}", if wparam as u32 == WTS_REMOTE_CONNECT { "remotely" } else { "at the console" });
+                lock(&state).revalidate = true;
+                Some(0)
+            }
+            // Sent with "ImmersiveColorSet" when switching between light and dark.
+            WM_SETTINGCHANGE if lparam != 0 => {
+                let area = unsafe { PCWSTR(lparam as *const u16).to_string() }.unwrap_or_default();
+                if area == "ImmersiveColorSet" {
+                    THEME_CHANGED.store(true, Ordering::Relaxed);
+                }
+                None
+            }
+            _ => None,
+        }
+    });
+    if let Some(hwnd) = window.handle.hwnd() {
+        // Without it the loop still notices a remote session on its next tick.
+        if let Err(e) = unsafe { WTSRegisterSessionNotification(HWND(hwnd as _), NOTIFY_FOR_THIS_SESSION) } {
+            log::warn!("Could not listen for session changes: {}", e.message());
+        }
+    }
+    match handler {
+        Ok(handler) => Some(SystemEvents { window, _handler: handler }),
+        Err(e) => {
+            log::warn!("Could not listen for system events: {}", e);
+            None
+        }
+    }
+}
+
+/// Puts the displays back before Windows ends the process, which it does as
+/// soon as WM_ENDSESSION returns.
+fn restore_for_session_end(state: &Arc<Mutex<AppState>>) {
+    let (shutdown, monitor_manager, restore_on_exit) = {
+        let state = lock(state);
+        (state.shutdown.clone(), state.monitor_manager.clone(), state.config.restore_on_exit)
+    };
+    // Keeps the monitor loop from disabling anything again meanwhile.
+    shutdown.request();
+
+    let results = monitor_manager.run(move |manager| {
+        if restore_on_exit && manager.has_pending_restore() {
+            log::info!("Session ending, restoring monitors");
+            manager.restore_all_monitors()
+        } else {
+            Vec::new()
+        }
+    });
+    let results = monitor_manager.finish_restore(results);
+    if let Some(failures) = monitor::describe_failures(&results) {
+        log::warn!("Some monitors could not be restored at session end: {}", failures);
+    }
+}
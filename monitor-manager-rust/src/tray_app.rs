@@ -1,98 +1,331 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex};
 use tray_icon::{
-    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
     TrayIconBuilder, Icon as TrayIconImage, TrayIconEvent, MouseButton, MouseButtonState,
 };
 use native_windows_gui as nwg;
 use nwg::NativeUi;
 use native_windows_derive::NwgUi;
-use std::cell::RefCell;
-use crate::AppState;
-use std::path::Path;
-use std::sync::atomic::Ordering;
-use windows::Win32::UI::WindowsAndMessaging::{DispatchMessageW, GetMessageW, PostQuitMessage, TranslateMessage, MSG};
-use windows::Win32::Foundation::HWND;
-use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
-
-pub fn run(state: Arc<Mutex<AppState>>) {
-    nwg::init().expect("Failed to init Native Windows GUI");
+use std::cell::{Cell, RefCell};
+use crate::{about_dialog, autostart, elevation, hotkey, lock, process_picker, system_events};
+use crate::{finish_restore, with_manager};
+use crate::{AppState, StatusEvent};
+use crate::notify::{self, Severity};
+use monitor_manager::config::{self, MatchMode, Profile, Schedule, Strategy, TimeOfDay, TimeWindow, Trigger};
+use monitor_manager::{audio, games, logging, matcher, monitor, power_plan, stats, AppError, MonitorManager, WatcherState};
+use std::path::{Path, PathBuf};
+use std::thread;
+use chrono::Weekday;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use windows::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, GetMessageW, PostQuitMessage, SetTimer, TranslateMessage, MSG, SW_SHOWNORMAL, WM_HOTKEY,
+};
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::UI::Controls::{
+    LIST_VIEW_ITEM_STATE_FLAGS, LVIS_STATEIMAGEMASK, LVITEMW, LVM_GETITEMSTATE, LVM_SETEXTENDEDLISTVIEWSTYLE,
+    LVM_SETITEMSTATE, LVS_EX_CHECKBOXES,
+};
+use windows::Win32::UI::WindowsAndMessaging::{SendMessageW, SetForegroundWindow};
+use windows::core::{w, Interface, HSTRING, PCWSTR};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, IPersistFile, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, STGM_READ,
+};
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+use windows::Win32::UI::Shell::{IShellLinkW, ShellExecuteW, ShellLink};
+
+/// HWND of the open settings dialog, or 0 when none is open.
+static SETTINGS_WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+/// How often the message loop wakes up to refresh the status text.
+const STATUS_REFRESH_MS: u32 = 500;
+/// The tray tooltip holds at most 127 characters.
+const TOOLTIP_MAX_CHARS: usize = 127;
+const TOOLTIP_TITLE: &str = "Monitor Manager\n";
+/// How much of each monitor's name the tooltip keeps when they don't all fit.
+const MONITOR_NAME_MAX_CHARS: usize = 14;
+const PAUSE_TEXT: &str = "⏸ Pause monitoring";
+const RESUME_TEXT: &str = "▶ Resume monitoring";
+const RELEASE_CURSOR_TEXT: &str = "🖱️ Release Cursor";
+const CONFINE_CURSOR_TEXT: &str = "🖱️ Confine Cursor Again";
+
+pub fn run(state: Arc<Mutex<AppState>>) -> Result<(), AppError> {
+    nwg::init().map_err(|e| AppError::TrayInit(e.to_string()))?;
+    let (status_sender, status_events) = mpsc::channel();
+    {
+        let mut state = lock(&state);
+        state.has_tray = true;
+        state.status_events = Some(status_sender);
+    }
     
     let tray_menu = Menu::new();
     let settings_item = MenuItem::new("⚙️ Settings", true, None);
+    let disable_item = MenuItem::new("🌙 Disable Secondary Monitors Now", true, None);
     let restore_item = MenuItem::new("🔄 Re-enable Monitors", true, None);
+    let retry_item = MenuItem::new("🔁 Retry Failed Changes", false, None);
+    let pause_item = CheckMenuItem::new(PAUSE_TEXT, true, false, None);
+    let cursor_item = MenuItem::new(RELEASE_CURSOR_TEXT, false, None);
+    let config_problem = { lock(&state).config_problem.take() };
+    if let Some(problem) = config_problem {
+        notify_user(&state, Severity::Warning, "Config File", &problem);
+    }
+    let autostart_enabled = autostart::sync().unwrap_or_else(|e| {
+        notify_user(&state, Severity::Warning, "Start with Windows", &e.to_string());
+        false
+    });
+    let autostart_item = CheckMenuItem::new("🚀 Start with Windows", true, autostart_enabled, None);
+    let open_config_item = MenuItem::new("📝 Open Config File", true, None);
+    let open_logs_item = MenuItem::new("📂 Open Log Folder", true, None);
+    let profiles_submenu = Submenu::new("🎮 Profiles", true);
     let monitors_submenu = Submenu::new("🖥️ Monitors", true);
-    let status_item = MenuItem::new("📊 Status: Idle", false, None);
+    let layouts_submenu = Submenu::new("🗂️ Layouts", true);
+    let statistics_item = MenuItem::new("📈 Statistics", true, None);
+    let status_item = MenuItem::new("📊 Idle - waiting for process", false, None);
+    let baseline_item = MenuItem::new("🕒 Baseline: not captured yet", false, None);
+    let elevated = { lock(&state).elevated };
+    let elevate_text = if elevated { "🛡️ Running Elevated" } else { "🛡️ Restart Elevated" };
+    let elevate_item = MenuItem::new(elevate_text, !elevated, None);
+    let about_item = MenuItem::new("ℹ️ About", true, None);
     let quit_item = MenuItem::new("❌ Exit", true, None);
 
-    tray_menu.append(&settings_item).unwrap();
-    tray_menu.append(&restore_item).unwrap();
-    tray_menu.append(&monitors_submenu).unwrap();
-    tray_menu.append(&status_item).unwrap();
-    tray_menu.append(&quit_item).unwrap();
+    tray_menu
+        .append_items(&[
+            &settings_item,
+            &disable_item,
+            &restore_item,
+            &retry_item,
+            &pause_item,
+            &cursor_item,
+            &autostart_item,
+            &open_config_item,
+            &open_logs_item,
+            &profiles_submenu,
+            &monitors_submenu,
+            &layouts_submenu,
+            &statistics_item,
+            &status_item,
+            &baseline_item,
+            &elevate_item,
+            &about_item,
+            &quit_item,
+        ])
+        .map_err(|e| AppError::TrayInit(e.to_string()))?;
 
-    refresh_monitors_submenu(&monitors_submenu, &state);
+    let mut monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+    let mut profile_items = Vec::new();
+    let mut layout_items = refresh_layouts_submenu(&layouts_submenu);
+    let _system_events = system_events::listen(&state);
 
-    let icon = load_icon_from_file("icon.ico");
+    let mut icons = TrayIcons::load(TaskbarTheme::current());
+    let mut shown_icon = IconKind::Idle;
 
     let mut tray_icon = Some(
         TrayIconBuilder::new()
         .with_menu(Box::new(tray_menu))
         .with_menu_on_left_click(false) // left-click opens settings, not the context menu
-        .with_tooltip("Monitor Manager\nStatus: Idle")
-        .with_icon(icon)
+        .with_tooltip(tooltip_text("Idle - waiting for process"))
+        .with_icon(icons.idle.clone())
         .build()
-        .unwrap(),
+        .map_err(|e| AppError::TrayInit(e.to_string()))?,
     );
 
     let menu_channel = MenuEvent::receiver();
     let tray_channel = TrayIconEvent::receiver();
 
     let settings_id = settings_item.id().clone();
+    let disable_id = disable_item.id().clone();
     let restore_id = restore_item.id().clone();
+    let status_id = status_item.id().clone();
+    let retry_id = retry_item.id().clone();
+    let pause_id = pause_item.id().clone();
+    let cursor_id = cursor_item.id().clone();
+    let autostart_id = autostart_item.id().clone();
+    let statistics_id = statistics_item.id().clone();
+    let open_config_id = open_config_item.id().clone();
+    let open_logs_id = open_logs_item.id().clone();
+    let elevate_id = elevate_item.id().clone();
+    let about_id = about_item.id().clone();
     let quit_id = quit_item.id().clone();
 
+    let mut shown_status = String::new();
+    // Pausing can also come in over the command pipe.
+    let mut shown_paused = false;
+    let mut shown_retry = false;
+    // Whether the cursor is confined, and whether it has been let go.
+    let mut shown_cursor = (false, false);
+    // Outer `None` until the first registration attempt.
+    let mut registered_hotkey: Option<Option<String>> = None;
+    let mut config_generation = None;
+
     unsafe {
+        // A thread timer, so GetMessageW returns regularly even when the user
+        // isn't touching the tray.
+        SetTimer(HWND(std::ptr::null_mut()), 0, STATUS_REFRESH_MS, None);
+
         let mut msg = MSG::default();
         while GetMessageW(&mut msg, HWND(std::ptr::null_mut()), 0, 0).as_bool() {
             let _ = TranslateMessage(&msg);
             DispatchMessageW(&msg);
 
+            let (generation, configured_hotkey) = {
+                let state = lock(&state);
+                (state.config_generation, state.config.hotkey.clone())
+            };
+            if config_generation != Some(generation) {
+                config_generation = Some(generation);
+                // Profiles may have been added, renamed or switched.
+                profile_items = refresh_profiles_submenu(&profiles_submenu, &state);
+                if registered_hotkey.as_ref() != Some(&configured_hotkey) {
+                    register_hotkey(&state, configured_hotkey.as_deref());
+                    registered_hotkey = Some(configured_hotkey);
+                }
+            }
+            if msg.message == WM_HOTKEY && msg.wParam.0 as i32 == hotkey::HOTKEY_ID {
+                let disabled = with_manager(&state, |manager| manager.are_monitors_disabled());
+                if disabled {
+                    restore_from_tray(&state);
+                } else {
+                    disable_from_tray(&state);
+                }
+                monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+            }
+
+            while let Ok(event) = status_events.try_recv() {
+                notify_status_event(&state, event);
+            }
+
+            // Stopped from elsewhere, e.g. the session ending; the loop restores.
+            let stopping = { lock(&state).shutdown.is_requested() };
+            if stopping && tray_icon.is_some() {
+                tray_icon.take();
+                PostQuitMessage(0);
+            }
+
+            let show_settings = { lock(&state).show_settings.swap(false, Ordering::Relaxed) };
+            if show_settings {
+                open_settings(&state, &autostart_item);
+            }
+
+            let (current_status, current_icon, paused, restore_pending, can_retry, cursor) = {
+                let state = lock(&state);
+                let restore_pending = matches!(state.watcher, WatcherState::PendingRestore { .. });
+                let can_retry = state.failures.has_pending();
+                let cursor = (state.cursor.device_name.is_some(), state.cursor.released);
+                (status_summary(&state), IconKind::for_state(&state), state.paused, restore_pending, can_retry, cursor)
+            };
+            if cursor != shown_cursor {
+                cursor_item.set_enabled(cursor.0);
+                cursor_item.set_text(if cursor.1 { CONFINE_CURSOR_TEXT } else { RELEASE_CURSOR_TEXT });
+                shown_cursor = cursor;
+            }
+            if can_retry != shown_retry {
+                retry_item.set_enabled(can_retry);
+                shown_retry = can_retry;
+            }
+            if paused != shown_paused {
+                pause_item.set_checked(paused);
+                pause_item.set_text(if paused { RESUME_TEXT } else { PAUSE_TEXT });
+                shown_paused = paused;
+            }
+            if system_events::take_theme_change() {
+                let theme = TaskbarTheme::current();
+                if theme != icons.theme {
+                    log::info!("Taskbar theme changed to {:?}", theme);
+                    icons = TrayIcons::load(theme);
+                    if let Some(tray_icon) = &tray_icon {
+                        let _ = tray_icon.set_icon(Some(icons.get(current_icon).clone()));
+                    }
+                    shown_icon = current_icon;
+                }
+            }
+            if current_icon != shown_icon {
+                if let Some(tray_icon) = &tray_icon {
+                    let _ = tray_icon.set_icon(Some(icons.get(current_icon).clone()));
+                }
+                shown_icon = current_icon;
+            }
+            if current_status != shown_status {
+                // During a restore countdown the status line restores right away.
+                status_item.set_enabled(restore_pending);
+                if restore_pending {
+                    status_item.set_text(format!("📊 {} (click to restore now)", current_status));
+                } else {
+                    status_item.set_text(format!("📊 {}", current_status));
+                }
+                if let Some(tray_icon) = &tray_icon {
+                    let _ = tray_icon.set_tooltip(Some(tooltip_text(&current_status)));
+                }
+                shown_status = current_status;
+            }
+
             while let Ok(event) = menu_channel.try_recv() {
                 if event.id == settings_id {
-                    show_settings_dialog(&state);
-                } else if event.id == restore_id {
-                    let monitor_manager = {
-                        let state = state.lock().unwrap();
-                        state.monitor_manager.clone()
-                    };
-                    let mut manager = monitor_manager.lock().unwrap();
-                    let restored = manager.restore_all_monitors();
-
-                    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
-                    if !restored.is_empty() {
-                        nwg::simple_message(
-                            "Monitors Restored",
-                            &format!("Restored {} monitors.", restored.len()),
-                        );
-                    } else {
-                        nwg::simple_message("Info", "No monitors needed restoration.");
+                    open_settings(&state, &autostart_item);
+                } else if event.id == disable_id {
+                    disable_from_tray(&state);
+                    monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                } else if event.id == restore_id || event.id == status_id {
+                    restore_from_tray(&state);
+                    monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                } else if event.id == retry_id {
+                    retry_from_tray(&state);
+                    monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                } else if let Some((_, name)) = profile_items.iter().find(|(id, _)| *id == event.id) {
+                    if let Err(e) = crate::switch_profile(&state, name) {
+                        notify_user(&state, Severity::Error, "Profile Not Saved", &e.to_string());
                     }
-
-                    refresh_monitors_submenu(&monitors_submenu, &state);
-                } else if event.id == quit_id {
-                    let shutdown = {
-                        let state = state.lock().unwrap();
-                        state.shutdown.clone()
+                    // The click toggled the entry's checkmark, even on the active one.
+                    profile_items = refresh_profiles_submenu(&profiles_submenu, &state);
+                } else if let Some((_, key)) = monitor_items.iter().find(|(id, _)| *id == event.id) {
+                    toggle_monitor(&state, key);
+                    monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                } else if let Some((_, name)) = layout_items.iter().find(|(id, _)| *id == event.id) {
+                    apply_layout(&state, name);
+                    monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                } else if event.id == cursor_id {
+                    let mut state = lock(&state);
+                    state.cursor.released = !state.cursor.released;
+                } else if event.id == statistics_id {
+                    show_statistics();
+                } else if event.id == open_config_id {
+                    open_config_file(&state);
+                } else if event.id == open_logs_id {
+                    open_log_folder(&state);
+                } else if event.id == about_id {
+                    about_dialog::show(&state);
+                } else if event.id == pause_id {
+                    let paused = { lock(&state).paused };
+                    crate::set_paused(&state, !paused);
+                } else if event.id == autostart_id {
+                    // The menu has already flipped the checkmark to the requested state.
+                    let enabled = autostart_item.is_checked();
+                    if let Err(e) = autostart::set_enabled(enabled) {
+                        autostart_item.set_checked(!enabled);
+                        notify_user(&state, Severity::Error, "Start with Windows", &e.to_string());
+                    }
+                } else if event.id == quit_id || (event.id == elevate_id && restart_elevated(&state)) {
+                    let (shutdown, restore_on_exit) = {
+                        let state = lock(&state);
+                        (state.shutdown.clone(), state.config.restore_on_exit)
                     };
-                    shutdown.store(true, Ordering::Relaxed);
+                    shutdown.request();
 
-                    let monitor_manager = {
-                        let state = state.lock().unwrap();
-                        state.monitor_manager.clone()
-                    };
-                    {
-                        let mut manager = monitor_manager.lock().unwrap();
-                        let _ = manager.restore_all_monitors();
+                    if restore_on_exit && with_manager(&state, |manager| manager.has_pending_restore()) {
+                        let status = {
+                            let mut state = lock(&state);
+                            state.status = "Restoring monitors before exit...".to_string();
+                            state.changed_monitors.clear();
+                            status_summary(&state)
+                        };
+                        status_item.set_text(format!("📊 {}", status));
+                        if let Some(tray_icon) = &tray_icon {
+                            let _ = tray_icon.set_tooltip(Some(tooltip_text(&status)));
+                        }
+                        let results = with_manager(&state, |manager| manager.restore_all_monitors());
+                        let results = finish_restore(&state, results);
+                        if let Some(failures) = monitor::describe_failures(&results) {
+                            log::warn!("Some monitors could not be restored on exit: {}", failures);
+                        }
+                        lock(&state).monitoring = false;
                     }
                     tray_icon.take();
                     PostQuitMessage(0);
@@ -106,46 +339,422 @@ pub fn run(state: Arc<Mutex<AppState>>) {
                         button_state: MouseButtonState::Up,
                         ..
                     } => {
-                        show_settings_dialog(&state);
+                        open_settings(&state, &autostart_item);
                     }
                     TrayIconEvent::Click {
                         button: MouseButton::Right,
                         button_state: MouseButtonState::Down,
                         ..
                     } => {
-                        refresh_monitors_submenu(&monitors_submenu, &state);
+                        monitor_items = refresh_monitors_submenu(&monitors_submenu, &state);
+                        // Layouts may have been saved from the command line.
+                        layout_items = refresh_layouts_submenu(&layouts_submenu);
 
-                        let current_status = {
-                            let state = state.lock().unwrap();
-                            state.status.clone()
-                        };
-                        status_item.set_text(format!("📊 Status: {}", current_status));
+                        let baseline_captured_at = with_manager(&state, |manager| manager.baseline_captured_at());
+                        if let Some(captured_at) = baseline_captured_at {
+                            baseline_item.set_text(format!("🕒 Baseline saved at {}", captured_at.format("%H:%M:%S")));
+                        }
                     }
                     _ => {}
                 }
             }
         }
+        hotkey::unregister();
     }
+    Ok(())
 }
 
-fn refresh_monitors_submenu(monitors_submenu: &Submenu, state: &Arc<Mutex<AppState>>) {
-    while monitors_submenu.remove_at(0).is_some() {}
+/// Says why the displays just changed, so they don't go dark unexplained.
+fn notify_status_event(state: &Arc<Mutex<AppState>>, event: StatusEvent) {
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    let (title, message) = match event {
+        StatusEvent::Detected { target, monitors, strategy } => {
+            let message = match strategy {
+                Strategy::Disable => format!("Disabling {} secondary monitor{}", monitors, plural(monitors)),
+                Strategy::SwitchPrimary => "Switching the primary monitor".to_string(),
+                Strategy::ReduceRefreshRate { hz } => {
+                    format!("Setting {} monitor{} to {}Hz", monitors, plural(monitors), hz)
+                }
+                Strategy::ReduceResolution { width, height } => {
+                    format!("Setting {} monitor{} to {}x{}", monitors, plural(monitors), width, height)
+                }
+                Strategy::DdcPowerOff => format!("Turning off {} monitor{}", monitors, plural(monitors)),
+                Strategy::Blank => format!("Blanking {} monitor{}", monitors, plural(monitors)),
+            };
+            (format!("{} detected", program_name(&target)), message)
+        }
+        StatusEvent::Restored { target, monitors } => (
+            "Monitors Restored".to_string(),
+            format!("Restored {} monitor{} after {}", monitors, plural(monitors), program_name(&target)),
+        ),
+    };
+    notify_user(state, Severity::Info, &title, &message);
+}
 
-    let monitors = {
-        let monitor_manager = {
-            let state = state.lock().unwrap();
-            state.monitor_manager.clone()
-        };
-        let manager = monitor_manager.lock().unwrap();
-        manager
-            .get_all_monitors()
-            .into_iter()
-            .filter(|m| m.is_active)
-            .collect::<Vec<_>>()
+/// A target label without the ".exe", e.g. "League of Legends".
+fn program_name(target: &str) -> &str {
+    let len = target.len();
+    if len > 4 && target.is_char_boundary(len - 4) && target[len - 4..].eq_ignore_ascii_case(".exe") {
+        &target[..len - 4]
+    } else {
+        target
+    }
+}
+
+/// Opens the config file in the default editor, writing it out first if it
+/// doesn't exist yet.
+fn open_config_file(state: &Arc<Mutex<AppState>>) {
+    let config = { lock(state).config.clone() };
+    let path = config.path();
+    if !path.exists() {
+        if let Err(e) = config.save() {
+            notify_user(state, Severity::Error, "Config Not Opened", &e.to_string());
+            return;
+        }
+    }
+    if let Err(e) = shell_open(&path) {
+        notify_user(state, Severity::Error, "Config Not Opened", &e);
+    }
+}
+
+/// Opens the log directory in Explorer, creating it if needed.
+fn open_log_folder(state: &Arc<Mutex<AppState>>) {
+    let dir = logging::log_dir();
+    let result = std::fs::create_dir_all(&dir).map_err(|e| e.to_string()).and_then(|()| shell_open(&dir));
+    if let Err(e) = result {
+        notify_user(state, Severity::Error, "Log Folder Not Opened", &e);
+    }
+}
+
+/// Opens `path` with whatever Windows associates with it.
+fn shell_open(path: &Path) -> Result<(), String> {
+    let file = HSTRING::from(path.as_os_str());
+    let result =
+        unsafe { ShellExecuteW(HWND::default(), w!("open"), &file, PCWSTR::null(), PCWSTR::null(), SW_SHOWNORMAL) };
+    // Values up to 32 are error codes.
+    if result.0 as usize <= 32 {
+        return Err(format!("could not open {} (error {})", path.display(), result.0 as usize));
+    }
+    Ok(())
+}
+
+/// Starts an elevated instance to take over. Returns whether it started, in
+/// which case this one exits the usual way.
+fn restart_elevated(state: &Arc<Mutex<AppState>>) -> bool {
+    match elevation::relaunch_elevated() {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Could not restart elevated: {}", e);
+            notify_user(state, Severity::Warning, "Restart Elevated", &e);
+            false
+        }
+    }
+}
+
+/// Shows how many sessions there were and how long they ran.
+fn show_statistics() {
+    let sessions = stats::load();
+    let lines: Vec<String> = stats::summary(&sessions)
+        .iter()
+        .map(|(period, totals)| {
+            let minutes = totals.duration.as_secs() / 60;
+            format!("{}: {} session(s), {}h {:02}m", period, totals.sessions, minutes / 60, minutes % 60)
+        })
+        .collect();
+    nwg::simple_message("Statistics", &lines.join("\n"));
+}
+
+/// The tray's single way into the settings dialog. If one is already open it
+/// is brought to the front instead: a second dialog's event loop would end
+/// the first one's and leave it unresponsive.
+fn open_settings(state: &Arc<Mutex<AppState>>, autostart_item: &CheckMenuItem) {
+    let open = SETTINGS_WINDOW.load(Ordering::Relaxed);
+    if open != 0 {
+        unsafe {
+            let _ = SetForegroundWindow(HWND(open as _));
+        }
+        return;
+    }
+    show_settings_dialog(state);
+    // The dialog has its own Start with Windows checkbox.
+    if let Ok(enabled) = autostart::is_enabled() {
+        autostart_item.set_checked(enabled);
+    }
+}
+
+/// Registers the configured hotkey in place of the previous one, reporting
+/// a bad or taken combination as a notification.
+fn register_hotkey(state: &Arc<Mutex<AppState>>, text: Option<&str>) {
+    hotkey::unregister();
+    let Some(text) = text.filter(|t| !t.trim().is_empty()) else {
+        return;
     };
+    if let Err(e) = hotkey::Hotkey::parse(text).and_then(|hotkey| hotkey.register()) {
+        log::warn!("Hotkey {}: {}", text, e);
+        notify_user(state, Severity::Warning, "Hotkey Not Available", &format!("{}: {}", text, e));
+    }
+}
+
+/// The "Disable Secondary Monitors Now" action, shared with the hotkey.
+fn disable_from_tray(state: &Arc<Mutex<AppState>>) {
+    if crate::is_remote_session() {
+        notify_user(state, Severity::Info, "Monitors Left Alone", "Displays aren't changed over Remote Desktop.");
+        return;
+    }
+    let disabled = crate::disable_now(state);
+    let (failed, status) = {
+        let state = lock(state);
+        (state.failed, state.status.clone())
+    };
+    if failed {
+        notify_user(state, Severity::Warning, "Monitors Partly Disabled", &status);
+    } else {
+        notify_user(state, Severity::Info, "Monitors Disabled", &format!("Disabled {} monitors.", disabled));
+    }
+}
+
+/// The "Re-enable Monitors" action, shared with the hotkey.
+fn restore_from_tray(state: &Arc<Mutex<AppState>>) {
+    let results = crate::restore_now(state);
+    let restored = results.iter().filter(|(_, r)| r.is_ok()).count();
+
+    if let Some(failures) = monitor::describe_failures(&results) {
+        notify_user(
+            state,
+            Severity::Error,
+            "Restore Incomplete",
+            &format!("Restored {} of {} monitors.\n{}", restored, results.len(), failures),
+        );
+    } else if restored > 0 {
+        notify_user(
+            state,
+            Severity::Info,
+            "Monitors Restored",
+            &format!("Restored and verified {} monitors.", restored),
+        );
+    } else {
+        notify_user(state, Severity::Info, "Info", "No monitors needed restoration.");
+    }
+}
+
+/// The "Retry Failed Changes" action.
+fn retry_from_tray(state: &Arc<Mutex<AppState>>) {
+    let results = crate::retry_failed(state);
+    let changed = results.iter().filter(|(_, r)| r.is_ok()).count();
+    if let Some(failures) = monitor::describe_failures(&results) {
+        notify_user(state, Severity::Warning, "Retry Failed", &failures);
+    } else if changed > 0 {
+        notify_user(state, Severity::Info, "Retry Succeeded", &format!("Changed {} monitors.", changed));
+    }
+}
+
+/// Applies a saved layout from the Layouts submenu and reports the outcome.
+fn apply_layout(state: &Arc<Mutex<AppState>>, name: &str) {
+    let layout = name.to_string();
+    let results = match with_manager(state, move |manager| manager.apply_snapshot(&layout)) {
+        Ok(results) => results,
+        Err(e) => {
+            notify_user(state, Severity::Error, "Layout Not Applied", &format!("{}: {}", name, e));
+            return;
+        }
+    };
+    if let Some(failures) = monitor::describe_failures(&results) {
+        notify_user(state, Severity::Warning, "Layout Partly Applied", &format!("{}\n{}", name, failures));
+    } else {
+        notify_user(state, Severity::Info, "Layout Applied", &format!("Applied {}.", name));
+    }
+}
+
+/// Turns a monitor off if it's on and back on if it's off, reporting a
+/// failure as an error notification.
+fn toggle_monitor(state: &Arc<Mutex<AppState>>, key: &str) {
+    let key = key.to_string();
+    let (device_name, result) = with_manager(state, move |manager| {
+        let is_active = manager.get_all_monitors().iter().any(|m| m.is_active && m.matches_id(&key));
+        if is_active {
+            manager.disable_monitor_manually(&key)
+        } else {
+            manager.enable_monitor_manually(&key)
+        }
+    });
+    if let Err(e) = result {
+        notify_user(state, Severity::Error, "Monitor Manager", &format!("Could not change {}: {}", device_name, e));
+    }
+}
+
+fn notify_user(state: &Arc<Mutex<AppState>>, severity: Severity, title: &str, message: &str) {
+    let enabled = { lock(state).config.notifications };
+    notify::notify(enabled, severity, title, message);
+}
+
+/// The target of a .lnk shortcut, or `None` if it can't be read. Runs on the
+/// dialog thread, which `show_settings_dialog` has already set up for COM.
+fn resolve_shortcut(path: &str) -> Option<String> {
+    unsafe {
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).ok()?;
+        link.cast::<IPersistFile>().ok()?.Load(&HSTRING::from(path), STGM_READ).ok()?;
+        let mut buffer = [0u16; 1024];
+        link.GetPath(&mut buffer, std::ptr::null_mut(), 0).ok()?;
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        (len > 0).then(|| String::from_utf16_lossy(&buffer[..len]))
+    }
+}
+
+/// "Monitor Manager\nStatus: ...", cut to what the tray tooltip can show.
+fn tooltip_text(summary: &str) -> String {
+    truncate(&format!("{}{}", TOOLTIP_TITLE, summary), TOOLTIP_MAX_CHARS)
+}
+
+/// What the status menu item and the tooltip show, so the two never
+/// disagree: the profile and, while a target runs, the target and the
+/// monitors changed for it; otherwise the loop's status. Short enough for the
+/// tooltip.
+fn status_summary(state: &AppState) -> String {
+    let max_chars = TOOLTIP_MAX_CHARS - TOOLTIP_TITLE.chars().count();
+    let profile = &state.config.active_profile;
+    if !state.monitoring || state.failed || state.changed_monitors.is_empty() {
+        return truncate(&format!("{}: {}", profile, state.status), max_chars);
+    }
+    let verb = match state.config.profile().strategy {
+        Strategy::Disable | Strategy::SwitchPrimary => "disabled",
+        Strategy::ReduceRefreshRate { .. } | Strategy::ReduceResolution { .. } => "changed",
+        Strategy::DdcPowerOff => "turned off",
+        Strategy::Blank => "blanked",
+    };
+    let count = state.changed_monitors.len();
+    let noun = if count == 1 { "monitor" } else { "monitors" };
+    // Monitors disabled from the tray aren't for a target.
+    let head = if state.watcher.holds_monitors() && !state.last_target.is_empty() {
+        format!("{} · {}: {} {} {}", profile, state.last_target, count, noun, verb)
+    } else {
+        format!("{}: {} {} {}", profile, count, noun, verb)
+    };
+    let budget = max_chars.saturating_sub(head.chars().count() + " ()".len());
+    truncate(&format!("{} ({})", head, fit_names(&state.changed_monitors, budget)), max_chars)
+}
+
+/// `names` joined within `budget` characters: in full if they fit, then
+/// each cut short, then as many as fit and a count of the rest.
+fn fit_names(names: &[String], budget: usize) -> String {
+    for max_len in [usize::MAX, MONITOR_NAME_MAX_CHARS] {
+        let joined = names.iter().map(|name| truncate(name, max_len)).collect::<Vec<_>>().join(", ");
+        if joined.chars().count() <= budget {
+            return joined;
+        }
+    }
+    let mut shown: Vec<String> = Vec::new();
+    for name in names {
+        let mut candidate = shown.clone();
+        candidate.push(truncate(name, MONITOR_NAME_MAX_CHARS));
+        let rest = names.len() - candidate.len();
+        let text = format!("{}, +{} more", candidate.join(", "), rest);
+        if text.chars().count() > budget {
+            break;
+        }
+        shown = candidate;
+    }
+    if shown.is_empty() {
+        return format!("+{} more", names.len());
+    }
+    format!("{}, +{} more", shown.join(", "), names.len() - shown.len())
+}
+
+/// `text` cut to `max_chars` characters, ending in "…" if anything was cut.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+// nwg's ListView has no checkbox support, so these talk to the control directly.
+// The checkbox is the item's state image: 1 is unchecked, 2 is checked.
+
+fn list_view_hwnd(list: &nwg::ListView) -> HWND {
+    HWND(list.handle.hwnd().map_or(std::ptr::null_mut(), |hwnd| hwnd as _))
+}
+
+fn enable_list_view_checkboxes(list: &nwg::ListView) {
+    let style = LVS_EX_CHECKBOXES as usize;
+    unsafe {
+        SendMessageW(list_view_hwnd(list), LVM_SETEXTENDEDLISTVIEWSTYLE, WPARAM(style), LPARAM(style as isize));
+    }
+}
+
+fn set_list_view_checked(list: &nwg::ListView, row: usize, checked: bool) {
+    let item = LVITEMW {
+        stateMask: LVIS_STATEIMAGEMASK,
+        state: LIST_VIEW_ITEM_STATE_FLAGS(if checked { 2 } else { 1 } << 12),
+        ..Default::default()
+    };
+    unsafe {
+        SendMessageW(list_view_hwnd(list), LVM_SETITEMSTATE, WPARAM(row), LPARAM(&item as *const _ as isize));
+    }
+}
+
+fn list_view_checked(list: &nwg::ListView, row: usize) -> bool {
+    let state = unsafe {
+        SendMessageW(list_view_hwnd(list), LVM_GETITEMSTATE, WPARAM(row), LPARAM(LVIS_STATEIMAGEMASK.0 as isize))
+    };
+    (state.0 as u32 & LVIS_STATEIMAGEMASK.0) >> 12 == 2
+}
+
+/// Rebuilds the Profiles submenu with one entry per profile, checked on the
+/// active one. Returns each entry's menu ID with the profile name.
+fn refresh_profiles_submenu(profiles_submenu: &Submenu, state: &Arc<Mutex<AppState>>) -> Vec<(MenuId, String)> {
+    while profiles_submenu.remove_at(0).is_some() {}
+
+    let (names, active) = {
+        let state = lock(state);
+        let names: Vec<String> = state.config.profiles.iter().map(|p| p.name.clone()).collect();
+        (names, state.config.profile().name.clone())
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let item = CheckMenuItem::new(&name, true, name == active, None);
+            let _ = profiles_submenu.append(&item);
+            (item.id().clone(), name)
+        })
+        .collect()
+}
+
+/// Rebuilds the Layouts submenu with one entry per saved layout. Returns each
+/// entry's menu ID with the layout name.
+fn refresh_layouts_submenu(layouts_submenu: &Submenu) -> Vec<(MenuId, String)> {
+    while layouts_submenu.remove_at(0).is_some() {}
+
+    let names = MonitorManager::layout_names(&config::config_dir());
+    if names.is_empty() {
+        let hint = MenuItem::new("Save one with: monitor-manager snapshot save NAME", false, None);
+        let _ = layouts_submenu.append(&hint);
+        return Vec::new();
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let item = MenuItem::new(&name, true, None);
+            let _ = layouts_submenu.append(&item);
+            (item.id().clone(), name)
+        })
+        .collect()
+}
+
+/// Rebuilds the Monitors submenu with one checkable entry per monitor,
+/// checked while it's active. Returns each entry's menu ID with the key of
+/// the monitor it toggles.
+fn refresh_monitors_submenu(monitors_submenu: &Submenu, state: &Arc<Mutex<AppState>>) -> Vec<(MenuId, String)> {
+    while monitors_submenu.remove_at(0).is_some() {}
+
+    let monitors: Vec<_> = with_manager(state, |manager| manager.get_all_monitors())
+        .into_iter()
+        .filter(|m| m.is_physical())
+        .collect();
 
     let header = MenuItem::new(
-        format!("Total Monitors: {}", monitors.len()),
+        format!("Total Monitors: {}", monitors.iter().filter(|m| m.is_active).count()),
         false,
         None,
     );
@@ -155,7 +764,7 @@ fn refresh_monitors_submenu(monitors_submenu: &Submenu, state: &Arc<Mutex<AppSta
     if monitors.is_empty() {
         let empty = MenuItem::new("No monitors detected", false, None);
         let _ = monitors_submenu.append(&empty);
-        return;
+        return Vec::new();
     }
 
     let mut monitors_sorted = monitors;
@@ -165,15 +774,111 @@ fn refresh_monitors_submenu(monitors_submenu: &Submenu, state: &Arc<Mutex<AppSta
             .then_with(|| a.description.to_lowercase().cmp(&b.description.to_lowercase()))
     });
 
-    for monitor in monitors_sorted {
-        let role = if monitor.is_primary { "PRIMARY" } else { "Secondary" };
-        let text = format!("• {} ({})", monitor.description, role);
-        let item = MenuItem::new(text, false, None);
-        let _ = monitors_submenu.append(&item);
+    monitors_sorted
+        .into_iter()
+        .map(|monitor| {
+            // The primary can't be detached.
+            let item = CheckMenuItem::new(monitor.label(), !monitor.is_primary, monitor.is_active, None);
+            let _ = monitors_submenu.append(&item);
+            (item.id().clone(), monitor.key().to_string())
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IconKind {
+    Idle,
+    Active,
+    Error,
+}
+
+impl IconKind {
+    fn for_state(state: &AppState) -> Self {
+        if state.failed {
+            Self::Error
+        } else if state.monitoring {
+            Self::Active
+        } else {
+            Self::Idle
+        }
     }
 }
 
-fn load_icon_from_file(path: &str) -> TrayIconImage {
+/// Whether the taskbar, and so the tray, is light or dark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskbarTheme {
+    Light,
+    Dark,
+}
+
+impl TaskbarTheme {
+    /// Windows' own setting; dark when it can't be read, as before Windows 10
+    /// 1903 added the light taskbar.
+    fn current() -> Self {
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                w!(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+                w!("SystemUsesLightTheme"),
+                RRF_RT_REG_DWORD,
+                None,
+                Some(&mut value as *mut u32 as *mut std::ffi::c_void),
+                Some(&mut size),
+            )
+        };
+        if status.is_ok() && value != 0 {
+            Self::Light
+        } else {
+            Self::Dark
+        }
+    }
+
+    /// A dark rim stands out on a light taskbar and a light one on a dark taskbar.
+    fn outline(self) -> [u8; 3] {
+        match self {
+            Self::Light => [32, 32, 32],
+            Self::Dark => [240, 240, 240],
+        }
+    }
+}
+
+struct TrayIcons {
+    theme: TaskbarTheme,
+    idle: TrayIconImage,
+    active: TrayIconImage,
+    error: TrayIconImage,
+}
+
+impl TrayIcons {
+    /// Loads the `.ico` files next to the exe for `theme`, `icon_light.ico`
+    /// and so on on a light taskbar, drawing a colored circle for any that
+    /// are missing.
+    fn load(theme: TaskbarTheme) -> Self {
+        let suffix = match theme {
+            TaskbarTheme::Light => "_light",
+            TaskbarTheme::Dark => "",
+        };
+        let outline = theme.outline();
+        Self {
+            theme,
+            idle: load_icon_from_file(&format!("icon{}.ico", suffix), [0, 120, 212], outline),
+            active: load_icon_from_file(&format!("icon_active{}.ico", suffix), [16, 160, 72], outline),
+            error: load_icon_from_file(&format!("icon_error{}.ico", suffix), [212, 48, 48], outline),
+        }
+    }
+
+    fn get(&self, kind: IconKind) -> &TrayIconImage {
+        match kind {
+            IconKind::Idle => &self.idle,
+            IconKind::Active => &self.active,
+            IconKind::Error => &self.error,
+        }
+    }
+}
+
+fn load_icon_from_file(path: &str, fallback_color: [u8; 3], outline: [u8; 3]) -> TrayIconImage {
     if Path::new(path).exists() {
         if let Ok(img) = image::open(path) {
             let rgba = img.to_rgba8();
@@ -186,10 +891,10 @@ fn load_icon_from_file(path: &str) -> TrayIconImage {
         }
     }
 
-    create_default_icon()
+    create_icon(fallback_color, outline)
 }
 
-fn create_default_icon() -> TrayIconImage {
+fn create_icon(fill: [u8; 3], outline: [u8; 3]) -> TrayIconImage {
     let mut rgba = vec![0u8; 64 * 64 * 4];
 
     for y in 0..64 {
@@ -199,21 +904,59 @@ fn create_default_icon() -> TrayIconImage {
             let distance = (dx * dx + dy * dy).sqrt();
             
             if distance < 28.0 {
+                let [r, g, b] = if distance >= 24.0 { outline } else { fill };
                 let idx = (y * 64 + x) * 4;
-                rgba[idx] = 0;      // R
-                rgba[idx + 1] = 120; // G
-                rgba[idx + 2] = 212; // B
-                rgba[idx + 3] = 255; // A
+                rgba[idx] = r;
+                rgba[idx + 1] = g;
+                rgba[idx + 2] = b;
+                rgba[idx + 3] = 255;
             }
         }
     }
     
     TrayIconImage::from_rgba(rgba, 64, 64).expect("Failed to create icon")
 }
+/// `trigger_combo` entries.
+const TRIGGER_CHOICES: [&str; 4] =
+    ["Target programs run", "Any app goes fullscreen", "Fullscreen or borderless", "A window title contains"];
+
+const SCHEDULE_WEEK: [Weekday; 7] =
+    [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+/// `schedule_days_combo` entries and the days each one covers.
+const SCHEDULE_DAY_CHOICES: [(&str, &[Weekday]); 3] = [
+    ("Every day", &SCHEDULE_WEEK),
+    ("Weekdays", &[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]),
+    ("Weekends", &[Weekday::Sat, Weekday::Sun]),
+];
+/// Offered after the presets when the config file sets days differently.
+const CUSTOM_SCHEDULE_LABEL: &str = "As set in the config file";
+const DEFAULT_SCHEDULE_WINDOW: TimeWindow = TimeWindow {
+    start: TimeOfDay::from_minutes(18 * 60),
+    end: TimeOfDay::from_minutes(23 * 60),
+};
+
+/// Every half hour of the day, plus `time` if it falls between them.
+fn time_choices(time: TimeOfDay) -> Vec<TimeOfDay> {
+    let mut choices: Vec<TimeOfDay> = (0..48).map(|half_hour| TimeOfDay::from_minutes(half_hour * 30)).collect();
+    if let Err(idx) = choices.binary_search(&time) {
+        choices.insert(idx, time);
+    }
+    choices
+}
+
+/// Progress of a game scan started from the settings dialog.
+#[derive(Default)]
+struct GameScan {
+    done: usize,
+    total: usize,
+    /// `Some` once the scan has finished.
+    games: Option<Vec<games::FoundGame>>,
+}
+
 #[derive(Default, NwgUi)]
 pub struct SettingsDialog {
-    #[nwg_control(size: (740, 440), position: (300, 300), title: "Monitor Manager Settings", flags: "WINDOW|VISIBLE", icon: Some(&data.window_icon))]
-    #[nwg_events( OnWindowClose: [SettingsDialog::close] )]
+    #[nwg_control(size: (740, 1010), position: (300, 300), title: "Monitor Manager Settings", flags: "WINDOW|VISIBLE", accept_files: true, icon: Some(&data.window_icon))]
+    #[nwg_events( OnWindowClose: [SettingsDialog::close], OnFileDrop: [SettingsDialog::files_dropped(SELF, EVT_DATA)] )]
     window: nwg::Window,
 
     #[nwg_resource(source_bin: Some(include_bytes!("../icon.ico")))]
@@ -235,46 +978,237 @@ pub struct SettingsDialog {
     #[nwg_layout_item(layout: layout, row: 0, col: 0, col_span: 6)]
     title_label: nwg::Label,
 
-    #[nwg_control(text: "🎯 Target executable", font: Some(&data.section_font))]
-    #[nwg_layout_item(layout: layout, row: 1, col: 0, col_span: 6)]
+    #[nwg_control(text: "Profile:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 0)]
+    profile_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 1)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::profile_changed] )]
+    profile_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 2)]
+    profile_name_input: nwg::TextInput,
+
+    #[nwg_control(text: "New", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 3)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::new_profile] )]
+    new_profile_button: nwg::Button,
+
+    #[nwg_control(text: "Rename", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 4)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::rename_profile] )]
+    rename_profile_button: nwg::Button,
+
+    #[nwg_control(text: "Delete", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 1, col: 5)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::delete_profile] )]
+    delete_profile_button: nwg::Button,
+
+    #[nwg_control(text: "Trigger:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 2, col: 0)]
+    trigger_label: nwg::Label,
+
+    #[nwg_control(collection: TRIGGER_CHOICES.to_vec(), selected_index: Some(0), font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 2, col: 1, col_span: 2)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::trigger_changed] )]
+    trigger_combo: nwg::ComboBox<&'static str>,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 2, col: 3, col_span: 3)]
+    window_title_input: nwg::TextInput,
+
+    #[nwg_control(text: "🎯 Target executables (paths, or glob:/regex: patterns)", font: Some(&data.section_font))]
+    #[nwg_layout_item(layout: layout, row: 3, col: 0, col_span: 6)]
     target_header: nwg::Label,
 
+    #[nwg_control(size: (600, 90), font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 4, col: 0, col_span: 5, row_span: 3)]
+    targets_list: nwg::ListBox<String>,
+
+    #[nwg_control(text: "Remove", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 4, col: 5)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::remove_target] )]
+    remove_button: nwg::Button,
+
     #[nwg_control(text: "", readonly: false, font: Some(&data.ui_font))]
-    #[nwg_layout_item(layout: layout, row: 2, col: 0, col_span: 5)]
+    #[nwg_layout_item(layout: layout, row: 7, col: 0, col_span: 3)]
     path_input: nwg::TextInput,
 
+    #[nwg_control(text: "Running…", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 7, col: 3)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::pick_process] )]
+    pick_process_button: nwg::Button,
+
     #[nwg_control(text: "Browse…", font: Some(&data.ui_font), size: (120, 28))]
-    #[nwg_layout_item(layout: layout, row: 2, col: 5)]
+    #[nwg_layout_item(layout: layout, row: 7, col: 4)]
     #[nwg_events( OnButtonClick: [SettingsDialog::browse] )]
     browse_button: nwg::Button,
 
+    #[nwg_control(text: "Add", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 7, col: 5)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::add_target] )]
+    add_button: nwg::Button,
+
+    #[nwg_control(text: "Suggestions:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 0)]
+    suggestions_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 1, col_span: 3)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::suggestion_picked] )]
+    suggestions_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(range: 0..1)]
+    #[nwg_layout_item(layout: layout, row: 8, col: 4)]
+    scan_progress: nwg::ProgressBar,
+
+    #[nwg_control(text: "Find Games", font: Some(&data.ui_font), size: (120, 28))]
+    #[nwg_layout_item(layout: layout, row: 8, col: 5)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::find_games] )]
+    find_games_button: nwg::Button,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [SettingsDialog::scan_updated] )]
+    scan_notice: nwg::Notice,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 9, col: 0, col_span: 6)]
+    target_warning_label: nwg::Label,
+
+    #[nwg_control(text: "Match by:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 10, col: 0)]
+    match_mode_label: nwg::Label,
+
+    #[nwg_control(collection: vec!["Full path", "File name"], selected_index: Some(0), font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 10, col: 1, col_span: 2)]
+    match_mode_combo: nwg::ComboBox<&'static str>,
+
+    #[nwg_control(text: "Poll interval (ms):", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 10, col: 3)]
+    poll_interval_label: nwg::Label,
+
+    #[nwg_control(text: "", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 10, col: 4)]
+    poll_interval_input: nwg::TextInput,
+
+    #[nwg_control(text: "Only in front", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 10, col: 5)]
+    require_foreground_check: nwg::CheckBox,
+
+    #[nwg_control(text: "Keep enabled:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 11, col: 0)]
+    keep_enabled_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 11, col: 1, col_span: 4)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::keep_enabled_changed] )]
+    keep_enabled_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "Turn on HDR", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 11, col: 5)]
+    enable_hdr_check: nwg::CheckBox,
+
+    #[nwg_control(text: "Strategy:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 12, col: 0)]
+    strategy_label: nwg::Label,
+
+    #[nwg_control(
+        collection: vec!["Disable monitors", "Switch primary", "Reduce refresh rate", "Reduce resolution", "Power off (DDC/CI)", "Black overlay"],
+        selected_index: Some(0),
+        font: Some(&data.ui_font)
+    )]
+    #[nwg_layout_item(layout: layout, row: 12, col: 1, col_span: 2)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::strategy_changed] )]
+    strategy_combo: nwg::ComboBox<&'static str>,
+
+    #[nwg_control(text: "Mode:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 12, col: 3)]
+    strategy_mode_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 12, col: 4, col_span: 2)]
+    strategy_mode_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "Only between", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 13, col: 0)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::schedule_changed] )]
+    schedule_check: nwg::CheckBox,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 13, col: 1)]
+    schedule_start_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "and", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 13, col: 2)]
+    schedule_and_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 13, col: 3)]
+    schedule_end_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 13, col: 4, col_span: 2)]
+    #[nwg_events( OnComboxBoxSelection: [SettingsDialog::schedule_changed] )]
+    schedule_days_combo: nwg::ComboBox<&'static str>,
+
+    #[nwg_control(text: "Audio output:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 14, col: 0)]
+    audio_device_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 14, col: 1, col_span: 5)]
+    audio_device_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "Power plan:", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 15, col: 0)]
+    power_scheme_label: nwg::Label,
+
+    #[nwg_control(font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 15, col: 1, col_span: 5)]
+    power_scheme_combo: nwg::ComboBox<String>,
+
     #[nwg_control(text: "📊 Status", font: Some(&data.section_font))]
-    #[nwg_layout_item(layout: layout, row: 3, col: 0, col_span: 6)]
+    #[nwg_layout_item(layout: layout, row: 16, col: 0, col_span: 6)]
     status_header: nwg::Label,
 
     #[nwg_control(text: "", readonly: true, font: Some(&data.ui_font))]
-    #[nwg_layout_item(layout: layout, row: 4, col: 0, col_span: 6)]
+    #[nwg_layout_item(layout: layout, row: 17, col: 0, col_span: 6)]
     status_value: nwg::TextInput,
 
-    #[nwg_control(text: "🖥️ Monitors", font: Some(&data.section_font))]
-    #[nwg_layout_item(layout: layout, row: 5, col: 0, col_span: 6)]
+    #[nwg_control(text: "🖥️ Monitors (checked ones may be turned off)", font: Some(&data.section_font))]
+    #[nwg_layout_item(layout: layout, row: 18, col: 0, col_span: 6)]
     monitors_header: nwg::Label,
 
-    #[nwg_control(size: (720, 160), font: Some(&data.ui_font))]
-    #[nwg_layout_item(layout: layout, row: 6, col: 0, col_span: 6, row_span: 4)]
-    monitors_list: nwg::ListBox<String>,
+    #[nwg_control(size: (720, 160), list_style: nwg::ListViewStyle::Detailed, ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT)]
+    #[nwg_layout_item(layout: layout, row: 19, col: 0, col_span: 6, row_span: 3)]
+    monitors_list: nwg::ListView,
 
-    #[nwg_control(text: "", font: Some(&data.ui_font))]
-    #[nwg_layout_item(layout: layout, row: 10, col: 0, col_span: 4)]
-    footer_spacer: nwg::Label,
+    #[nwg_control(text: "🚫 Never touch (checked ones are always left alone)", font: Some(&data.section_font))]
+    #[nwg_layout_item(layout: layout, row: 22, col: 0, col_span: 6)]
+    excluded_header: nwg::Label,
+
+    #[nwg_control(size: (720, 120), list_style: nwg::ListViewStyle::Detailed, ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT)]
+    #[nwg_layout_item(layout: layout, row: 23, col: 0, col_span: 6, row_span: 3)]
+    excluded_list: nwg::ListView,
+
+    #[nwg_control(text: "Start with Windows", font: Some(&data.ui_font))]
+    #[nwg_layout_item(layout: layout, row: 26, col: 0, col_span: 3)]
+    start_with_windows_check: nwg::CheckBox,
+
+    #[nwg_control(text: "Apply", font: Some(&data.ui_font), size: (110, 30))]
+    #[nwg_layout_item(layout: layout, row: 26, col: 3)]
+    #[nwg_events( OnButtonClick: [SettingsDialog::apply] )]
+    apply_button: nwg::Button,
 
     #[nwg_control(text: "Save", font: Some(&data.ui_font), size: (110, 30))]
-    #[nwg_layout_item(layout: layout, row: 10, col: 4)]
+    #[nwg_layout_item(layout: layout, row: 26, col: 4)]
     #[nwg_events( OnButtonClick: [SettingsDialog::save] )]
     save_button: nwg::Button,
 
     #[nwg_control(text: "Cancel", font: Some(&data.ui_font), size: (110, 30))]
-    #[nwg_layout_item(layout: layout, row: 10, col: 5)]
+    #[nwg_layout_item(layout: layout, row: 26, col: 5)]
     #[nwg_events( OnButtonClick: [SettingsDialog::close] )]
     cancel_button: nwg::Button,
 
@@ -282,9 +1216,307 @@ pub struct SettingsDialog {
     file_dialog: nwg::FileDialog,
 
     state: RefCell<Option<Arc<Mutex<AppState>>>>,
+
+    /// Every profile with the dialog's edits; the one at `profile_idx` is
+    /// shown and only written back by `store_profile`.
+    profiles: RefCell<Vec<Profile>>,
+    profile_idx: Cell<usize>,
+
+    /// Filled in by the game scan thread, which pokes `scan_notice` on
+    /// every change.
+    scan: Arc<Mutex<GameScan>>,
+    /// Exe for each `suggestions_combo` entry.
+    suggestion_paths: RefCell<Vec<PathBuf>>,
+
+    /// `keep_enabled` value for each `keep_enabled_combo` entry; `None` is the primary.
+    keep_enabled_keys: RefCell<Vec<Option<String>>>,
+    /// Whether the monitor behind each `keep_enabled_combo` entry can do HDR.
+    keep_enabled_hdr: RefCell<Vec<bool>>,
+    /// `audio_device` value for each `audio_device_combo` entry; `None` leaves audio alone.
+    audio_device_ids: RefCell<Vec<Option<String>>>,
+    /// `power_scheme` value for each `power_scheme_combo` entry; `None` leaves the plan alone.
+    power_scheme_guids: RefCell<Vec<Option<String>>>,
+
+    /// Refresh rates and resolutions the secondary monitors support, offered
+    /// in `strategy_mode_combo` for the matching strategies.
+    refresh_choices: RefCell<Vec<u32>>,
+    resolution_choices: RefCell<Vec<(u32, u32)>>,
+    current_strategy: RefCell<Strategy>,
+    /// The schedule as loaded, kept when its days don't fit a
+    /// `schedule_days_combo` choice.
+    loaded_schedule: RefCell<Option<Schedule>>,
+    /// `managed_monitors` key for each `monitors_list` row.
+    monitor_keys: RefCell<Vec<String>>,
+    /// `managed_monitors` as loaded, so saving with every row checked keeps
+    /// it unset and monitors connected later stay eligible.
+    managed_monitors: RefCell<Option<Vec<String>>>,
+    /// `excluded_monitors` key for each `excluded_list` row.
+    excluded_keys: RefCell<Vec<String>>,
+
+    /// Warnings already shown for the current targets; a second Save with the
+    /// same warnings saves anyway.
+    shown_warnings: RefCell<Vec<String>>,
+
+    /// Whether start-with-Windows was on when the dialog opened.
+    autostart_enabled: RefCell<Option<bool>>,
 }
 
 impl SettingsDialog {
+    fn profile_changed(&self) {
+        let Some(idx) = self.profile_combo.selection() else {
+            return;
+        };
+        self.store_profile();
+        self.show_profile(idx);
+    }
+
+    fn new_profile(&self) {
+        let Some(name) = self.typed_profile_name(None) else {
+            return;
+        };
+        self.store_profile();
+        let idx = {
+            let mut profiles = self.profiles.borrow_mut();
+            profiles.push(Profile::new(name));
+            profiles.len() - 1
+        };
+        self.show_profile(idx);
+    }
+
+    fn rename_profile(&self) {
+        let idx = self.profile_idx.get();
+        let Some(name) = self.typed_profile_name(Some(idx)) else {
+            return;
+        };
+        self.store_profile();
+        self.profiles.borrow_mut()[idx].name = name;
+        self.show_profile(idx);
+    }
+
+    fn delete_profile(&self) {
+        let idx = self.profile_idx.get();
+        let remaining = {
+            let mut profiles = self.profiles.borrow_mut();
+            if profiles.len() <= 1 {
+                drop(profiles);
+                self.target_warning_label.set_text("⚠ The last profile can't be deleted");
+                return;
+            }
+            profiles.remove(idx);
+            profiles.len()
+        };
+        self.show_profile(idx.min(remaining - 1));
+    }
+
+    /// The name in `profile_name_input`, unless it's empty or taken by a
+    /// profile other than the one at `renaming`.
+    fn typed_profile_name(&self, renaming: Option<usize>) -> Option<String> {
+        let name = self.profile_name_input.text().trim().to_string();
+        let taken = self
+            .profiles
+            .borrow()
+            .iter()
+            .enumerate()
+            .any(|(idx, profile)| Some(idx) != renaming && profile.name.eq_ignore_ascii_case(&name));
+        let problem = if name.is_empty() {
+            "Type a name for the profile first"
+        } else if taken {
+            "A profile with that name already exists"
+        } else {
+            return Some(name);
+        };
+        self.target_warning_label.set_text(&format!("⚠ {}", problem));
+        None
+    }
+
+    /// Copies the targets, match mode, kept monitor and strategy on screen
+    /// into the current profile.
+    fn store_profile(&self) {
+        // Pick up a path that was typed or browsed but not explicitly added.
+        self.add_target();
+        let keep_enabled = self
+            .keep_enabled_combo
+            .selection()
+            .and_then(|idx| self.keep_enabled_keys.borrow().get(idx).cloned())
+            .flatten();
+        let audio_device = self
+            .audio_device_combo
+            .selection()
+            .and_then(|idx| self.audio_device_ids.borrow().get(idx).cloned())
+            .flatten();
+        let power_scheme = self
+            .power_scheme_combo
+            .selection()
+            .and_then(|idx| self.power_scheme_guids.borrow().get(idx).cloned())
+            .flatten();
+        let mut profiles = self.profiles.borrow_mut();
+        let Some(profile) = profiles.get_mut(self.profile_idx.get()) else {
+            return;
+        };
+        profile.targets = self.targets_list.collection().clone();
+        profile.match_mode = match self.match_mode_combo.selection() {
+            Some(1) => MatchMode::FileName,
+            _ => MatchMode::FullPath,
+        };
+        profile.keep_enabled = keep_enabled;
+        profile.audio_device = audio_device;
+        profile.power_scheme = power_scheme;
+        profile.strategy = self.selected_strategy();
+        profile.require_foreground = self.require_foreground_check.check_state() == nwg::CheckBoxState::Checked;
+        profile.enable_hdr = self.enable_hdr_check.check_state() == nwg::CheckBoxState::Checked;
+        let window_title = self.window_title_input.text().trim().to_string();
+        profile.trigger = match self.trigger_combo.selection() {
+            Some(1) => Trigger::Fullscreen { borderless: false },
+            Some(2) => Trigger::Fullscreen { borderless: true },
+            Some(3) if !window_title.is_empty() => Trigger::WindowTitle(window_title),
+            _ => Trigger::Processes,
+        };
+    }
+
+    /// HDR can only be turned on for a kept monitor that supports it.
+    fn keep_enabled_changed(&self) {
+        let supported = self
+            .keep_enabled_combo
+            .selection()
+            .and_then(|idx| self.keep_enabled_hdr.borrow().get(idx).copied())
+            .unwrap_or(false);
+        self.enable_hdr_check.set_enabled(supported);
+    }
+
+    /// The title box only applies to the window title trigger.
+    fn trigger_changed(&self) {
+        self.window_title_input.set_enabled(self.trigger_combo.selection() == Some(3));
+    }
+
+    /// Shows the profile at `idx`, discarding whatever is on screen.
+    fn show_profile(&self, idx: usize) {
+        let (names, profile) = {
+            let profiles = self.profiles.borrow();
+            (profiles.iter().map(|p| p.name.clone()).collect(), profiles[idx].clone())
+        };
+        self.profile_idx.set(idx);
+        self.profile_combo.set_collection(names);
+        self.profile_combo.set_selection(Some(idx));
+        self.profile_name_input.set_text(&profile.name);
+
+        self.targets_list.set_collection(profile.targets);
+        self.match_mode_combo.set_selection(Some(match profile.match_mode {
+            MatchMode::FullPath => 0,
+            MatchMode::FileName => 1,
+        }));
+        let selected = self
+            .keep_enabled_keys
+            .borrow()
+            .iter()
+            .position(|key| *key == profile.keep_enabled)
+            .unwrap_or(0);
+        self.keep_enabled_combo.set_selection(Some(selected));
+        self.enable_hdr_check.set_check_state(if profile.enable_hdr {
+            nwg::CheckBoxState::Checked
+        } else {
+            nwg::CheckBoxState::Unchecked
+        });
+        self.keep_enabled_changed();
+        let selected = self
+            .audio_device_ids
+            .borrow()
+            .iter()
+            .position(|id| *id == profile.audio_device)
+            .unwrap_or(0);
+        self.audio_device_combo.set_selection(Some(selected));
+        let selected = self
+            .power_scheme_guids
+            .borrow()
+            .iter()
+            .position(|guid| match (guid, &profile.power_scheme) {
+                (Some(guid), Some(wanted)) => guid.eq_ignore_ascii_case(wanted),
+                (guid, wanted) => guid == wanted,
+            })
+            .unwrap_or(0);
+        self.power_scheme_combo.set_selection(Some(selected));
+        self.trigger_combo.set_selection(Some(match profile.trigger {
+            Trigger::Processes => 0,
+            Trigger::Fullscreen { borderless: false } => 1,
+            Trigger::Fullscreen { borderless: true } => 2,
+            Trigger::WindowTitle(_) => 3,
+        }));
+        self.window_title_input.set_text(match &profile.trigger {
+            Trigger::WindowTitle(title) => title,
+            _ => "",
+        });
+        self.trigger_changed();
+        self.require_foreground_check.set_check_state(if profile.require_foreground {
+            nwg::CheckBoxState::Checked
+        } else {
+            nwg::CheckBoxState::Unchecked
+        });
+        self.strategy_combo.set_selection(Some(match profile.strategy {
+            Strategy::Disable => 0,
+            Strategy::SwitchPrimary => 1,
+            Strategy::ReduceRefreshRate { .. } => 2,
+            Strategy::ReduceResolution { .. } => 3,
+            Strategy::DdcPowerOff => 4,
+            Strategy::Blank => 5,
+        }));
+        *self.current_strategy.borrow_mut() = profile.strategy;
+        self.strategy_changed();
+
+        self.shown_warnings.borrow_mut().clear();
+        self.target_warning_label.set_text("");
+        self.save_button.set_text("Save");
+    }
+
+    /// Scans for installed games on another thread; `scan_updated` shows
+    /// the progress and the result.
+    fn find_games(&self) {
+        self.find_games_button.set_enabled(false);
+        self.scan_progress.set_pos(0);
+        *lock(&self.scan) = GameScan::default();
+        let scan = Arc::clone(&self.scan);
+        let notice = self.scan_notice.sender();
+        thread::spawn(move || {
+            let games = games::scan(|done, total| {
+                let mut scan = lock(&scan);
+                scan.done = done;
+                scan.total = total;
+                drop(scan);
+                notice.notice();
+            });
+            lock(&scan).games = Some(games);
+            notice.notice();
+        });
+    }
+
+    fn scan_updated(&self) {
+        let (done, total, games) = {
+            let mut scan = lock(&self.scan);
+            (scan.done, scan.total, scan.games.take())
+        };
+        self.scan_progress.set_range(0..total.max(1) as u32);
+        self.scan_progress.set_pos(done as u32);
+        let Some(games) = games else {
+            return;
+        };
+        self.find_games_button.set_enabled(true);
+        let labels = if games.is_empty() {
+            vec!["No games found".to_string()]
+        } else {
+            games.iter().map(|game| format!("{}  —  {}", game.name, game.exe.display())).collect()
+        };
+        self.suggestions_combo.set_collection(labels);
+        *self.suggestion_paths.borrow_mut() = games.into_iter().map(|game| game.exe).collect();
+    }
+
+    fn suggestion_picked(&self) {
+        let path = self
+            .suggestions_combo
+            .selection()
+            .and_then(|idx| self.suggestion_paths.borrow().get(idx).cloned());
+        if let Some(path) = path {
+            self.path_input.set_text(&path.to_string_lossy());
+        }
+    }
+
     fn browse(&self) {
         if self.file_dialog.run(Some(&self.window)) {
             if let Ok(path) = self.file_dialog.get_selected_item() {
@@ -293,15 +1525,297 @@ impl SettingsDialog {
         }
     }
 
+    /// Like `browse`, but picks from the exes of running processes.
+    fn pick_process(&self) {
+        if let Some(path) = process_picker::pick(&self.window) {
+            self.path_input.set_text(&path.to_string_lossy());
+        }
+    }
+
+    /// A single dropped exe or shortcut fills `path_input` like `browse`;
+    /// several are added to the target list directly.
+    fn files_dropped(&self, data: &nwg::EventData) {
+        let mut exes = Vec::new();
+        let mut rejected = Vec::new();
+        for file in data.on_file_drop().files() {
+            let resolved = if file.to_lowercase().ends_with(".lnk") {
+                resolve_shortcut(&file)
+            } else {
+                Some(file.clone())
+            };
+            match resolved {
+                Some(path) if path.to_lowercase().ends_with(".exe") => exes.push(path),
+                _ => rejected.push(file),
+            }
+        }
+
+        match exes.as_slice() {
+            [] => {}
+            [exe] => self.path_input.set_text(exe),
+            _ => {
+                for exe in exes {
+                    self.path_input.set_text(&exe);
+                    self.add_target();
+                }
+            }
+        }
+        let hint = rejected
+            .iter()
+            .map(|file| format!("⚠ Ignored {}: not an .exe or a shortcut to one", file))
+            .collect::<Vec<_>>()
+            .join("   ");
+        self.target_warning_label.set_text(&hint);
+    }
+
+    fn strategy_changed(&self) {
+        let current = self.current_strategy.borrow().clone();
+        let (items, selected): (Vec<String>, Option<usize>) = match self.strategy_combo.selection() {
+            Some(2) => {
+                let choices = self.refresh_choices.borrow();
+                let selected = match current {
+                    Strategy::ReduceRefreshRate { hz } => choices.iter().position(|&c| c == hz),
+                    _ => None,
+                };
+                (choices.iter().map(|hz| format!("{} Hz", hz)).collect(), selected.or(Some(0)))
+            }
+            Some(3) => {
+                let choices = self.resolution_choices.borrow();
+                let selected = match current {
+                    Strategy::ReduceResolution { width, height } => choices.iter().position(|&c| c == (width, height)),
+                    _ => None,
+                };
+                (choices.iter().map(|(w, h)| format!("{}x{}", w, h)).collect(), selected.or(Some(0)))
+            }
+            _ => (Vec::new(), None),
+        };
+        self.strategy_mode_combo.set_enabled(!items.is_empty());
+        self.strategy_mode_combo.set_collection(items);
+        self.strategy_mode_combo.set_selection(selected);
+    }
+
+    fn selected_strategy(&self) -> Strategy {
+        let mode = self.strategy_mode_combo.selection();
+        let current = self.current_strategy.borrow().clone();
+        match self.strategy_combo.selection() {
+            Some(1) => Strategy::SwitchPrimary,
+            Some(2) => match mode.and_then(|idx| self.refresh_choices.borrow().get(idx).copied()) {
+                Some(hz) => Strategy::ReduceRefreshRate { hz },
+                None if matches!(current, Strategy::ReduceRefreshRate { .. }) => current,
+                None => Strategy::ReduceRefreshRate { hz: 60 },
+            },
+            Some(3) => match mode.and_then(|idx| self.resolution_choices.borrow().get(idx).copied()) {
+                Some((width, height)) => Strategy::ReduceResolution { width, height },
+                None if matches!(current, Strategy::ReduceResolution { .. }) => current,
+                None => Strategy::ReduceResolution { width: 1280, height: 720 },
+            },
+            Some(4) => Strategy::DdcPowerOff,
+            Some(5) => Strategy::Blank,
+            _ => Strategy::Disable,
+        }
+    }
+
+    /// Fills the schedule row from `schedule`, falling back to evenings
+    /// every day when there isn't one.
+    fn show_schedule(&self, schedule: Option<&Schedule>) {
+        let windows: Vec<(Weekday, TimeWindow)> = SCHEDULE_WEEK
+            .iter()
+            .filter_map(|&day| schedule.and_then(|s| s.day(day)).map(|window| (day, window)))
+            .collect();
+        let window = windows.first().map_or(DEFAULT_SCHEDULE_WINDOW, |&(_, window)| window);
+        let days: Vec<Weekday> = windows.iter().map(|&(day, _)| day).collect();
+        let same_window = windows.iter().all(|&(_, w)| w == window);
+        let preset = match schedule {
+            Some(_) => SCHEDULE_DAY_CHOICES
+                .iter()
+                .position(|&(_, preset)| same_window && preset == days.as_slice()),
+            None => Some(0),
+        };
+
+        let mut labels: Vec<&'static str> = SCHEDULE_DAY_CHOICES.iter().map(|&(label, _)| label).collect();
+        if preset.is_none() {
+            labels.push(CUSTOM_SCHEDULE_LABEL);
+        }
+        self.schedule_days_combo.set_collection(labels);
+        self.schedule_days_combo.set_selection(Some(preset.unwrap_or(SCHEDULE_DAY_CHOICES.len())));
+        for (combo, time) in [(&self.schedule_start_combo, window.start), (&self.schedule_end_combo, window.end)] {
+            let choices = time_choices(time);
+            let selected = choices.iter().position(|&choice| choice == time);
+            combo.set_collection(choices.iter().map(TimeOfDay::to_string).collect());
+            combo.set_selection(selected);
+        }
+        self.schedule_check.set_check_state(if schedule.is_some() {
+            nwg::CheckBoxState::Checked
+        } else {
+            nwg::CheckBoxState::Unchecked
+        });
+        *self.loaded_schedule.borrow_mut() = schedule.cloned();
+        self.schedule_changed();
+    }
+
+    /// Greys out the times when the schedule is off or kept as loaded.
+    fn schedule_changed(&self) {
+        let on = self.schedule_check.check_state() == nwg::CheckBoxState::Checked;
+        let custom = self.schedule_days_combo.selection() == Some(SCHEDULE_DAY_CHOICES.len());
+        self.schedule_days_combo.set_enabled(on);
+        self.schedule_start_combo.set_enabled(on && !custom);
+        self.schedule_end_combo.set_enabled(on && !custom);
+    }
+
+    fn selected_schedule(&self) -> Option<Schedule> {
+        if self.schedule_check.check_state() != nwg::CheckBoxState::Checked {
+            return None;
+        }
+        let Some(&(_, days)) = self.schedule_days_combo.selection().and_then(|idx| SCHEDULE_DAY_CHOICES.get(idx))
+        else {
+            return self.loaded_schedule.borrow().clone();
+        };
+        let time = |combo: &nwg::ComboBox<String>| combo.selection_string().and_then(|text| TimeOfDay::parse(&text));
+        let window = TimeWindow {
+            start: time(&self.schedule_start_combo).unwrap_or(DEFAULT_SCHEDULE_WINDOW.start),
+            end: time(&self.schedule_end_combo).unwrap_or(DEFAULT_SCHEDULE_WINDOW.end),
+        };
+        let mut schedule = Schedule::default();
+        for &day in days {
+            *schedule.day_mut(day) = Some(window);
+        }
+        Some(schedule)
+    }
+
+    fn selected_managed_monitors(&self) -> Option<Vec<String>> {
+        let keys = self.monitor_keys.borrow();
+        let checked: Vec<String> = keys
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| list_view_checked(&self.monitors_list, *row))
+            .map(|(_, key)| key.clone())
+            .collect();
+        if checked.len() == keys.len() && self.managed_monitors.borrow().is_none() {
+            None
+        } else {
+            Some(checked)
+        }
+    }
+
+    fn selected_excluded_monitors(&self) -> Vec<String> {
+        self.excluded_keys
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| list_view_checked(&self.excluded_list, *row))
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    fn add_target(&self) {
+        let path = matcher::clean_target_input(&self.path_input.text());
+        if path.is_empty() {
+            return;
+        }
+        let already_listed = self
+            .targets_list
+            .collection()
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&path));
+        if !already_listed {
+            self.targets_list.push(path);
+        }
+        self.path_input.set_text("");
+    }
+
+    fn remove_target(&self) {
+        if let Some(idx) = self.targets_list.selection() {
+            self.targets_list.remove(idx);
+        }
+    }
+
     fn save(&self) {
-        let path = self.path_input.text();
+        if self.apply_settings() {
+            nwg::stop_thread_dispatch();
+        }
+    }
+
+    fn apply(&self) {
+        self.apply_settings();
+    }
+
+    /// Stores the dialog's values in the live config and on disk. Returns
+    /// false if the dialog should stay open, either to show a warning about
+    /// the targets or because the config couldn't be written.
+    fn apply_settings(&self) -> bool {
+        self.store_profile();
+        let profiles = self.profiles.borrow().clone();
+        let active = profiles[self.profile_idx.get()].clone();
+        let requested_interval = self
+            .poll_interval_input
+            .text()
+            .trim()
+            .parse::<u64>()
+            .unwrap_or(config::DEFAULT_POLL_INTERVAL_MS);
+        let poll_interval_ms = config::clamp_poll_interval_ms(requested_interval);
+        let bad_title = profiles.iter().find_map(|profile| match &profile.trigger {
+            Trigger::WindowTitle(title) => matcher::TitlePattern::parse(title).err(),
+            _ => None,
+        });
+        if let Some(e) = bad_title {
+            self.target_warning_label.set_text(&format!("⚠ {}", e));
+            return false;
+        }
+        let warnings: Vec<String> = profiles
+            .iter()
+            .flat_map(|profile| profile.targets.iter().map(move |target| (target, profile.match_mode)))
+            .filter_map(|(target, match_mode)| {
+                matcher::target_warning(target, match_mode, Path::exists).map(|w| format!("⚠ {}: {}", target, w))
+            })
+            .collect();
+        if !warnings.is_empty() && *self.shown_warnings.borrow() != warnings {
+            self.target_warning_label.set_text(&warnings.join("   "));
+            self.save_button.set_text("Save anyway");
+            *self.shown_warnings.borrow_mut() = warnings;
+            return false;
+        }
+        let mut summary = match active.trigger {
+            Trigger::Processes => format!("Profile {}, now monitoring:\n{}", active.name, active.targets.join("\n")),
+            Trigger::Fullscreen { .. } => format!("Profile {}, now watching for fullscreen apps", active.name),
+            Trigger::WindowTitle(title) => format!("Profile {}, now watching for windows titled:\n{}", active.name, title),
+        };
+        if poll_interval_ms != requested_interval {
+            summary.push_str(&format!(
+                "\n\nPoll interval {}ms is out of range; using {}ms.",
+                requested_interval, poll_interval_ms
+            ));
+        }
         if let Some(state) = self.state.borrow().as_ref() {
-            let mut state = state.lock().unwrap();
-            state.config.target_exe = path.clone();
-            let _ = state.config.save();
-            nwg::simple_message("Settings Saved", &format!("Now monitoring:\n{}", path));
+            let mut state = lock(state);
+            state.config.active_profile = active.name.clone();
+            state.config.profiles = profiles;
+            state.config.poll_interval_ms = poll_interval_ms;
+            state.config.managed_monitors = self.selected_managed_monitors();
+            state.config.excluded_monitors = self.selected_excluded_monitors();
+            state.config.schedule = self.selected_schedule();
+            state.config_generation += 1;
+            if let Err(e) = state.config.save() {
+                // Keep the dialog open so the user doesn't lose their edits.
+                nwg::modal_error_message(
+                    &self.window,
+                    "Settings Not Saved",
+                    &format!("The settings apply until Monitor Manager exits, but could not be written:\n{}", e),
+                );
+                return false;
+            }
+            let enabled = state.config.notifications;
+            drop(state);
+            let start_with_windows = self.start_with_windows_check.check_state() == nwg::CheckBoxState::Checked;
+            if *self.autostart_enabled.borrow() != Some(start_with_windows) {
+                match autostart::set_enabled(start_with_windows) {
+                    Ok(()) => *self.autostart_enabled.borrow_mut() = Some(start_with_windows),
+                    Err(e) => notify::notify(enabled, Severity::Error, "Start with Windows", &e.to_string()),
+                }
+            }
+            notify::notify(enabled, Severity::Info, "Settings Saved", &summary);
         }
-        nwg::stop_thread_dispatch();
+        self.target_warning_label.set_text("");
+        self.save_button.set_text("Save");
+        true
     }
 
     fn close(&self) {
@@ -315,58 +1829,221 @@ fn show_settings_dialog(state: &Arc<Mutex<AppState>>) {
         let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
     }
 
-    let current_exe = {
-        let state = state.lock().unwrap();
-        state.config.target_exe.clone()
+    let (profiles, active_idx, current_poll_interval_ms, current_managed, current_excluded, current_schedule) = {
+        let state = lock(state);
+        (
+            state.config.profiles.clone(),
+            state.config.profile_index(&state.config.active_profile).unwrap_or(0),
+            state.config.poll_interval_ms,
+            state.config.managed_monitors.clone(),
+            state.config.excluded_monitors.clone(),
+            state.config.schedule.clone(),
+        )
     };
+    let current_keep_enabled = profiles[active_idx].keep_enabled.clone();
+    let kept_by_profiles: Vec<&String> = profiles.iter().filter_map(|p| p.keep_enabled.as_ref()).collect();
 
-    let (status_text, monitors_items) = {
+    let (status_text, monitor_lists, keep_enabled_choices, keep_enabled_hdr, refresh_choices, resolution_choices) = {
         let (status, monitoring, monitor_manager) = {
-            let state = state.lock().unwrap();
+            let state = lock(state);
             (state.status.clone(), state.monitoring, state.monitor_manager.clone())
         };
 
-        let manager = monitor_manager.lock().unwrap();
-        let mut monitors = manager.get_all_monitors();
+        let keep_enabled = current_keep_enabled.clone();
+        let (mut refresh_choices, mut resolution_choices, mut monitors) = monitor_manager.run(move |manager| {
+            let mut refresh_choices = Vec::new();
+            let mut resolution_choices = Vec::new();
+            for device_name in manager.secondary_device_names(keep_enabled.as_deref()) {
+                for mode in manager.available_modes(&device_name) {
+                    if !refresh_choices.contains(&mode.frequency) {
+                        refresh_choices.push(mode.frequency);
+                    }
+                }
+                for resolution in manager.available_resolutions(&device_name) {
+                    if !resolution_choices.contains(&resolution) {
+                        resolution_choices.push(resolution);
+                    }
+                }
+            }
+            (refresh_choices, resolution_choices, manager.get_all_monitors_detailed())
+        });
+        refresh_choices.sort_unstable_by(|a, b| b.cmp(a));
+        resolution_choices.sort_unstable_by_key(|&(width, height)| std::cmp::Reverse((width * height, width)));
+
         monitors.sort_by(|a, b| {
             b.is_primary
                 .cmp(&a.is_primary)
                 .then_with(|| a.description.to_lowercase().cmp(&b.description.to_lowercase()))
         });
 
+        let mut keep_enabled_choices = vec![(None, "Primary monitor (default)".to_string())];
+        keep_enabled_choices.extend(monitors.iter().filter(|m| m.is_active && m.is_physical()).map(|m| {
+            // Keep a configured value as-is if it names this monitor by device name.
+            let key = match kept_by_profiles.iter().find(|kept| m.matches_id(kept)) {
+                Some(kept) => kept.to_string(),
+                None => m.key().to_string(),
+            };
+            (Some(key), m.label())
+        }));
+        // Keep configured monitors that aren't connected right now selectable.
+        for kept in &kept_by_profiles {
+            if !keep_enabled_choices.iter().any(|(k, _)| k.as_ref() == Some(*kept)) {
+                keep_enabled_choices.push((Some(kept.to_string()), format!("{} (not connected)", kept)));
+            }
+        }
+        let keep_enabled_hdr: Vec<bool> = keep_enabled_choices
+            .iter()
+            .map(|(key, _)| match key {
+                Some(key) => monitors.iter().any(|m| m.is_active && m.matches_id(key) && m.hdr_supported),
+                None => monitors.iter().any(|m| m.is_primary && m.hdr_supported),
+            })
+            .collect();
+
         let status_text = format!(
             "{} (Monitoring: {})",
             status,
             if monitoring { "On" } else { "Off" }
         );
 
-        let items = if monitors.is_empty() {
-            vec!["No monitors detected".to_string()]
-        } else {
-            monitors
-                .into_iter()
-                .map(|m| {
-                    let role = if m.is_primary { "PRIMARY" } else { "Secondary" };
-                    let active = if m.is_active { "Active" } else { "Disabled" };
-                    format!("{}  —  {} / {}  ({})", m.description, role, active, m.device_name)
-                })
-                .collect::<Vec<_>>()
-        };
+        let is_managed = |key: &str| current_managed.as_ref().is_none_or(|keys| keys.iter().any(|k| k == key));
+        let mut items: Vec<(String, String, bool)> = monitors
+            .iter()
+            .filter(|m| m.is_physical())
+            .map(|m| {
+                // Keep the configured value as-is if it names this monitor by device name.
+                let key = current_managed
+                    .iter()
+                    .flatten()
+                    .find(|k| m.matches_id(k))
+                    .cloned()
+                    .unwrap_or_else(|| m.key().to_string());
+                let checked = is_managed(&key);
+                let scale = m.scale_percent.map(|p| format!(", {}%", p)).unwrap_or_default();
+                let profile = m.color_profile.as_deref().map(|p| format!(", {}", p)).unwrap_or_default();
+                (key, format!("{}  ({}{}{})", m.label(), m.device_name, scale, profile), checked)
+            })
+            .collect();
+        // List configured monitors that aren't connected so saving keeps them.
+        for key in current_managed.iter().flatten() {
+            if !items.iter().any(|(k, _, _)| k == key) {
+                items.push((key.clone(), format!("{} (not connected)", key), true));
+            }
+        }
+
+        let mut excluded_items: Vec<(String, String, bool)> = monitors
+            .iter()
+            .filter(|m| m.is_physical())
+            .map(|m| {
+                let configured = current_excluded.iter().find(|k| m.matches_id(k));
+                let key = configured.cloned().unwrap_or_else(|| m.key().to_string());
+                (key, format!("{}  ({})", m.label(), m.device_name), configured.is_some())
+            })
+            .collect();
+        for key in &current_excluded {
+            if !excluded_items.iter().any(|(k, _, _)| k == key) {
+                excluded_items.push((key.clone(), format!("{} (not connected)", key), true));
+            }
+        }
 
-        (status_text, items)
+        let monitor_lists = (items, excluded_items);
+        (status_text, monitor_lists, keep_enabled_choices, keep_enabled_hdr, refresh_choices, resolution_choices)
     };
 
+    let (monitors_items, excluded_items) = monitor_lists;
     let app = SettingsDialog::build_ui(Default::default()).expect("Failed to build UI");
     
     *app.state.borrow_mut() = Some(state.clone());
-    app.path_input.set_text(&current_exe);
+    app.poll_interval_input.set_text(&current_poll_interval_ms.to_string());
+
+    let mut audio_choices = vec![(None, "Don't switch".to_string())];
+    match audio::playback_devices() {
+        Ok(devices) => audio_choices.extend(devices.into_iter().map(|device| (Some(device.id), device.name))),
+        Err(e) => log::warn!("Could not list the audio devices: {}", e.message()),
+    }
+    // Keep configured devices that aren't connected right now selectable.
+    for id in profiles.iter().filter_map(|p| p.audio_device.as_ref()) {
+        if !audio_choices.iter().any(|(known, _)| known.as_ref() == Some(id)) {
+            audio_choices.push((Some(id.clone()), format!("{} (not connected)", id)));
+        }
+    }
+    let (audio_ids, audio_labels): (Vec<_>, Vec<_>) = audio_choices.into_iter().unzip();
+    app.audio_device_combo.set_collection(audio_labels);
+    *app.audio_device_ids.borrow_mut() = audio_ids;
+
+    let mut power_choices = vec![(None, "Don't switch".to_string())];
+    match power_plan::schemes() {
+        Ok(schemes) => power_choices.extend(schemes.into_iter().map(|scheme| (Some(scheme.guid), scheme.name))),
+        Err(e) => log::warn!("Could not list the power plans: {}", e.message()),
+    }
+    // Keep configured plans that were deleted selectable.
+    for guid in profiles.iter().filter_map(|p| p.power_scheme.as_ref()) {
+        let known = power_choices
+            .iter()
+            .any(|(known, _)| known.as_ref().is_some_and(|known| known.eq_ignore_ascii_case(guid)));
+        if !known {
+            power_choices.push((Some(guid.clone()), format!("{} (not found)", guid)));
+        }
+    }
+    let (power_guids, power_labels): (Vec<_>, Vec<_>) = power_choices.into_iter().unzip();
+    app.power_scheme_combo.set_collection(power_labels);
+    *app.power_scheme_guids.borrow_mut() = power_guids;
+
+    let (keep_keys, keep_labels): (Vec<_>, Vec<_>) = keep_enabled_choices.into_iter().unzip();
+    app.keep_enabled_combo.set_collection(keep_labels);
+    *app.keep_enabled_keys.borrow_mut() = keep_keys;
+    *app.keep_enabled_hdr.borrow_mut() = keep_enabled_hdr;
+
+    *app.refresh_choices.borrow_mut() = refresh_choices;
+    *app.resolution_choices.borrow_mut() = resolution_choices;
+    *app.profiles.borrow_mut() = profiles;
+    app.show_profile(active_idx);
+    app.show_schedule(current_schedule.as_ref());
     app.status_value.set_text(&status_text);
 
-    app.monitors_list.clear();
-    for (idx, item) in monitors_items.iter().cloned().enumerate() {
-        app.monitors_list.insert(idx, item);
+    let autostart_enabled = match autostart::is_enabled() {
+        Ok(enabled) => Some(enabled),
+        Err(e) => {
+            log::warn!("Could not read the start-with-Windows setting: {}", e);
+            None
+        }
+    };
+    app.start_with_windows_check.set_check_state(if autostart_enabled == Some(true) {
+        nwg::CheckBoxState::Checked
+    } else {
+        nwg::CheckBoxState::Unchecked
+    });
+    *app.autostart_enabled.borrow_mut() = autostart_enabled;
+
+    enable_list_view_checkboxes(&app.monitors_list);
+    app.monitors_list.insert_column(nwg::InsertListViewColumn {
+        index: Some(0),
+        fmt: None,
+        width: Some(680),
+        text: Some("Monitor".to_string()),
+    });
+    for (row, (_, label, checked)) in monitors_items.iter().enumerate() {
+        app.monitors_list.insert_item(label.as_str());
+        set_list_view_checked(&app.monitors_list, row, *checked);
     }
-    
+    *app.monitor_keys.borrow_mut() = monitors_items.into_iter().map(|(key, _, _)| key).collect();
+    *app.managed_monitors.borrow_mut() = current_managed;
+
+    enable_list_view_checkboxes(&app.excluded_list);
+    app.excluded_list.insert_column(nwg::InsertListViewColumn {
+        index: Some(0),
+        fmt: None,
+        width: Some(680),
+        text: Some("Monitor".to_string()),
+    });
+    for (row, (_, label, checked)) in excluded_items.iter().enumerate() {
+        app.excluded_list.insert_item(label.as_str());
+        set_list_view_checked(&app.excluded_list, row, *checked);
+    }
+    *app.excluded_keys.borrow_mut() = excluded_items.into_iter().map(|(key, _, _)| key).collect();
+
+    let hwnd = app.window.handle.hwnd().map_or(0, |hwnd| hwnd as isize);
+    SETTINGS_WINDOW.store(hwnd, Ordering::Relaxed);
     nwg::dispatch_thread_events();
+    SETTINGS_WINDOW.store(0, Ordering::Relaxed);
 }
 
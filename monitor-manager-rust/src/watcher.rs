@@ -0,0 +1,681 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use windows::Win32::Foundation::{BOOL, HWND, LPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
+};
+
+use crate::config::{ChildTrigger, MatchMode};
+use crate::fullscreen;
+use crate::matcher::{PatternError, TargetPattern, TitlePattern};
+
+/// How far up the parent chain a process is followed; PID reuse can make
+/// the chain loop.
+const MAX_ANCESTORS: usize = 64;
+
+/// A change in which target is running, as reported by [`RunningTargets::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The target at this index of the patterns started running.
+    Started(usize),
+    /// No target is running any more.
+    Exited,
+}
+
+/// Whether each target was running at the last poll, keyed by its index in
+/// the patterns. The monitors follow the union: only the first target
+/// starting and the last one exiting count, so targets that overlap don't
+/// restore the monitors while one of them still runs.
+#[derive(Debug, Clone, Default)]
+pub struct RunningTargets {
+    targets: BTreeMap<usize, bool>,
+}
+
+impl RunningTargets {
+    /// Records the targets a poll found, as pattern indexes, and returns the
+    /// event for the union if it started or ended.
+    pub fn update(&mut self, detected: &[usize]) -> Option<WatchEvent> {
+        let was_running = self.first().is_some();
+        for (idx, running) in &mut self.targets {
+            *running = detected.contains(idx);
+        }
+        for &idx in detected {
+            self.targets.insert(idx, true);
+        }
+        match (was_running, self.first()) {
+            (false, Some(idx)) => Some(WatchEvent::Started(idx)),
+            (true, None) => Some(WatchEvent::Exited),
+            _ => None,
+        }
+    }
+
+    /// The lowest index of a running target; the one whose rules apply.
+    pub fn first(&self) -> Option<usize> {
+        self.targets.iter().find_map(|(&idx, &running)| running.then_some(idx))
+    }
+
+    /// The running targets' indexes, in order.
+    pub fn running(&self) -> Vec<usize> {
+        self.targets.iter().filter_map(|(&idx, &running)| running.then_some(idx)).collect()
+    }
+}
+
+/// Where the monitor loop is between a target starting and the monitors
+/// coming back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatcherState {
+    #[default]
+    Idle,
+    /// A target is running but the disable delay hasn't passed yet.
+    PendingDisable { since: Instant },
+    /// Monitors are changed for a running target.
+    Active,
+    /// The last target exited and the restore delay is counting down.
+    PendingRestore { since: Instant },
+    /// Monitoring is paused from the tray, or it's outside the schedule; the
+    /// displays are left alone.
+    Paused,
+}
+
+impl fmt::Display for WatcherState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Idle => write!(f, "idle"),
+            Self::PendingDisable { .. } => write!(f, "pending disable"),
+            Self::Active => write!(f, "active"),
+            Self::PendingRestore { .. } => write!(f, "pending restore"),
+            Self::Paused => write!(f, "paused"),
+        }
+    }
+}
+
+/// What the monitor loop has to do to the displays after a
+/// [`WatcherState::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Apply the strategy for the target at this index.
+    Disable(usize),
+    Restore,
+}
+
+impl WatcherState {
+    /// Moves on given the index of the running target, if any. `Paused` is
+    /// only entered and left by the caller.
+    pub fn step(
+        self,
+        running: Option<usize>,
+        now: Instant,
+        disable_delay: Duration,
+        restore_delay: Duration,
+    ) -> (Self, Option<WatchAction>) {
+        match (self, running) {
+            (Self::Paused, _) => (self, None),
+            (Self::Idle, None) => (self, None),
+            (Self::Idle, Some(_)) => Self::PendingDisable { since: now }.step(running, now, disable_delay, restore_delay),
+            (Self::PendingDisable { .. }, None) => (Self::Idle, None),
+            (Self::PendingDisable { since }, Some(idx)) if now.duration_since(since) >= disable_delay => {
+                (Self::Active, Some(WatchAction::Disable(idx)))
+            }
+            (Self::PendingDisable { .. }, Some(_)) => (self, None),
+            (Self::Active, Some(_)) => (self, None),
+            (Self::Active, None) => Self::PendingRestore { since: now }.step(running, now, disable_delay, restore_delay),
+            (Self::PendingRestore { .. }, Some(_)) => (Self::Active, None),
+            (Self::PendingRestore { since }, None) if now.duration_since(since) >= restore_delay => {
+                (Self::Idle, Some(WatchAction::Restore))
+            }
+            (Self::PendingRestore { .. }, None) => (self, None),
+        }
+    }
+
+    /// Whether monitors are changed for a target, counting a pending restore.
+    pub fn holds_monitors(self) -> bool {
+        matches!(self, Self::Active | Self::PendingRestore { .. })
+    }
+
+    /// Time left on the delay being counted down, if any.
+    pub fn remaining(self, now: Instant, disable_delay: Duration, restore_delay: Duration) -> Option<Duration> {
+        match self {
+            Self::PendingDisable { since } => Some(disable_delay.saturating_sub(now.duration_since(since))),
+            Self::PendingRestore { since } => Some(restore_delay.saturating_sub(now.duration_since(since))),
+            _ => None,
+        }
+    }
+}
+
+/// A flag that only changes once the raw value has held for a while.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Debounce {
+    settled: bool,
+    /// When the raw value last started to differ from `settled`.
+    changing_since: Option<Instant>,
+}
+
+impl Debounce {
+    /// Feeds in the current raw value and returns the settled one.
+    pub fn update(&mut self, value: bool, now: Instant, delay: Duration) -> bool {
+        if value == self.settled {
+            self.changing_since = None;
+        } else {
+            let since = *self.changing_since.get_or_insert(now);
+            if now.duration_since(since) >= delay {
+                self.settled = value;
+                self.changing_since = None;
+            }
+        }
+        self.settled
+    }
+
+    /// Settles on `value` straight away.
+    pub fn reset(&mut self, value: bool) {
+        *self = Self { settled: value, changing_since: None };
+    }
+
+    /// Time left until a pending change settles, if one is pending.
+    pub fn remaining(&self, now: Instant, delay: Duration) -> Option<Duration> {
+        self.changing_since.map(|since| delay.saturating_sub(now.duration_since(since)))
+    }
+}
+
+/// A compiled [`ChildTrigger`].
+#[derive(Debug, Clone)]
+pub enum ChildPattern {
+    Pattern(TargetPattern),
+    Fullscreen,
+}
+
+impl ChildPattern {
+    pub fn compile(trigger: &ChildTrigger) -> Result<Self, PatternError> {
+        match trigger {
+            ChildTrigger::Pattern(pattern) => TargetPattern::parse(pattern).map(Self::Pattern),
+            ChildTrigger::Fullscreen => Ok(Self::Fullscreen),
+        }
+    }
+}
+
+/// Looks for running processes that match a set of target patterns.
+pub struct ProcessWatcher {
+    system: System,
+    /// Processes started under a launcher target, with the target's index.
+    /// Kept after the launcher or a process in between exits so that orphans
+    /// stay attributed.
+    launched: HashMap<Pid, usize>,
+    /// See `Config::name_fallback`.
+    name_fallback: bool,
+    /// Processes whose exe path couldn't be read, already logged.
+    unreadable: HashSet<Pid>,
+    /// Name of the last such process, until `take_unreadable` is called.
+    new_unreadable: Option<String>,
+}
+
+impl ProcessWatcher {
+    /// Creates a watcher with an empty process list; the first `poll` fills it.
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            launched: HashMap::new(),
+            name_fallback: true,
+            unreadable: HashSet::new(),
+            new_unreadable: None,
+        }
+    }
+
+    /// Whether processes whose exe path can't be read are matched by name;
+    /// see `Config::name_fallback`.
+    pub fn set_name_fallback(&mut self, enabled: bool) {
+        self.name_fallback = enabled;
+    }
+
+    /// Refreshes the process list and returns the index of the first pattern
+    /// with a matching process, if any. Only processes whose name could match
+    /// get their exe path read; nothing else about them is refreshed.
+    pub fn poll(&mut self, patterns: &[TargetPattern], mode: MatchMode) -> Option<usize> {
+        self.poll_all(patterns, mode).first().copied()
+    }
+
+    /// Like [`poll`](Self::poll), but returns the index of every pattern with
+    /// a matching process, in order.
+    pub fn poll_all(&mut self, patterns: &[TargetPattern], mode: MatchMode) -> Vec<usize> {
+        self.refresh(|name| patterns.iter().any(|pattern| pattern.may_match_name(name, mode)));
+        (0..patterns.len())
+            .filter(|&idx| {
+                self.system
+                    .processes()
+                    .values()
+                    .any(|process| self.matches(&patterns[idx], process, mode))
+            })
+            .collect()
+    }
+
+    /// Like [`poll`](Self::poll), but `patterns` are launchers: returns the
+    /// index of the first one that started a process matching `child`.
+    pub fn poll_launched(
+        &mut self,
+        patterns: &[TargetPattern],
+        mode: MatchMode,
+        child: &ChildPattern,
+    ) -> Option<usize> {
+        self.refresh(|name| {
+            patterns.iter().any(|pattern| pattern.may_match_name(name, mode))
+                || matches!(child, ChildPattern::Pattern(pattern) if pattern.may_match_name(name, mode))
+        });
+
+        let roots: HashMap<Pid, usize> = self
+            .system
+            .processes()
+            .iter()
+            .filter_map(|(pid, process)| {
+                let root = patterns.iter().position(|pattern| self.matches(pattern, process, mode))?;
+                Some((*pid, root))
+            })
+            .collect();
+        let mut adopted = Vec::new();
+        for (pid, process) in self.system.processes() {
+            if roots.contains_key(pid) || self.launched.contains_key(pid) {
+                continue;
+            }
+            // Ancestors already adopted count even if they have exited.
+            let (mut parent, mut child) = (process.parent(), process);
+            for _ in 0..MAX_ANCESTORS {
+                let Some(parent_pid) = parent else {
+                    break;
+                };
+                if let Some(&root) = self.launched.get(&parent_pid) {
+                    adopted.push((*pid, root));
+                    break;
+                }
+                let Some(parent_process) = self.system.process(parent_pid) else {
+                    break;
+                };
+                // A reused PID: the real parent is gone.
+                if parent_process.start_time() > child.start_time() && child.start_time() != 0 {
+                    break;
+                }
+                if let Some(&root) = roots.get(&parent_pid) {
+                    adopted.push((*pid, root));
+                    break;
+                }
+                (parent, child) = (parent_process.parent(), parent_process);
+            }
+        }
+        self.launched.extend(adopted);
+        self.launched.retain(|pid, _| self.system.process(*pid).is_some());
+
+        match child {
+            ChildPattern::Pattern(pattern) => self
+                .launched
+                .iter()
+                .filter(|(pid, _)| {
+                    self.system
+                        .process(**pid)
+                        .is_some_and(|process| self.matches(pattern, process, mode))
+                })
+                .map(|(_, &root)| root)
+                .min(),
+            ChildPattern::Fullscreen => fullscreen::foreground_process(true)
+                .and_then(|pid| self.launched.get(&Pid::from_u32(pid)).copied()),
+        }
+    }
+
+    /// Refreshes the process list, reading the exe path only for processes
+    /// whose name passes `wanted`.
+    fn refresh(&mut self, wanted: impl Fn(&OsStr) -> bool) {
+        self.system
+            .refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::new());
+        let candidates: Vec<Pid> = self
+            .system
+            .processes()
+            .iter()
+            .filter(|(_, process)| wanted(process.name()))
+            .map(|(pid, _)| *pid)
+            .collect();
+        if !candidates.is_empty() {
+            self.system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&candidates),
+                false,
+                ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet),
+            );
+        }
+        for pid in candidates {
+            let Some(process) = self.system.process(pid) else {
+                continue;
+            };
+            if process.exe().is_none() && self.unreadable.insert(pid) {
+                let name = process.name().to_string_lossy();
+                if self.name_fallback {
+                    log::info!("Can't read the exe path of {} (PID {}); matching it by name only", name, pid);
+                } else {
+                    log::info!("Can't read the exe path of {} (PID {}); ignoring it (name_fallback is off)", name, pid);
+                }
+                self.new_unreadable = Some(name.into_owned());
+            }
+        }
+        self.unreadable.retain(|pid| self.system.process(*pid).is_some());
+    }
+
+    /// The name of a process that could be a target but whose exe path
+    /// couldn't be read, usually because it runs elevated, if one turned up
+    /// since the last call.
+    pub fn take_unreadable(&mut self) -> Option<String> {
+        self.new_unreadable.take()
+    }
+
+    /// Whether `process` matches `pattern`. A process whose exe path
+    /// couldn't be read only matches by name, and only with `name_fallback`.
+    fn matches(&self, pattern: &TargetPattern, process: &Process, mode: MatchMode) -> bool {
+        if process.exe().is_none() && !self.name_fallback {
+            return false;
+        }
+        pattern.matches(process.exe(), process.name(), mode)
+    }
+
+    /// The index of the first pattern matching the process that owns the
+    /// foreground window. Only processes seen by the last `poll` are known.
+    pub fn foreground_target(&self, patterns: &[TargetPattern], mode: MatchMode) -> Option<usize> {
+        let mut pid = 0u32;
+        unsafe {
+            let window = GetForegroundWindow();
+            if window.is_invalid() {
+                return None;
+            }
+            GetWindowThreadProcessId(window, Some(&mut pid));
+        }
+        let process = self.system.process(Pid::from_u32(pid))?;
+        patterns.iter().position(|pattern| self.matches(pattern, process, mode))
+    }
+
+    /// The IDs of processes matching `patterns`, and of processes started
+    /// under a launcher target. Only processes seen by the last `poll` are
+    /// known.
+    pub fn target_pids(&self, patterns: &[TargetPattern], mode: MatchMode) -> Vec<u32> {
+        self.system
+            .processes()
+            .iter()
+            .filter(|(pid, process)| {
+                self.launched.contains_key(pid)
+                    || patterns.iter().any(|pattern| self.matches(pattern, process, mode))
+            })
+            .map(|(pid, _)| pid.as_u32())
+            .collect()
+    }
+
+    /// Whether the process with this ID is still running. Only that process
+    /// is refreshed.
+    pub fn is_running(&mut self, pid: u32) -> bool {
+        let pid = Pid::from_u32(pid);
+        self.system
+            .refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::new());
+        self.system.process(pid).is_some()
+    }
+
+    /// Polls every `interval` and calls `on_event` whenever a target starts or
+    /// the last one exits, until the callback returns `ControlFlow::Break`.
+    /// Targets are tracked through [`RunningTargets`], so one exiting while
+    /// another still runs is no event.
+    pub fn watch(
+        &mut self,
+        patterns: &[TargetPattern],
+        mode: MatchMode,
+        interval: Duration,
+        mut on_event: impl FnMut(WatchEvent) -> ControlFlow<()>,
+    ) {
+        let mut running = RunningTargets::default();
+        loop {
+            let detected = self.poll_all(patterns, mode);
+            if let Some(event) = running.update(&detected) {
+                if on_event(event).is_break() {
+                    return;
+                }
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+/// The ID of the process owning the first visible top-level window whose
+/// title matches `pattern`, leaving out this process's own windows.
+pub fn window_title_owner(pattern: &TitlePattern) -> Option<u32> {
+    struct Search<'a> {
+        pattern: &'a TitlePattern,
+        owner: Option<u32>,
+    }
+
+    unsafe extern "system" fn visit(window: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam.0 as *mut Search);
+        if !IsWindowVisible(window).as_bool() {
+            return true.into();
+        }
+        let mut title = [0u16; 512];
+        let len = GetWindowTextW(window, &mut title).max(0) as usize;
+        if len == 0 || !search.pattern.matches(&String::from_utf16_lossy(&title[..len])) {
+            return true.into();
+        }
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(window, Some(&mut pid));
+        if pid == std::process::id() {
+            return true.into();
+        }
+        search.owner = Some(pid);
+        false.into()
+    }
+
+    let mut search = Search { pattern, owner: None };
+    unsafe {
+        // Fails when `visit` stops the enumeration early.
+        let _ = EnumWindows(Some(visit), LPARAM(&mut search as *mut Search as isize));
+    }
+    search.owner
+}
+
+impl Default for ProcessWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISABLE_DELAY: Duration = Duration::from_secs(3);
+    const RESTORE_DELAY: Duration = Duration::from_secs(5);
+
+    fn step(state: WatcherState, running: Option<usize>, now: Instant) -> (WatcherState, Option<WatchAction>) {
+        state.step(running, now, DISABLE_DELAY, RESTORE_DELAY)
+    }
+
+    #[test]
+    fn disables_once_the_delay_has_passed() {
+        let start = Instant::now();
+        let (state, action) = step(WatcherState::Idle, Some(1), start);
+        assert_eq!((state, action), (WatcherState::PendingDisable { since: start }, None));
+        assert_eq!(state.remaining(start, DISABLE_DELAY, RESTORE_DELAY), Some(DISABLE_DELAY));
+
+        let (state, action) = step(state, Some(1), start + Duration::from_secs(2));
+        assert_eq!((state, action), (WatcherState::PendingDisable { since: start }, None));
+        let (state, action) = step(state, Some(1), start + DISABLE_DELAY);
+        assert_eq!((state, action), (WatcherState::Active, Some(WatchAction::Disable(1))));
+        assert!(state.holds_monitors());
+    }
+
+    #[test]
+    fn no_delay_disables_straight_away() {
+        let now = Instant::now();
+        let (state, action) = WatcherState::Idle.step(Some(0), now, Duration::ZERO, Duration::ZERO);
+        assert_eq!((state, action), (WatcherState::Active, Some(WatchAction::Disable(0))));
+        let (state, action) = state.step(None, now, Duration::ZERO, Duration::ZERO);
+        assert_eq!((state, action), (WatcherState::Idle, Some(WatchAction::Restore)));
+    }
+
+    #[test]
+    fn target_exiting_during_the_disable_delay_cancels_it() {
+        let start = Instant::now();
+        let (state, _) = step(WatcherState::Idle, Some(0), start);
+        let (state, action) = step(state, None, start + Duration::from_secs(1));
+        assert_eq!((state, action), (WatcherState::Idle, None));
+        // Starting again counts the delay from the new start.
+        let restart = start + Duration::from_secs(2);
+        let (state, _) = step(state, Some(0), restart);
+        let (state, action) = step(state, Some(0), start + DISABLE_DELAY);
+        assert_eq!((state, action), (WatcherState::PendingDisable { since: restart }, None));
+    }
+
+    #[test]
+    fn restores_once_the_delay_has_passed() {
+        let start = Instant::now();
+        let (state, action) = step(WatcherState::Active, None, start);
+        assert_eq!((state, action), (WatcherState::PendingRestore { since: start }, None));
+        assert!(state.holds_monitors());
+        assert_eq!(
+            state.remaining(start + Duration::from_secs(1), DISABLE_DELAY, RESTORE_DELAY),
+            Some(Duration::from_secs(4))
+        );
+
+        let (state, action) = step(state, None, start + Duration::from_secs(4));
+        assert_eq!(action, None);
+        let (state, action) = step(state, None, start + RESTORE_DELAY);
+        assert_eq!((state, action), (WatcherState::Idle, Some(WatchAction::Restore)));
+        assert!(!state.holds_monitors());
+    }
+
+    #[test]
+    fn target_returning_during_the_restore_delay_cancels_it() {
+        let start = Instant::now();
+        let (state, _) = step(WatcherState::Active, None, start);
+        let (state, action) = step(state, Some(0), start + Duration::from_secs(2));
+        assert_eq!((state, action), (WatcherState::Active, None));
+        // The delay starts over on the next exit.
+        let (state, _) = step(state, None, start + Duration::from_secs(3));
+        let (_, action) = step(state, None, start + RESTORE_DELAY);
+        assert_eq!(action, None);
+    }
+
+    #[test]
+    fn paused_ignores_targets() {
+        let now = Instant::now();
+        assert_eq!(step(WatcherState::Paused, Some(0), now + DISABLE_DELAY), (WatcherState::Paused, None));
+        assert_eq!(step(WatcherState::Paused, None, now + RESTORE_DELAY), (WatcherState::Paused, None));
+    }
+
+    /// The events for the running targets going through `polls`, starting with none.
+    fn events(polls: &[&[usize]]) -> Vec<WatchEvent> {
+        let mut running = RunningTargets::default();
+        polls.iter().filter_map(|detected| running.update(detected)).collect()
+    }
+
+    #[test]
+    fn overlapping_targets_report_only_the_first_start_and_last_exit() {
+        use WatchEvent::{Exited, Started};
+        // A starts, B starts, A exits first, then B.
+        assert_eq!(events(&[&[0], &[0, 1], &[1], &[]]), [Started(0), Exited]);
+        // A starts, B starts, B exits first, then A.
+        assert_eq!(events(&[&[0], &[0, 1], &[0], &[]]), [Started(0), Exited]);
+        // B starts first.
+        assert_eq!(events(&[&[1], &[0, 1], &[0], &[]]), [Started(1), Exited]);
+        // Both within one poll, and gone within one.
+        assert_eq!(events(&[&[0, 1], &[]]), [Started(0), Exited]);
+        // B taking over from A within one poll is no change at all.
+        assert_eq!(events(&[&[0], &[1], &[]]), [Started(0), Exited]);
+        // One after the other without overlapping.
+        assert_eq!(events(&[&[0], &[], &[1], &[]]), [Started(0), Exited, Started(1), Exited]);
+    }
+
+    /// The action for each poll, given as the targets found so many seconds
+    /// in, fed through `RunningTargets` and `step` the way `monitor_loop` does.
+    fn actions(polls: &[(&[usize], u64)]) -> Vec<Option<WatchAction>> {
+        let start = Instant::now();
+        let mut running = RunningTargets::default();
+        let mut state = WatcherState::Idle;
+        polls
+            .iter()
+            .map(|&(detected, secs)| {
+                running.update(detected);
+                let action;
+                (state, action) = step(state, running.first(), start + Duration::from_secs(secs));
+                action
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_target_exiting_while_another_runs_keeps_the_monitors() {
+        use WatchAction::{Disable, Restore};
+        // A starts, B starts, A exits first, then B.
+        let polls: [(&[usize], u64); 5] = [(&[0], 0), (&[0, 1], 3), (&[1], 4), (&[], 5), (&[], 10)];
+        assert_eq!(actions(&polls), [None, Some(Disable(0)), None, None, Some(Restore)]);
+        // B exits first, then A.
+        let polls: [(&[usize], u64); 5] = [(&[0], 0), (&[0, 1], 3), (&[0], 4), (&[], 5), (&[], 10)];
+        assert_eq!(actions(&polls), [None, Some(Disable(0)), None, None, Some(Restore)]);
+        // B starts first and A joins it before the disable delay is up.
+        let polls: [(&[usize], u64); 4] = [(&[1], 0), (&[0, 1], 1), (&[0, 1], 3), (&[], 8)];
+        assert_eq!(actions(&polls), [None, None, Some(Disable(0)), None]);
+        // A exits long after the disable; B keeps the monitors changed.
+        let polls: [(&[usize], u64); 4] = [(&[0, 1], 0), (&[0, 1], 3), (&[1], 20), (&[1], 30)];
+        assert_eq!(actions(&polls), [None, Some(Disable(0)), None, None]);
+    }
+
+    /// Times a poll of the real process list against the full refresh it
+    /// replaced. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn poll_is_cheaper_than_a_full_refresh() {
+        const ROUNDS: u32 = 50;
+        let patterns: Vec<TargetPattern> =
+            [r"C:\Games\Elden Ring\eldenring.exe", r"glob:C:\Games\*\*.exe", r"regex:^obs(32|64)\.exe$"]
+                .iter()
+                .map(|target| TargetPattern::parse(target).unwrap())
+                .collect();
+
+        let mut watcher = ProcessWatcher::new();
+        watcher.poll(&patterns, MatchMode::FullPath);
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            watcher.poll(&patterns, MatchMode::FullPath);
+        }
+        let poll = start.elapsed() / ROUNDS;
+
+        let mut system = System::new();
+        system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::everything());
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::everything());
+        }
+        let full = start.elapsed() / ROUNDS;
+
+        println!("{} processes: {:?} per poll, {:?} per full refresh", system.processes().len(), poll, full);
+        assert!(poll < full, "poll took {:?}, a full refresh {:?}", poll, full);
+    }
+
+    #[test]
+    fn debounce_settles_after_the_delay() {
+        let start = Instant::now();
+        let delay = Duration::from_secs(2);
+        let mut debounce = Debounce::default();
+        assert!(!debounce.update(true, start, delay));
+        assert_eq!(debounce.remaining(start + Duration::from_secs(1), delay), Some(Duration::from_secs(1)));
+        assert!(!debounce.update(true, start + Duration::from_secs(1), delay));
+        assert!(debounce.update(true, start + delay, delay));
+        assert_eq!(debounce.remaining(start + delay, delay), None);
+    }
+
+    #[test]
+    fn debounce_ignores_a_blip() {
+        let start = Instant::now();
+        let delay = Duration::from_secs(2);
+        let mut debounce = Debounce::default();
+        debounce.update(true, start, delay);
+        assert!(!debounce.update(false, start + Duration::from_secs(1), delay));
+        assert_eq!(debounce.remaining(start + Duration::from_secs(1), delay), None);
+        // A new change waits the whole delay again.
+        assert!(!debounce.update(true, start + delay, delay));
+        assert!(debounce.update(true, start + delay * 2, delay));
+
+        debounce.reset(false);
+        assert!(!debounce.update(false, start + delay * 3, delay));
+    }
+}
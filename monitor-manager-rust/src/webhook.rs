@@ -0,0 +1,181 @@
+use std::ffi::c_void;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use windows::core::{w, HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Networking::WinHttp::{
+    WinHttpCloseHandle, WinHttpConnect, WinHttpCrackUrl, WinHttpOpen, WinHttpOpenRequest, WinHttpQueryHeaders,
+    WinHttpReceiveResponse, WinHttpSendRequest, WinHttpSetTimeouts, URL_COMPONENTS, WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+    WINHTTP_FLAG_SECURE, WINHTTP_INTERNET_SCHEME_HTTPS, WINHTTP_OPEN_REQUEST_FLAGS, WINHTTP_QUERY_FLAG_NUMBER,
+    WINHTTP_QUERY_STATUS_CODE,
+};
+
+/// How long each of resolving, connecting, sending and receiving may take.
+const TIMEOUT: Duration = Duration::from_secs(5);
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// The transition a webhook reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// A target started and monitors were changed.
+    Active,
+    /// The targets are gone and monitors were put back.
+    Idle,
+}
+
+/// The JSON body POSTed to each webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    pub event: Event,
+    /// The target's exe or title; the one that started it for `idle`.
+    pub target: String,
+    pub monitors_affected: usize,
+    /// RFC 3339, local time.
+    pub timestamp: String,
+}
+
+impl Payload {
+    pub fn new(event: Event, target: String, monitors_affected: usize) -> Self {
+        Self { event, target, monitors_affected, timestamp: chrono::Local::now().to_rfc3339() }
+    }
+}
+
+/// POSTs `payload` to every URL on a background thread, retrying each a
+/// couple of times. Failures are only logged.
+pub fn send(urls: &[String], payload: Payload) {
+    if urls.is_empty() {
+        return;
+    }
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("Could not encode the webhook body: {}", e);
+            return;
+        }
+    };
+    let urls = urls.to_vec();
+    thread::spawn(move || {
+        for url in urls {
+            for attempt in 1..=ATTEMPTS {
+                match post(&url, &body) {
+                    Ok(status) if (200..300).contains(&status) => break,
+                    // The server understood and said no; asking again won't help.
+                    Ok(status) if (400..500).contains(&status) => {
+                        log::warn!("Webhook {} answered {}", url, status);
+                        break;
+                    }
+                    Ok(status) => log::warn!("Webhook {} answered {} (attempt {})", url, status, attempt),
+                    Err(e) => log::warn!("Webhook {} failed: {} (attempt {})", url, e, attempt),
+                }
+                if attempt < ATTEMPTS {
+                    thread::sleep(RETRY_DELAY);
+                }
+            }
+        }
+    });
+}
+
+/// Closes a WinHTTP handle when dropped.
+struct Handle(*mut c_void);
+
+impl Handle {
+    fn new(raw: *mut c_void) -> windows::core::Result<Self> {
+        if raw.is_null() {
+            Err(windows::core::Error::from_win32())
+        } else {
+            Ok(Self(raw))
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = WinHttpCloseHandle(self.0);
+        }
+    }
+}
+
+/// Sends one request and returns the HTTP status. Proxy settings come from
+/// the system.
+fn post(url: &str, body: &[u8]) -> Result<u32, String> {
+    let wide: Vec<u16> = url.encode_utf16().collect();
+    let mut parts = URL_COMPONENTS {
+        dwStructSize: std::mem::size_of::<URL_COMPONENTS>() as u32,
+        // Non-zero lengths ask for pointers into `wide`.
+        dwHostNameLength: u32::MAX,
+        dwUrlPathLength: u32::MAX,
+        dwExtraInfoLength: u32::MAX,
+        ..Default::default()
+    };
+    unsafe { WinHttpCrackUrl(&wide, 0, &mut parts) }.map_err(|_| "not an http or https URL".to_string())?;
+    let host = unsafe { slice(parts.lpszHostName, parts.dwHostNameLength) };
+    let mut path = unsafe { slice(parts.lpszUrlPath, parts.dwUrlPathLength) }.to_vec();
+    path.extend_from_slice(unsafe { slice(parts.lpszExtraInfo, parts.dwExtraInfoLength) });
+    if path.is_empty() {
+        path.push(u16::from(b'/'));
+    }
+    let flags = if parts.nScheme == WINHTTP_INTERNET_SCHEME_HTTPS {
+        WINHTTP_FLAG_SECURE
+    } else {
+        WINHTTP_OPEN_REQUEST_FLAGS(0)
+    };
+
+    let request = || -> windows::core::Result<u32> {
+        unsafe {
+            let session = Handle::new(WinHttpOpen(
+                w!("Monitor Manager"),
+                WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                0,
+            ))?;
+            let millis = TIMEOUT.as_millis() as i32;
+            WinHttpSetTimeouts(session.0, millis, millis, millis, millis)?;
+            let connection = Handle::new(WinHttpConnect(session.0, &HSTRING::from_wide(host)?, parts.nPort, 0))?;
+            let request = Handle::new(WinHttpOpenRequest(
+                connection.0,
+                w!("POST"),
+                &HSTRING::from_wide(&path)?,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                std::ptr::null(),
+                flags,
+            ))?;
+            let headers: Vec<u16> = "Content-Type: application/json".encode_utf16().collect();
+            WinHttpSendRequest(
+                request.0,
+                Some(&headers),
+                Some(body.as_ptr().cast()),
+                body.len() as u32,
+                body.len() as u32,
+                0,
+            )?;
+            WinHttpReceiveResponse(request.0, std::ptr::null_mut())?;
+            let mut status = 0u32;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            WinHttpQueryHeaders(
+                request.0,
+                WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+                PCWSTR::null(),
+                Some(&mut status as *mut u32 as *mut c_void),
+                &mut size,
+                std::ptr::null_mut(),
+            )?;
+            Ok(status)
+        }
+    };
+    request().map_err(|e| e.message())
+}
+
+/// The part of a cracked URL a component points at.
+unsafe fn slice<'a>(start: PWSTR, len: u32) -> &'a [u16] {
+    if start.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(start.0, len as usize)
+    }
+}
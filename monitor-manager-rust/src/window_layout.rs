@@ -0,0 +1,230 @@
+use std::ffi::c_void;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::monitor::MonitorInfo;
+
+const SAVED_WINDOWS_FILE: &str = "saved_windows.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowState {
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+/// Where a top-level window sat before the monitors changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedWindow {
+    hwnd: isize,
+    /// Guards against the handle having been reused by another process.
+    process_id: u32,
+    /// `MonitorInfo::key` of the monitor the window was on.
+    monitor: String,
+    /// Offset from that monitor's top-left corner. For minimized and maximized
+    /// windows this is the restored ("normal") rectangle.
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    state: WindowState,
+}
+
+unsafe extern "system" fn collect_window(hwnd: HWND, data: LPARAM) -> BOOL {
+    let windows = &mut *(data.0 as *mut Vec<HWND>);
+    windows.push(hwnd);
+    true.into()
+}
+
+fn window_state(hwnd: HWND) -> WindowState {
+    if unsafe { IsIconic(hwnd) }.as_bool() {
+        WindowState::Minimized
+    } else if unsafe { IsZoomed(hwnd) }.as_bool() {
+        WindowState::Maximized
+    } else {
+        WindowState::Normal
+    }
+}
+
+fn placement(hwnd: HWND) -> Option<WINDOWPLACEMENT> {
+    let mut placement = WINDOWPLACEMENT {
+        length: mem::size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetWindowPlacement(hwnd, &mut placement) }.ok()?;
+    Some(placement)
+}
+
+/// The rectangle that decides which monitor a window belongs to: the window
+/// itself when it's in its normal state, its restored position otherwise.
+fn window_rect(hwnd: HWND, state: WindowState) -> Option<RECT> {
+    match state {
+        WindowState::Normal => {
+            let mut rect = RECT::default();
+            unsafe { GetWindowRect(hwnd, &mut rect) }.ok()?;
+            Some(rect)
+        }
+        _ => placement(hwnd).map(|p| p.rcNormalPosition),
+    }
+}
+
+fn contains_center(outer: &RECT, inner: &RECT) -> bool {
+    let x = (inner.left + inner.right) / 2;
+    let y = (inner.top + inner.bottom) / 2;
+    x >= outer.left && x < outer.right && y >= outer.top && y < outer.bottom
+}
+
+fn monitor_for<'a>(rect: &RECT, monitors: &'a [MonitorInfo]) -> Option<(&'a MonitorInfo, RECT)> {
+    monitors
+        .iter()
+        .filter(|m| m.is_active && m.is_physical())
+        .filter_map(|m| Some((m, m.rect()?)))
+        .find(|(_, monitor_rect)| contains_center(monitor_rect, rect))
+}
+
+/// Records every visible top-level window and the monitor it's on.
+pub fn capture(monitors: &[MonitorInfo]) -> Vec<SavedWindow> {
+    let mut handles: Vec<HWND> = Vec::new();
+    unsafe {
+        let _ = EnumWindows(Some(collect_window), LPARAM(&mut handles as *mut Vec<HWND> as isize));
+    }
+
+    let own_process = std::process::id();
+    handles
+        .into_iter()
+        .filter_map(|hwnd| {
+            if !unsafe { IsWindowVisible(hwnd) }.as_bool() {
+                return None;
+            }
+            let ex_style = unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } as u32;
+            if ex_style & WS_EX_TOOLWINDOW.0 != 0 {
+                return None;
+            }
+            let mut process_id = 0u32;
+            unsafe { GetWindowThreadProcessId(hwnd, Some(&mut process_id)) };
+            if process_id == own_process {
+                return None;
+            }
+            let state = window_state(hwnd);
+            let rect = window_rect(hwnd, state)?;
+            let (monitor, monitor_rect) = monitor_for(&rect, monitors)?;
+            Some(SavedWindow {
+                hwnd: hwnd.0 as isize,
+                process_id,
+                monitor: monitor.key().to_string(),
+                x: rect.left - monitor_rect.left,
+                y: rect.top - monitor_rect.top,
+                width: rect.right - rect.left,
+                height: rect.bottom - rect.top,
+                state,
+            })
+        })
+        .collect()
+}
+
+/// Moves windows back onto their original monitor if that monitor is active
+/// again and the window isn't already there. Windows that closed in the
+/// meantime are skipped.
+pub fn restore(windows: &[SavedWindow], monitors: &[MonitorInfo]) {
+    for window in windows {
+        let Some(monitor_rect) = monitors
+            .iter()
+            .find(|m| m.is_active && m.matches_id(&window.monitor))
+            .and_then(|m| m.rect())
+        else {
+            continue;
+        };
+        let hwnd = HWND(window.hwnd as *mut c_void);
+        if !unsafe { IsWindow(hwnd) }.as_bool() {
+            continue;
+        }
+        let mut process_id = 0u32;
+        unsafe { GetWindowThreadProcessId(hwnd, Some(&mut process_id)) };
+        if process_id != window.process_id {
+            continue;
+        }
+        let state = window_state(hwnd);
+        match window_rect(hwnd, state) {
+            Some(current) if !contains_center(&monitor_rect, &current) => {}
+            _ => continue,
+        }
+
+        let x = monitor_rect.left + window.x;
+        let y = monitor_rect.top + window.y;
+        let result = match state {
+            WindowState::Normal => unsafe {
+                // Move first so a per-monitor DPI aware window rescales for the
+                // target monitor, then apply the saved size on top of that.
+                SetWindowPos(hwnd, None, x, y, 0, 0, SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE)
+                    .and_then(|_| SetWindowPos(hwnd, None, x, y, window.width, window.height, SWP_NOZORDER | SWP_NOACTIVATE))
+            },
+            _ => match placement(hwnd) {
+                Some(mut placement) => {
+                    placement.rcNormalPosition = RECT {
+                        left: x,
+                        top: y,
+                        right: x + window.width,
+                        bottom: y + window.height,
+                    };
+                    unsafe { SetWindowPlacement(hwnd, &placement) }
+                }
+                None => continue,
+            },
+        };
+        if let Err(e) = result {
+            log::debug!("Could not move window {:#x} back: {}", window.hwnd, e.message());
+        }
+    }
+}
+
+/// Whether the window's monitor is active in `monitors`, i.e. `restore` has
+/// had its chance to move it back.
+pub fn is_on_active_monitor(window: &SavedWindow, monitors: &[MonitorInfo]) -> bool {
+    monitors.iter().any(|m| m.is_active && m.matches_id(&window.monitor))
+}
+
+fn saved_windows_path(dir: &Path) -> PathBuf {
+    dir.join(SAVED_WINDOWS_FILE)
+}
+
+/// Persists window positions next to the monitor snapshot in `dir` so they
+/// can be put back after a crash.
+pub fn save_to_disk(dir: &Path, windows: &[SavedWindow]) {
+    let path = saved_windows_path(dir);
+    let result = serde_json::to_string_pretty(windows)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, content).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("Could not write {}: {}", path.display(), e);
+    }
+}
+
+pub fn load_from_disk(dir: &Path) -> Vec<SavedWindow> {
+    let path = saved_windows_path(dir);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+pub fn remove_file(dir: &Path) {
+    let path = saved_windows_path(dir);
+    if let Err(e) = fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+}